@@ -0,0 +1,462 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ntgcalls::{
+    preflight,
+    structures::{ChatId, GroupCall, MediaDescription, MediaState},
+    utils, NTgCall,
+};
+#[cfg(unix)]
+use ntgcalls_client::protocol;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A report that can be printed for humans.
+pub trait Report {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()>;
+
+    fn print(&self) {
+        let _ = self.write(&mut io::stdout().lock());
+    }
+}
+
+/// Result of a single diagnostic step.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step<T> {
+    Ok(T),
+    Error(String),
+}
+
+impl<T> Step<T> {
+    fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok(_))
+    }
+}
+
+impl<T, E: Display> From<Result<T, E>> for Step<T> {
+    fn from(value: Result<T, E>) -> Self {
+        match value {
+            Ok(v) => Self::Ok(v),
+            Err(e) => Self::Error(e.to_string()),
+        }
+    }
+}
+
+impl<T: Display> Display for Step<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok(v) => v.fmt(f),
+            Self::Error(e) => write!(f, "error: {e}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct VersionReport {
+    pub version: String,
+}
+
+pub fn version() -> VersionReport {
+    VersionReport {
+        version: NTgCall::version(),
+    }
+}
+
+impl Report for VersionReport {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "NTgCalls version: {}", self.version)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FfmpegReport {
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+}
+
+pub fn ffmpeg() -> FfmpegReport {
+    let path = utils::find_ffmpeg();
+
+    let version = path.as_deref().and_then(|path: &Path| {
+        let output = Command::new(path).arg("-version").output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout.lines().next().map(str::to_owned)
+    });
+
+    FfmpegReport { path, version }
+}
+
+impl Report for FfmpegReport {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        match (&self.path, &self.version) {
+            (Some(path), Some(version)) => writeln!(out, "ffmpeg: {} ({version})", path.display()),
+            (Some(path), None) => {
+                writeln!(out, "ffmpeg: {} (failed to get version)", path.display())
+            }
+            (None, _) => writeln!(out, "ffmpeg: not found in PATH"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ParamsReport {
+    pub chat_id: ChatId,
+    pub params: Step<Value>,
+}
+
+impl ParamsReport {
+    pub fn is_ok(&self) -> bool {
+        self.params.is_ok()
+    }
+}
+
+fn get_params(call: &NTgCall, chat_id: ChatId, desc: MediaDescription) -> Step<Value> {
+    call.get_params(chat_id, desc)
+        .map_err(|e| Box::new(e) as Box<dyn Error>)
+        .and_then(|params| Ok(serde_json::from_str(&params)?))
+        .into()
+}
+
+pub fn params(chat_id: ChatId, desc: MediaDescription) -> ParamsReport {
+    let call = NTgCall::new();
+
+    ParamsReport {
+        chat_id,
+        params: get_params(&call, chat_id, desc),
+    }
+}
+
+impl Report for ParamsReport {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        match &self.params {
+            Step::Ok(params) => writeln!(out, "{params}"),
+            Step::Error(e) => writeln!(out, "error: {e}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CheckReport {
+    pub version: String,
+    pub ffmpeg: FfmpegReport,
+    pub media_description: Step<()>,
+    pub preflight: Option<Step<()>>,
+    pub params: Option<ParamsReport>,
+    pub cpu_usage: Step<f64>,
+    pub calls: Step<Vec<GroupCall>>,
+    pub state: Option<Step<MediaState>>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.media_description.is_ok()
+            && self.preflight.as_ref().is_some_and(Step::is_ok)
+            && self.params.as_ref().is_some_and(ParamsReport::is_ok)
+            && self.cpu_usage.is_ok()
+            && self.calls.is_ok()
+            && self.state.as_ref().is_some_and(Step::is_ok)
+    }
+}
+
+/// Run all the checks with a new instance, the calls and the state are the ones
+/// of the test call created by this process, see [`status`] for a running daemon.
+pub fn check(chat_id: ChatId, desc: Result<MediaDescription, Box<dyn Error>>) -> CheckReport {
+    let call = NTgCall::new();
+
+    let (media_description, preflight, params) = match desc {
+        Ok(desc) => (
+            Step::Ok(()),
+            Some(preflight::preflight(&desc).into()),
+            Some(ParamsReport {
+                chat_id,
+                params: get_params(&call, chat_id, desc),
+            }),
+        ),
+        Err(e) => (Step::Error(e.to_string()), None, None),
+    };
+
+    let state = params
+        .as_ref()
+        .filter(|p| p.is_ok())
+        .map(|_| call.get_state(chat_id).into());
+
+    CheckReport {
+        version: version().version,
+        ffmpeg: ffmpeg(),
+        media_description,
+        preflight,
+        params,
+        cpu_usage: call.cpu_usage().into(),
+        calls: call.calls().into(),
+        state,
+    }
+}
+
+impl Report for CheckReport {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "NTgCalls version: {}", self.version)?;
+        self.ffmpeg.write(out)?;
+
+        match &self.media_description {
+            Step::Ok(()) => writeln!(out, "media description: ok")?,
+            Step::Error(e) => writeln!(out, "media description: error: {e}")?,
+        }
+
+        match &self.preflight {
+            Some(Step::Ok(())) => writeln!(out, "preflight: ok")?,
+            Some(Step::Error(e)) => writeln!(out, "preflight: error: {e}")?,
+            None => {}
+        }
+
+        if let Some(params) = &self.params {
+            match &params.params {
+                Step::Ok(_) => writeln!(out, "params: ok (chat {})", params.chat_id)?,
+                Step::Error(e) => writeln!(out, "params: error: {e}")?,
+            }
+        }
+
+        writeln!(out, "cpu usage: {}", self.cpu_usage)?;
+
+        match &self.calls {
+            Step::Ok(calls) => {
+                writeln!(out, "calls: {}", calls.len())?;
+
+                for call in calls {
+                    writeln!(out, "  - chat {}: {:?}", call.chat_id, call.stream_status)?;
+                }
+            }
+            Step::Error(e) => writeln!(out, "calls: error: {e}")?,
+        }
+
+        match &self.state {
+            Some(Step::Ok(state)) => writeln!(
+                out,
+                "state: muted={} video_paused={} video_stopped={}",
+                state.muted, state.video_paused, state.video_stopped
+            )?,
+            Some(Step::Error(e)) => writeln!(out, "state: error: {e}")?,
+            None => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// A call of a running daemon.
+#[cfg(unix)]
+#[derive(Serialize)]
+pub struct DaemonCall {
+    pub chat_id: i64,
+    pub stream_status: protocol::StreamStatus,
+    pub state: Step<protocol::MediaState>,
+}
+
+#[cfg(unix)]
+#[derive(Serialize)]
+pub struct StatusReport {
+    pub socket: PathBuf,
+    pub calls: Step<Vec<DaemonCall>>,
+}
+
+#[cfg(unix)]
+impl StatusReport {
+    pub fn is_ok(&self) -> bool {
+        self.calls.is_ok()
+    }
+}
+
+#[cfg(unix)]
+fn daemon_calls(socket: &Path) -> Result<Vec<DaemonCall>, Box<dyn Error>> {
+    let mut client = ntgcalls_client::Client::open(socket)?;
+
+    let calls = client.calls()?;
+    Ok(calls
+        .into_iter()
+        .map(|call| DaemonCall {
+            chat_id: call.chat_id,
+            stream_status: call.stream_status,
+            // the call may end between listing and querying it
+            state: client.get_state(call.chat_id).into(),
+        })
+        .collect())
+}
+
+/// Dump the calls and the media state of every call of a running `ntgcalls-daemon`.
+#[cfg(unix)]
+pub fn status(socket: PathBuf) -> StatusReport {
+    StatusReport {
+        calls: daemon_calls(&socket).into(),
+        socket,
+    }
+}
+
+#[cfg(unix)]
+impl Report for StatusReport {
+    fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "daemon: {}", self.socket.display())?;
+
+        match &self.calls {
+            Step::Ok(calls) => {
+                writeln!(out, "calls: {}", calls.len())?;
+
+                for call in calls {
+                    write!(out, "  - chat {}: {:?}, ", call.chat_id, call.stream_status)?;
+
+                    match &call.state {
+                        Step::Ok(state) => writeln!(
+                            out,
+                            "muted={} video_paused={} video_stopped={}",
+                            state.muted, state.video_paused, state.video_stopped
+                        )?,
+                        Step::Error(e) => writeln!(out, "state: error: {e}")?,
+                    }
+                }
+            }
+            Step::Error(e) => writeln!(out, "calls: error: {e}")?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ntgcalls::enums::StreamStatus;
+
+    use super::*;
+
+    fn output<R: Report>(report: &R) -> String {
+        let mut out = Vec::new();
+        report.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn check_report() {
+        let report = CheckReport {
+            version: String::from("1.2.3"),
+            ffmpeg: FfmpegReport {
+                path: None,
+                version: None,
+            },
+            media_description: Step::Ok(()),
+            preflight: Some(Step::Error(String::from("missing file"))),
+            params: Some(ParamsReport {
+                chat_id: ChatId::from_bot_api(-1001),
+                params: Step::Ok(Value::Null),
+            }),
+            cpu_usage: Step::Ok(1.5),
+            calls: Step::Ok(vec![GroupCall {
+                chat_id: ChatId::from_bot_api(-1001),
+                stream_status: StreamStatus::Playing,
+            }]),
+            state: None,
+        };
+
+        assert!(!report.is_ok());
+        assert_eq!(
+            output(&report),
+            "NTgCalls version: 1.2.3\n\
+             ffmpeg: not found in PATH\n\
+             media description: ok\n\
+             preflight: error: missing file\n\
+             params: ok (chat -1001)\n\
+             cpu usage: 1.5\n\
+             calls: 1\n  - chat -1001: Playing\n"
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json["preflight"],
+            serde_json::json!({ "error": "missing file" })
+        );
+        assert_eq!(json["cpu_usage"], serde_json::json!({ "ok": 1.5 }));
+    }
+
+    #[test]
+    fn params_report() {
+        let report = ParamsReport {
+            chat_id: ChatId::from_bot_api(1),
+            params: Step::Error(String::from("[Destroyed]: gone")),
+        };
+
+        assert!(!report.is_ok());
+        assert_eq!(output(&report), "error: [Destroyed]: gone\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn status_of_a_daemon() {
+        use std::{
+            io::{BufRead, BufReader},
+            os::unix::net::UnixListener,
+        };
+
+        use ntgcalls_client::protocol::{Request, Response, RpcError, NTGCALLS_ERROR};
+        use serde_json::json;
+
+        let socket = std::env::temp_dir().join(format!("ntgcalls-cli-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        // a daemon with a playing call and one that ends before its state is queried
+        let daemon = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+
+            for line in BufReader::new(stream).lines() {
+                let request: Request = serde_json::from_str(&line.unwrap()).unwrap();
+                let id = request.id.unwrap();
+
+                let response = match (request.method.as_str(), request.params["chat_id"].as_i64()) {
+                    ("calls", _) => Response::ok(
+                        id,
+                        json!([
+                            { "chat_id": -1001, "stream_status": "Playing" },
+                            { "chat_id": -1002, "stream_status": "Idling" },
+                        ]),
+                    ),
+                    ("get_state", Some(-1001)) => Response::ok(
+                        id,
+                        json!({ "muted": true, "video_paused": false, "video_stopped": true }),
+                    ),
+                    _ => Response::error(
+                        id,
+                        RpcError {
+                            code: NTGCALLS_ERROR,
+                            message: String::from("not found"),
+                            data: Some(json!("ConnectionNotFound")),
+                        },
+                    ),
+                };
+
+                writeln!(writer, "{}", serde_json::to_string(&response).unwrap()).unwrap();
+            }
+        });
+
+        let report = status(socket.clone());
+        drop(daemon);
+        std::fs::remove_file(&socket).unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(
+            output(&report),
+            format!(
+                "daemon: {}\n\
+                 calls: 2\n  \
+                 - chat -1001: Playing, muted=true video_paused=false video_stopped=true\n  \
+                 - chat -1002: Idling, state: error: [Rpc]: [1]: not found\n",
+                socket.display()
+            )
+        );
+
+        // nothing listens on the socket anymore
+        assert!(!status(socket).is_ok());
+    }
+}
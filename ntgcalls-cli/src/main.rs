@@ -0,0 +1,227 @@
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand};
+use ntgcalls::{
+    enums::InputMode,
+    profiles::{Profiles, StreamProfile},
+    structures::{ChatId, MediaDescription},
+};
+use serde::Serialize;
+
+mod checks;
+
+/// Diagnostics for the NTgCalls library on this host.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Print the output as JSON.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the version of the NTgCalls library.
+    Version,
+    /// Check whether ffmpeg is available on PATH.
+    Ffmpeg,
+    /// Validate a media description and print the connection params generated for it.
+    Params(MediaArgs),
+    /// Run all the checks and dump the calls and the media state of the test instance.
+    Check(MediaArgs),
+    /// Dump the calls and the media state of every call of a running ntgcalls-daemon.
+    #[cfg(unix)]
+    Status {
+        /// Path of the daemon socket.
+        #[arg(long, default_value_os_t = ntgcalls_client::protocol::default_socket_path())]
+        socket: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct MediaArgs {
+    /// Unique identifier of the chat used for the test call.
+    #[arg(long, default_value_t = ChatId::from_bot_api(-1001), allow_hyphen_values = true)]
+    chat_id: ChatId,
+    /// Name of the stream profile.
+    #[arg(long, default_value = "music")]
+    profile: String,
+    /// Load custom profiles from a TOML or JSON file.
+    #[arg(long)]
+    profiles: Option<PathBuf>,
+    /// Input mode of the media source (File, Shell or FFmpeg, optionally followed by |NoLatency).
+    #[arg(long, default_value = "FFmpeg")]
+    input_mode: InputMode,
+    /// Media source, without it the params are generated without any media.
+    #[arg(long)]
+    input: Option<String>,
+}
+
+impl MediaArgs {
+    fn profile(&self) -> Result<StreamProfile, Box<dyn Error>> {
+        let mut profiles = Profiles::builtin();
+
+        if let Some(path) = &self.profiles {
+            profiles.merge(Profiles::load(path)?);
+        }
+
+        let profile = profiles
+            .get(&self.profile)
+            .copied()
+            .ok_or_else(|| format!("unknown profile `{}`", self.profile))?;
+
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    fn media_description(&self) -> Result<MediaDescription, Box<dyn Error>> {
+        let profile = self.profile()?;
+
+        Ok(match &self.input {
+            Some(input) => profile.media_description(self.input_mode, input.as_str())?,
+            None => MediaDescription::default(),
+        })
+    }
+}
+
+fn print<T: Serialize + checks::Report>(json: bool, report: &T) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(report).expect("reports are always serializable")
+        );
+    } else {
+        report.print();
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let success = match cli.command {
+        Command::Version => {
+            print(cli.json, &checks::version());
+            true
+        }
+        Command::Ffmpeg => {
+            let report = checks::ffmpeg();
+            print(cli.json, &report);
+            report.path.is_some()
+        }
+        Command::Params(args) => match args.media_description() {
+            Ok(desc) => {
+                let report = checks::params(args.chat_id, desc);
+                print(cli.json, &report);
+                report.is_ok()
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                false
+            }
+        },
+        Command::Check(args) => {
+            let report = checks::check(args.chat_id, args.media_description());
+            print(cli.json, &report);
+            report.is_ok()
+        }
+        #[cfg(unix)]
+        Command::Status { socket } => {
+            let report = checks::status(socket);
+            print(cli.json, &report);
+            report.is_ok()
+        }
+    };
+
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn media_args(args: &[&str]) -> MediaArgs {
+        let cli = Cli::try_parse_from(["ntgcalls-cli", "check"].iter().chain(args)).unwrap();
+
+        match cli.command {
+            Command::Check(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parse_media_args() {
+        let args = media_args(&[]);
+        assert_eq!(args.chat_id, ChatId::from_bot_api(-1001));
+        assert_eq!(args.profile, "music");
+        assert_eq!(args.input_mode, InputMode::FFmpeg);
+        assert!(args.media_description().unwrap().audio.is_none());
+
+        let args = media_args(&[
+            "--chat-id",
+            "-1001234567890",
+            "--profile",
+            "720p",
+            "--input-mode",
+            "Shell|NoLatency",
+            "--input",
+            "cat video",
+        ]);
+        assert_eq!(args.chat_id, ChatId::from_channel(1234567890));
+        assert_eq!(args.input_mode, InputMode::Shell | InputMode::NoLatency);
+
+        let desc = args.media_description().unwrap();
+        assert_eq!(desc.video.unwrap().width, 1280);
+        assert_eq!(desc.audio.unwrap().input_mode, args.input_mode);
+
+        let args = media_args(&["--profile", "4k", "--input", "a.mp4"]);
+        assert_eq!(
+            args.media_description().unwrap_err().to_string(),
+            "unknown profile `4k`"
+        );
+
+        let args = media_args(&["--input", "a.mp4\0"]);
+        assert_eq!(
+            args.media_description().unwrap_err().to_string(),
+            "[Nul]: The string contains a nul byte at 5"
+        );
+    }
+
+    #[test]
+    fn parse_input_modes() {
+        let input_mode = |mode: &str| media_args(&["--input-mode", mode]).input_mode;
+        assert_eq!(input_mode("File"), InputMode::File);
+        assert_eq!(
+            input_mode("FFmpeg|NoLatency"),
+            InputMode::FFmpeg | InputMode::NoLatency
+        );
+
+        for mode in ["mp3", "File|Shell"] {
+            assert!(Cli::try_parse_from(["ntgcalls-cli", "params", "--input-mode", mode]).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_status() {
+        let cli = Cli::try_parse_from([
+            "ntgcalls-cli",
+            "--json",
+            "status",
+            "--socket",
+            "/run/a.sock",
+        ])
+        .unwrap();
+
+        assert!(cli.json);
+        assert!(
+            matches!(cli.command, Command::Status { socket } if socket == std::path::Path::new("/run/a.sock"))
+        );
+    }
+}
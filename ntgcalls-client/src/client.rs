@@ -0,0 +1,173 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::{ClientError, ClientResult},
+    protocol::{
+        ChatParams, ConnectParams, Event, GroupCall, MediaDescription, MediaState, Request,
+        Response, StreamParams,
+    },
+};
+
+/// A blocking connection to the daemon.
+///
+/// Each method mirrors the `NTgCall` method with the same name.
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Open a connection to the daemon listening on the given socket.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let writer = UnixStream::connect(path)?;
+
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            next_id: 0,
+        })
+    }
+
+    /// Call a method and wait for its result.
+    pub fn call<P: Serialize, T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> ClientResult<T> {
+        self.next_id += 1;
+
+        let request = Request::new(self.next_id, method, serde_json::to_value(params)?);
+        write_line(&mut self.writer, &request)?;
+
+        loop {
+            let line = read_line(&mut self.reader)?;
+
+            // notifications don't have an id, skip them
+            let value: Value = serde_json::from_str(&line)?;
+            if value.get("id").is_none() {
+                continue;
+            }
+
+            let response: Response = serde_json::from_value(value)?;
+
+            if request.id.as_ref() != Some(&response.id) {
+                continue;
+            }
+
+            return match (response.result, response.error) {
+                (_, Some(error)) => Err(ClientError::Rpc(error)),
+                (result, None) => Ok(serde_json::from_value(result.unwrap_or_default())?),
+            };
+        }
+    }
+
+    /// See `NTgCall::get_params`.
+    pub fn get_params(&mut self, chat_id: i64, desc: MediaDescription) -> ClientResult<String> {
+        self.call("get_params", StreamParams { chat_id, desc })
+    }
+
+    /// See `NTgCall::connect`.
+    pub fn connect<S: Into<String>>(&mut self, chat_id: i64, params: S) -> ClientResult<()> {
+        let params = params.into();
+        self.call("connect", ConnectParams { chat_id, params })
+    }
+
+    /// See `NTgCall::change_stream`.
+    pub fn change_stream(&mut self, chat_id: i64, desc: MediaDescription) -> ClientResult<()> {
+        self.call("change_stream", StreamParams { chat_id, desc })
+    }
+
+    /// See `NTgCall::pause`.
+    pub fn pause(&mut self, chat_id: i64) -> ClientResult<bool> {
+        self.call("pause", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::resume`.
+    pub fn resume(&mut self, chat_id: i64) -> ClientResult<bool> {
+        self.call("resume", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::mute`.
+    pub fn mute(&mut self, chat_id: i64) -> ClientResult<bool> {
+        self.call("mute", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::unmute`.
+    pub fn unmute(&mut self, chat_id: i64) -> ClientResult<bool> {
+        self.call("unmute", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::stop`.
+    pub fn stop(&mut self, chat_id: i64) -> ClientResult<()> {
+        self.call("stop", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::played_time`.
+    pub fn played_time(&mut self, chat_id: i64) -> ClientResult<i64> {
+        self.call("played_time", ChatParams { chat_id })
+    }
+
+    /// See `NTgCall::calls`.
+    pub fn calls(&mut self) -> ClientResult<Vec<GroupCall>> {
+        self.call("calls", Value::Null)
+    }
+
+    /// See `NTgCall::get_state`.
+    pub fn get_state(&mut self, chat_id: i64) -> ClientResult<MediaState> {
+        self.call("get_state", ChatParams { chat_id })
+    }
+
+    /// Subscribe to the callback events, the connection then only yields [`Event`]s.
+    pub fn subscribe(mut self) -> ClientResult<Subscription> {
+        self.call::<_, bool>("subscribe", Value::Null)?;
+
+        Ok(Subscription {
+            reader: self.reader,
+        })
+    }
+}
+
+/// A connection subscribed to the callback events, see [`Client::subscribe`].
+///
+/// The iterator ends when the daemon closes the connection.
+pub struct Subscription {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for Subscription {
+    type Item = ClientResult<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match read_line(&mut self.reader) {
+            Ok(line) => Some(serde_json::from_str(&line).map_err(ClientError::from)),
+            Err(ClientError::Closed) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn write_line<T: Serialize>(writer: &mut UnixStream, value: &T) -> ClientResult<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+
+    Ok(())
+}
+
+fn read_line(reader: &mut BufReader<UnixStream>) -> ClientResult<String> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Err(ClientError::Closed);
+    }
+
+    Ok(line)
+}
@@ -0,0 +1,84 @@
+use std::{error::Error, fmt::Display, io};
+
+use crate::protocol::RpcError;
+
+/// Result type alias for client errors.
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// All errors related to the daemon client.
+#[derive(Debug)]
+pub enum ClientError {
+    /// Reading from or writing to the socket failed.
+    Io(io::Error),
+    /// A message couldn't be encoded or decoded.
+    Json(serde_json::Error),
+    /// The daemon returned an error.
+    Rpc(RpcError),
+    /// The daemon closed the connection.
+    Closed,
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Rpc(e) => Some(e),
+            Self::Closed => None,
+        }
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "[Io]: {e}"),
+            Self::Json(e) => write!(f, "[Json]: {e}"),
+            Self::Rpc(e) => write!(f, "[Rpc]: {e}"),
+            Self::Closed => "[Closed]: The daemon closed the connection".fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+/// InputModeError represents an [`InputMode`](crate::protocol::InputMode) that NTgCalls can't use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputModeError {
+    /// None of the sources, `File`, `Shell` or `FFmpeg`, is set.
+    MissingSource,
+    /// More than one source is set, for example `File | Shell`.
+    ConflictingSources,
+    /// The name of a flag is unknown.
+    UnknownName(String),
+}
+
+impl Error for InputModeError {}
+
+impl Display for InputModeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSource => write!(
+                f,
+                "[MissingSource]: The input mode must set one of File, Shell or FFmpeg"
+            ),
+            Self::ConflictingSources => write!(
+                f,
+                "[ConflictingSources]: The input mode must set only one of File, Shell or FFmpeg"
+            ),
+            Self::UnknownName(name) => {
+                write!(f, "[UnknownName]: The input mode `{name}` is unknown")
+            }
+        }
+    }
+}
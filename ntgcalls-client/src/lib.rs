@@ -0,0 +1,12 @@
+//! Client for the `ntgcalls-daemon` JSON-RPC API.
+//!
+//! The daemon owns the NTgCalls instances so this crate doesn't link the native library,
+//! the [`protocol`] module documents the wire format for clients in other languages.
+
+#[cfg(unix)]
+pub use client::{Client, Subscription};
+
+#[cfg(unix)]
+mod client;
+pub mod errors;
+pub mod protocol;
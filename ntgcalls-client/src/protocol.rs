@@ -0,0 +1,522 @@
+//! Wire format of the daemon API.
+//!
+//! Every message is a single line of JSON following [JSON-RPC 2.0](https://www.jsonrpc.org/specification).
+//! The types in this module mirror the serde representation of the `ntgcalls` types so the
+//! client doesn't need to link the native library.
+
+use std::{
+    env,
+    fmt::Display,
+    ops::{BitOr, BitOrAssign},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::InputModeError;
+
+/// Version of the JSON-RPC protocol.
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Invalid JSON was received.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters.
+pub const INVALID_PARAMS: i64 = -32602;
+/// NTgCalls returned an error, the `data` of the error is the name of the `NTgCallError` variant.
+pub const NTGCALLS_ERROR: i64 = 1;
+
+/// Default path of the daemon socket.
+///
+/// On Unix it's `$XDG_RUNTIME_DIR/ntgcalls.sock`, a directory only the user can write to,
+/// falling back to `ntgcalls-$UID.sock` in the temporary directory when it's not set.
+/// Elsewhere it's `ntgcalls.sock` in the temporary directory, which is already per user.
+pub fn default_socket_path() -> PathBuf {
+    #[cfg(unix)]
+    {
+        match env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+            Some(dir) if dir.is_absolute() => dir.join("ntgcalls.sock"),
+            // SAFETY: getuid has no preconditions and never fails.
+            _ => env::temp_dir().join(format!("ntgcalls-{}.sock", unsafe { libc::getuid() })),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        env::temp_dir().join("ntgcalls.sock")
+    }
+}
+
+/// A method call, or a notification when it has no id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    /// Always [`JSONRPC_VERSION`].
+    pub jsonrpc: String,
+    /// Identifier echoed back in the [`Response`], notifications don't have any and get no response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    /// Name of the method.
+    pub method: String,
+    /// Parameters of the method.
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl Request {
+    /// Create a new request.
+    pub fn new<S: Into<String>>(id: u64, method: S, params: Value) -> Self {
+        Self {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id: Some(Value::from(id)),
+            method: method.into(),
+            params,
+        }
+    }
+
+    /// Create a new notification, which gets no response.
+    pub fn notification<S: Into<String>>(method: S, params: Value) -> Self {
+        Self {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id: None,
+            method: method.into(),
+            params,
+        }
+    }
+
+    /// Whether the request is a notification.
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// Result of a method call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    /// Always [`JSONRPC_VERSION`].
+    pub jsonrpc: String,
+    /// Identifier of the [`Request`].
+    pub id: Value,
+    /// Result of the call in case of success.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Error of the call in case of failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    /// Create a successful response.
+    pub fn ok(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// Create a failed response.
+    pub fn error(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Error of a method call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    /// Error code, see the constants of this module.
+    pub code: i64,
+    /// Human readable description of the error.
+    pub message: String,
+    /// Additional information about the error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// Create a new error.
+    pub fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Name of the `NTgCallError` variant if this is a [`NTGCALLS_ERROR`].
+    pub fn kind(&self) -> Option<&str> {
+        match self.code {
+            NTGCALLS_ERROR => self.data.as_ref().and_then(Value::as_str),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}]: {}", self.code, self.message)
+    }
+}
+
+/// Parameters of the methods that only need a chat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatParams {
+    /// Unique identifier of a chat.
+    pub chat_id: i64,
+}
+
+/// Parameters of the `connect` method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectParams {
+    /// Unique identifier of a chat.
+    pub chat_id: i64,
+    /// Connection params obtained from Telegram.
+    pub params: String,
+}
+
+/// Parameters of the `get_params` and `change_stream` methods.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamParams {
+    /// Unique identifier of a chat.
+    pub chat_id: i64,
+    /// Media parameters of the stream.
+    pub desc: MediaDescription,
+}
+
+/// Input modes for audio and video streams.
+///
+/// Like in `ntgcalls`, a mode is made of exactly one source, [`InputMode::File`], [`InputMode::Shell`]
+/// or [`InputMode::FFmpeg`], optionally combined with [`InputMode::NoLatency`], and it's sent as the
+/// names of its flags separated by `|`:
+///
+/// ```
+/// use ntgcalls_client::protocol::InputMode;
+///
+/// let mode = InputMode::FFmpeg | InputMode::NoLatency;
+/// assert_eq!(mode.to_string(), "FFmpeg|NoLatency");
+/// assert_eq!("FFmpeg|NoLatency".parse(), Ok(mode));
+///
+/// assert!(!(InputMode::File | InputMode::Shell).is_valid());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputMode(u8);
+
+#[allow(non_upper_case_globals)]
+impl InputMode {
+    /// Input mode using a file source.
+    pub const File: Self = Self(1 << 0);
+    /// Input mode using a shell command.
+    pub const Shell: Self = Self(1 << 1);
+    /// Input mode using FFmpeg.
+    pub const FFmpeg: Self = Self(1 << 2);
+    /// Exprimental mode, combined with one of the sources.
+    pub const NoLatency: Self = Self(1 << 3);
+}
+
+impl InputMode {
+    const SOURCES: [Self; 3] = [Self::File, Self::Shell, Self::FFmpeg];
+    const FLAGS: [(Self, &'static str); 4] = [
+        (Self::File, "File"),
+        (Self::Shell, "Shell"),
+        (Self::FFmpeg, "FFmpeg"),
+        (Self::NoLatency, "NoLatency"),
+    ];
+
+    /// Whether all the flags of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check that the mode has exactly one source.
+    ///
+    /// Combining modes with `|` doesn't check them, for example `File | Shell` is not valid.
+    pub fn validate(self) -> Result<Self, InputModeError> {
+        match Self::SOURCES
+            .iter()
+            .filter(|source| self.contains(**source))
+            .count()
+        {
+            0 => Err(InputModeError::MissingSource),
+            1 => Ok(self),
+            _ => Err(InputModeError::ConflictingSources),
+        }
+    }
+
+    /// Whether the mode has exactly one source, see [`InputMode::validate`].
+    pub fn is_valid(self) -> bool {
+        self.validate().is_ok()
+    }
+}
+
+impl BitOr for InputMode {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for InputMode {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Display for InputMode {
+    /// The names of the flags separated by `|`, like `FFmpeg|NoLatency`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = Self::FLAGS
+            .iter()
+            .filter(|(mode, _)| self.contains(*mode))
+            .map(|(_, name)| *name);
+
+        if let Some(first) = names.next() {
+            f.write_str(first)?;
+        }
+        names.try_for_each(|name| write!(f, "|{name}"))
+    }
+}
+
+impl std::fmt::Debug for InputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InputMode({self})")
+    }
+}
+
+impl FromStr for InputMode {
+    type Err = InputModeError;
+
+    /// Parse the names of the flags separated by `|`, the format of [`Display`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('|')
+            .map(str::trim)
+            .try_fold(Self(0), |mode, name| {
+                Self::FLAGS
+                    .iter()
+                    .find(|(_, flag)| *flag == name)
+                    .map(|(flag, _)| mode | *flag)
+                    .ok_or_else(|| InputModeError::UnknownName(name.to_owned()))
+            })?
+            .validate()
+    }
+}
+
+impl Serialize for InputMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InputMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Media Configuration for the Stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaDescription {
+    /// Audio configuration for the stream.
+    pub audio: Option<AudioDescription>,
+    /// Video configuration for the stream.
+    pub video: Option<VideoDescription>,
+}
+
+/// Stream’s Audio Configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioDescription {
+    /// The input mode for audio.
+    pub input_mode: InputMode,
+    /// The input media source.
+    pub input: String,
+    /// Audio sample rate (0-96000, max allowed by PCM16L).
+    pub sample_rate: u32,
+    /// Audio bits per sample (8 or 16).
+    pub bits_per_sample: u8,
+    /// Audio channel count (1-2, max allowed by PCM16L).
+    pub channel_count: u8,
+}
+
+/// Stream’s Video Configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoDescription {
+    /// The input mode for video.
+    pub input_mode: InputMode,
+    /// The input media source.
+    pub input: String,
+    /// Video width in pixels.
+    pub width: u16,
+    /// Video height in pixels.
+    pub height: u16,
+    /// Frames per second (FPS) for video playback.
+    pub fps: u8,
+}
+
+/// Stream status values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamStatus {
+    /// Indicates that the stream is currently playing.
+    Playing,
+    /// Indicates that the stream is currently paused.
+    Paused,
+    /// Indicates that the stream is currently idling (neither playing nor paused).
+    Idling,
+    /// A status unknown to the daemon, reported by a newer version of NTgCalls.
+    Unknown(i32),
+}
+
+/// Stream type values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamType {
+    /// Indicates that the stream type is audio.
+    Audio,
+    /// Indicates that the stream type is video.
+    Video,
+}
+
+/// Info about a group call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupCall {
+    /// Unique identifier of a chat.
+    pub chat_id: i64,
+    /// Stream’s Status.
+    pub stream_status: StreamStatus,
+}
+
+/// GroupCall MTProto Configurations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaState {
+    /// Indicates whether the media stream is muted.
+    pub muted: bool,
+    /// Indicates whether the video stream is paused.
+    pub video_paused: bool,
+    /// Indicates whether the video stream is stopped.
+    pub video_stopped: bool,
+}
+
+/// Events sent as notifications to the connections that called the `subscribe` method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Event {
+    /// A stream ended.
+    StreamEnd {
+        /// Unique identifier of a chat.
+        chat_id: i64,
+        /// Type of the ended stream.
+        stream_type: StreamType,
+    },
+    /// The media state of a call changed.
+    Upgrade {
+        /// Unique identifier of a chat.
+        chat_id: i64,
+        /// The new media state.
+        state: MediaState,
+    },
+    /// A call got disconnected.
+    Disconnect {
+        /// Unique identifier of a chat.
+        chat_id: i64,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn event_is_a_notification() {
+        let event = Event::StreamEnd {
+            chat_id: -1001,
+            stream_type: StreamType::Video,
+        };
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            json!({ "method": "stream_end", "params": { "chat_id": -1001, "stream_type": "Video" } })
+        );
+
+        let line = r#"{"jsonrpc":"2.0","method":"disconnect","params":{"chat_id":5}}"#;
+        assert_eq!(
+            serde_json::from_str::<Event>(line).unwrap(),
+            Event::Disconnect { chat_id: 5 }
+        );
+    }
+
+    #[test]
+    fn request_without_id_is_a_notification() {
+        let request: Request =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","method":"stop","params":{"chat_id":5}}"#)
+                .unwrap();
+        assert!(request.is_notification());
+
+        assert_eq!(
+            serde_json::to_value(Request::notification("calls", Value::Null)).unwrap(),
+            json!({ "jsonrpc": "2.0", "method": "calls", "params": null })
+        );
+        assert!(!Request::new(1, "calls", Value::Null).is_notification());
+    }
+
+    #[test]
+    fn input_mode_names() {
+        let mode = InputMode::FFmpeg | InputMode::NoLatency;
+        assert_eq!(
+            serde_json::to_value(mode).unwrap(),
+            json!("FFmpeg|NoLatency")
+        );
+        assert_eq!(
+            serde_json::from_value::<InputMode>(json!("Shell")).unwrap(),
+            InputMode::Shell
+        );
+
+        assert_eq!(
+            "File|Shell".parse::<InputMode>(),
+            Err(InputModeError::ConflictingSources)
+        );
+        assert_eq!(
+            "NoLatency".parse::<InputMode>(),
+            Err(InputModeError::MissingSource)
+        );
+        assert!(serde_json::from_value::<InputMode>(json!("Mp3")).is_err());
+    }
+
+    #[test]
+    fn error_kind() {
+        let error = RpcError {
+            code: NTGCALLS_ERROR,
+            message: String::from("not found"),
+            data: Some(json!("ConnectionNotFound")),
+        };
+
+        assert_eq!(error.kind(), Some("ConnectionNotFound"));
+        assert_eq!(RpcError::new(METHOD_NOT_FOUND, "nope").kind(), None);
+    }
+
+    #[test]
+    fn stream_status() {
+        // the known statuses are encoded as before
+        let calls: Vec<GroupCall> = serde_json::from_value(json!([
+            { "chat_id": 1, "stream_status": "Playing" },
+            { "chat_id": 2, "stream_status": { "Unknown": 7 } },
+        ]))
+        .unwrap();
+
+        assert_eq!(calls[0].stream_status, StreamStatus::Playing);
+        assert_eq!(calls[1].stream_status, StreamStatus::Unknown(7));
+    }
+}
@@ -0,0 +1,87 @@
+//! Daemon exposing NTgCalls over a local JSON-RPC socket.
+//!
+//! See the `ntgcalls-client` crate for the protocol and a Rust client.
+
+#[cfg(unix)]
+mod server;
+
+#[cfg(unix)]
+fn main() -> std::process::ExitCode {
+    use std::{
+        os::unix::{fs::FileTypeExt, net::UnixListener},
+        path::PathBuf,
+        process::ExitCode,
+    };
+
+    use clap::Parser;
+    use ntgcalls::pool::NTgCallPool;
+
+    /// Expose NTgCalls over a local JSON-RPC socket.
+    #[derive(Parser)]
+    #[command(version, about)]
+    struct Args {
+        /// Path of the Unix socket to listen on.
+        #[arg(long, default_value_os_t = ntgcalls_client::protocol::default_socket_path())]
+        socket: PathBuf,
+        /// Number of NTgCalls instances the chats are shared between.
+        #[arg(long, default_value_t = 1)]
+        instances: usize,
+    }
+
+    let args = Args::parse();
+
+    if args.instances == 0 {
+        eprintln!("error: at least one instance is needed");
+        return ExitCode::FAILURE;
+    }
+
+    // remove the socket left by a previous run, but never a file that isn't a socket
+    if let Ok(metadata) = std::fs::symlink_metadata(&args.socket) {
+        if !metadata.file_type().is_socket() {
+            eprintln!(
+                "error: {} exists and is not a socket, refusing to remove it",
+                args.socket.display()
+            );
+            return ExitCode::FAILURE;
+        }
+
+        if let Err(e) = std::fs::remove_file(&args.socket) {
+            eprintln!("error: failed to remove {}: {e}", args.socket.display());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let listener = match UnixListener::bind(&args.socket) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: failed to bind {}: {e}", args.socket.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let server = match server::Server::new(NTgCallPool::new(args.instances)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("error: failed to register the callbacks: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    eprintln!("listening on {}", args.socket.display());
+
+    match server.serve(listener) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() -> std::process::ExitCode {
+    eprintln!(
+        "error: ntgcalls-daemon needs Unix sockets, which are not supported on this platform"
+    );
+    std::process::ExitCode::FAILURE
+}
@@ -0,0 +1,329 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use ntgcalls::{
+    errors::NTgCallError,
+    pool::NTgCallPool,
+    structures::{ChatId, MediaDescription, MediaState},
+};
+use ntgcalls_client::protocol::{
+    Request, Response, RpcError, INVALID_PARAMS, INVALID_REQUEST, JSONRPC_VERSION,
+    METHOD_NOT_FOUND, NTGCALLS_ERROR, PARSE_ERROR,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Parameters of the methods, using the `ntgcalls` types directly.
+#[derive(Deserialize)]
+struct ChatParams {
+    chat_id: ChatId,
+}
+
+#[derive(Deserialize)]
+struct ConnectParams {
+    chat_id: ChatId,
+    params: String,
+}
+
+#[derive(Deserialize)]
+struct StreamParams {
+    chat_id: ChatId,
+    desc: MediaDescription,
+}
+
+/// A notification ready to be written to the subscribers.
+type Notification = Arc<str>;
+
+/// The daemon state shared by all the connections.
+pub struct Server {
+    pool: NTgCallPool,
+    subscribers: Mutex<Vec<Sender<Notification>>>,
+}
+
+impl Server {
+    /// Create a new server and register the callbacks of the pool.
+    pub fn new(pool: NTgCallPool) -> Result<Arc<Self>, NTgCallError> {
+        let server = Arc::new(Self {
+            pool,
+            subscribers: Mutex::default(),
+        });
+
+        // the callbacks only keep a weak reference so the server can be dropped
+        let weak = Arc::downgrade(&server);
+        server.pool.on_stream_end(move |chat_id, stream_type| {
+            if let Some(server) = weak.upgrade() {
+                server.notify(
+                    "stream_end",
+                    json!({ "chat_id": chat_id, "stream_type": stream_type }),
+                );
+            }
+        })?;
+
+        let weak = Arc::downgrade(&server);
+        server.pool.on_upgrade(move |chat_id, state: MediaState| {
+            if let Some(server) = weak.upgrade() {
+                server.notify("upgrade", json!({ "chat_id": chat_id, "state": state }));
+            }
+        })?;
+
+        let weak = Arc::downgrade(&server);
+        server.pool.on_disconnect(move |chat_id| {
+            if let Some(server) = weak.upgrade() {
+                server.notify("disconnect", json!({ "chat_id": chat_id }));
+            }
+        })?;
+
+        Ok(server)
+    }
+
+    /// Accept connections forever, every connection is handled on its own thread.
+    pub fn serve(self: &Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+
+            thread::spawn(move || {
+                if let Err(e) = server.handle(stream) {
+                    eprintln!("connection failed: {e}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Send a notification to all the subscribers, dropping the ones that are gone.
+    fn notify(&self, method: &str, params: Value) {
+        let notification: Notification = json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "method": method,
+            "params": params,
+        })
+        .to_string()
+        .into();
+
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|subscriber| subscriber.send(notification.clone()).is_ok());
+    }
+
+    fn handle(&self, stream: UnixStream) -> io::Result<()> {
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&line) {
+                Err(e) => Response::error(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+                Ok(value) => match serde_json::from_value::<Request>(value) {
+                    Err(e) => {
+                        Response::error(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string()))
+                    }
+                    Ok(request) if request.jsonrpc != JSONRPC_VERSION => Response::error(
+                        request.id.unwrap_or(Value::Null),
+                        RpcError::new(
+                            INVALID_REQUEST,
+                            format!("unsupported JSON-RPC version `{}`", request.jsonrpc),
+                        ),
+                    ),
+                    Ok(request) => {
+                        let result = if request.method == "subscribe" {
+                            self.subscribe(writer.clone());
+                            Ok(Value::Bool(true))
+                        } else {
+                            self.dispatch(&request.method, request.params)
+                        };
+
+                        // notifications are executed but never answered
+                        let Some(id) = request.id else {
+                            continue;
+                        };
+
+                        match result {
+                            Ok(result) => Response::ok(id, result),
+                            Err(error) => Response::error(id, error),
+                        }
+                    }
+                },
+            };
+
+            write_line(&writer, &serde_json::to_string(&response)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forward the notifications to the connection until writing to it fails.
+    fn subscribe(&self, writer: Arc<Mutex<UnixStream>>) {
+        let (sender, receiver) = mpsc::channel::<Notification>();
+
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+
+        thread::spawn(move || {
+            for notification in receiver {
+                if write_line(&writer, &notification).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let pool = &self.pool;
+
+        match method {
+            "get_params" => {
+                let StreamParams { chat_id, desc } = parse(params)?;
+                result(pool.get_params(chat_id, desc))
+            }
+            "connect" => {
+                let ConnectParams { chat_id, params } = parse(params)?;
+                result(pool.connect(chat_id, params))
+            }
+            "change_stream" => {
+                let StreamParams { chat_id, desc } = parse(params)?;
+                result(pool.change_stream(chat_id, desc))
+            }
+            "pause" => result(pool.pause(parse::<ChatParams>(params)?.chat_id)),
+            "resume" => result(pool.resume(parse::<ChatParams>(params)?.chat_id)),
+            "mute" => result(pool.mute(parse::<ChatParams>(params)?.chat_id)),
+            "unmute" => result(pool.unmute(parse::<ChatParams>(params)?.chat_id)),
+            "stop" => result(pool.stop(parse::<ChatParams>(params)?.chat_id)),
+            "played_time" => result(pool.played_time(parse::<ChatParams>(params)?.chat_id)),
+            "get_state" => result(pool.get_state(parse::<ChatParams>(params)?.chat_id)),
+            "calls" => result(pool.calls()),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{method}`"),
+            )),
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn result<T: Serialize>(result: Result<T, NTgCallError>) -> Result<Value, RpcError> {
+    match result {
+        Ok(value) => Ok(serde_json::to_value(value).expect("results are always serializable")),
+        Err(e) => Err(RpcError {
+            code: NTGCALLS_ERROR,
+            message: e.to_string(),
+            data: Some(Value::from(e.name())),
+        }),
+    }
+}
+
+fn write_line(writer: &Mutex<UnixStream>, line: &str) -> io::Result<()> {
+    let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod test {
+    use ntgcalls_client::{
+        protocol::{
+            AudioDescription, Event, InputMode, MediaDescription, StreamStatus, StreamType,
+        },
+        Client,
+    };
+
+    use super::*;
+
+    #[test]
+    fn client_round_trip() {
+        let path = std::env::temp_dir().join(format!("ntgcalls-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let server = Server::new(NTgCallPool::new(2)).unwrap();
+        let listener = UnixListener::bind(&path).unwrap();
+        {
+            let server = server.clone();
+            thread::spawn(move || server.serve(listener));
+        }
+
+        let mut client = Client::open(&path).unwrap();
+        let subscription = Client::open(&path).unwrap().subscribe().unwrap();
+
+        let desc = MediaDescription {
+            audio: Some(AudioDescription {
+                input_mode: InputMode::FFmpeg,
+                input: String::from("music.mp3"),
+                sample_rate: 48000,
+                bits_per_sample: 16,
+                channel_count: 2,
+            }),
+            video: None,
+        };
+
+        assert!(!client.get_params(-1001, desc).unwrap().is_empty());
+        client.connect(-1001, "{}").unwrap();
+        assert!(client.mute(-1001).unwrap());
+        assert!(client.get_state(-1001).unwrap().muted);
+        assert_eq!(
+            client.calls().unwrap()[0].stream_status,
+            StreamStatus::Playing
+        );
+
+        let error = client.pause(42).unwrap_err();
+        assert!(
+            matches!(error, ntgcalls_client::errors::ClientError::Rpc(ref e) if e.kind() == Some("ConnectionNotFound"))
+        );
+
+        // events are forwarded to the subscribers
+        server.notify(
+            "stream_end",
+            json!({ "chat_id": -1001, "stream_type": ntgcalls::enums::StreamType::Audio }),
+        );
+        assert_eq!(
+            subscription.take(1).next().unwrap().unwrap(),
+            Event::StreamEnd {
+                chat_id: -1001,
+                stream_type: StreamType::Audio
+            }
+        );
+
+        // notifications get no response, the next line is the response of `calls`
+        let mut raw = UnixStream::connect(&path).unwrap();
+        let notification = Request::notification("stop", json!({ "chat_id": -1001 }));
+        let request = Request::new(7, "calls", Value::Null);
+        for request in [notification, request] {
+            writeln!(raw, "{}", serde_json::to_string(&request).unwrap()).unwrap();
+        }
+
+        let mut line = String::new();
+        BufReader::new(raw).read_line(&mut line).unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert_eq!(response.id, Value::from(7));
+        assert_eq!(response.result, Some(json!([])));
+
+        // other versions of the protocol are rejected
+        let mut raw = UnixStream::connect(&path).unwrap();
+        writeln!(raw, r#"{{"jsonrpc":"1.0","id":8,"method":"calls"}}"#).unwrap();
+
+        let mut line = String::new();
+        BufReader::new(raw).read_line(&mut line).unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert_eq!(response.id, Value::from(8));
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
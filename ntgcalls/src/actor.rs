@@ -0,0 +1,213 @@
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Sender},
+    thread::{self, ThreadId},
+};
+
+use crate::{
+    enums::StreamType,
+    errors::NTgCallResult,
+    filters::Volume,
+    structures::{ChatId, GroupCall, MediaDescription, MediaState},
+    utils::IntoCString,
+    NTgCall,
+};
+
+type Command = Box<dyn FnOnce(&NTgCall) + Send>;
+
+/// NTgCallActor owns a [`NTgCall`] instance on a dedicated worker thread.
+///
+/// All the calls into NTgCalls are sent to the worker thread through a channel and
+/// executed one at a time, so the native instance is never used concurrently and is
+/// always initialized, used and destroyed on the same thread.
+///
+/// The handle is cheap to clone, the worker thread stops and destroys the instance
+/// when the last handle is dropped, even if a clone of the instance escaped from
+/// [`NTgCallActor::execute`].
+///
+/// The instance is bound to the worker thread: using a clone of it that escaped from
+/// [`NTgCallActor::execute`] on another thread panics instead of racing with the worker.
+///
+/// ## Note
+/// The callbacks registered with the `on_*` methods are called by NTgCalls from its own
+/// threads, not from the worker thread.
+#[derive(Clone)]
+pub struct NTgCallActor {
+    sender: Sender<Command>,
+    worker: ThreadId,
+}
+
+/// Initialization and execution
+impl NTgCallActor {
+    /// Spawn a new worker thread and initialize a [`NTgCall`] instance on it.
+    ///
+    /// ## Return
+    /// A [`io::Error`] if the worker thread can't be spawned.
+    pub fn spawn() -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Command>();
+
+        let worker = thread::Builder::new()
+            .name(String::from("ntgcalls-actor"))
+            .spawn(move || {
+                let call = NTgCall::new();
+                let _ = call.inner.owner.set(thread::current().id());
+
+                for command in receiver {
+                    command(&call);
+                }
+
+                // the loop ends when all the handles are dropped, the instance is destroyed
+                // here even if a clone escaped from `execute`, so it's never destroyed by
+                // another thread
+                let _ = call.inner.destroy();
+            })?;
+
+        Ok(Self {
+            sender,
+            worker: worker.thread().id(),
+        })
+    }
+
+    /// Run the given function with the [`NTgCall`] instance on the worker thread and wait for its result.
+    ///
+    /// The instance must only be used inside the function, cloning it out of the function
+    /// doesn't give access to it from other threads. Inside the function use the given
+    /// instance instead of calling the actor again, the worker runs one function at a time
+    /// so it can't wait for itself.
+    ///
+    /// ## Panics
+    /// - If the function panics, the panic is resumed on the calling thread.
+    /// - If it's called from the worker thread, for example from inside another function
+    ///   given to [`NTgCallActor::execute`], since waiting for the result would deadlock.
+    pub fn execute<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&NTgCall) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        assert_ne!(
+            thread::current().id(),
+            self.worker,
+            "NTgCallActor::execute can't be called from its own worker thread"
+        );
+
+        let (reply, result) = mpsc::sync_channel(1);
+
+        let command: Command = Box::new(move |call| {
+            // catch the panic so a single failing command doesn't stop the worker
+            let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(|| f(call))));
+        });
+
+        self.sender
+            .send(command)
+            .expect("NTgCall actor thread has stopped");
+
+        match result.recv().expect("NTgCall actor thread has stopped") {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Forwarded methods, see [`NTgCall`] for the details of each method.
+impl NTgCallActor {
+    /// See [`NTgCall::get_params`].
+    pub fn get_params(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<String> {
+        self.execute(move |call| call.get_params(chat_id, desc))
+    }
+
+    /// See [`NTgCall::connect`].
+    pub fn connect<S: IntoCString>(&self, chat_id: ChatId, params: S) -> NTgCallResult<()> {
+        let params = params.try_into_c_string()?;
+        self.execute(move |call| call.connect(chat_id, params))
+    }
+
+    /// See [`NTgCall::stop`].
+    pub fn stop(&self, chat_id: ChatId) -> NTgCallResult<()> {
+        self.execute(move |call| call.stop(chat_id))
+    }
+
+    /// See [`NTgCall::change_stream`].
+    pub fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<()> {
+        self.execute(move |call| call.change_stream(chat_id, desc))
+    }
+
+    /// See [`NTgCall::mute`].
+    pub fn mute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.execute(move |call| call.mute(chat_id))
+    }
+
+    /// See [`NTgCall::pause`].
+    pub fn pause(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.execute(move |call| call.pause(chat_id))
+    }
+
+    /// See [`NTgCall::resume`].
+    pub fn resume(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.execute(move |call| call.resume(chat_id))
+    }
+
+    /// See [`NTgCall::played_time`].
+    pub fn played_time(&self, chat_id: ChatId) -> NTgCallResult<i64> {
+        self.execute(move |call| call.played_time(chat_id))
+    }
+
+    /// See [`NTgCall::unmute`].
+    pub fn unmute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.execute(move |call| call.unmute(chat_id))
+    }
+
+    /// See [`NTgCall::count_calls`].
+    pub fn count_calls(&self) -> NTgCallResult<i32> {
+        self.execute(NTgCall::count_calls)
+    }
+
+    /// See [`NTgCall::volume`].
+    pub fn volume(&self, chat_id: ChatId) -> NTgCallResult<Volume> {
+        self.execute(move |call| call.volume(chat_id))
+    }
+
+    /// See [`NTgCall::set_volume`].
+    pub fn set_volume(&self, chat_id: ChatId, volume: f32) -> NTgCallResult<()> {
+        self.execute(move |call| call.set_volume(chat_id, volume))
+    }
+
+    /// See [`NTgCall::cpu_usage`].
+    pub fn cpu_usage(&self) -> NTgCallResult<f64> {
+        self.execute(NTgCall::cpu_usage)
+    }
+
+    /// See [`NTgCall::calls`].
+    pub fn calls(&self) -> NTgCallResult<Vec<GroupCall>> {
+        self.execute(NTgCall::calls)
+    }
+
+    /// See [`NTgCall::get_state`].
+    pub fn get_state(&self, chat_id: ChatId) -> NTgCallResult<MediaState> {
+        self.execute(move |call| call.get_state(chat_id))
+    }
+
+    /// See [`NTgCall::on_stream_end`].
+    pub fn on_stream_end<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, StreamType) + Send + Sync + 'static,
+    {
+        self.execute(move |call| call.on_stream_end(callback))
+    }
+
+    /// See [`NTgCall::on_upgrade`].
+    pub fn on_upgrade<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, MediaState) + Send + Sync + 'static,
+    {
+        self.execute(move |call| call.on_upgrade(callback))
+    }
+
+    /// See [`NTgCall::on_disconnect`].
+    pub fn on_disconnect<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId) + Send + Sync + 'static,
+    {
+        self.execute(move |call| call.on_disconnect(callback))
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+use libntgcalls_sys::{ntg_media_state_struct, ntg_stream_type_enum};
+
+use crate::{
+    enums::StreamType,
+    filters::Volumes,
+    structures::{ChatId, MediaState},
+};
+
+pub(crate) type StreamEndCallback = Arc<dyn Fn(ChatId, StreamType) + Send + Sync>;
+pub(crate) type UpgradeCallback = Arc<dyn Fn(ChatId, MediaState) + Send + Sync>;
+pub(crate) type DisconnectCallback = Arc<dyn Fn(ChatId) + Send + Sync>;
+
+/// Callbacks registered for a single NTgCalls instance.
+#[derive(Default)]
+pub(crate) struct Callbacks {
+    pub stream_end: Option<StreamEndCallback>,
+    pub upgrade: Option<UpgradeCallback>,
+    pub disconnect: Option<DisconnectCallback>,
+    /// Volumes of the instance, the volume of a chat is reset when its call gets disconnected.
+    pub volumes: Arc<Volumes>,
+}
+
+/// The C API doesn't let us pass any user data with the callbacks, so we keep
+/// a global registry keyed by the instance UID and look them up from the trampolines.
+fn registry() -> MutexGuard<'static, HashMap<u32, Callbacks>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<u32, Callbacks>>> = OnceLock::new();
+
+    REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Update the callbacks registered for the given uid.
+pub(crate) fn update(uid: u32, f: impl FnOnce(&mut Callbacks)) {
+    f(registry().entry(uid).or_default());
+}
+
+/// The upgrade callback registered for the given uid.
+pub(crate) fn upgrade(uid: u32) -> Option<UpgradeCallback> {
+    registry().get(&uid).and_then(|c| c.upgrade.clone())
+}
+
+/// Remove all the callbacks registered for the given uid.
+pub(crate) fn remove(uid: u32) {
+    registry().remove(&uid);
+}
+
+pub(crate) unsafe extern "C" fn stream_end_trampoline(
+    uid: u32,
+    chat_id: i64,
+    stream_type: ntg_stream_type_enum,
+) {
+    let stream_type = StreamType::from_ffi(stream_type);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(uid, chat_id, ?stream_type, "stream ended");
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_stream_end(uid, stream_type);
+
+    // clone the callback out so we don't hold the lock while running user code
+    let callback = registry().get(&uid).and_then(|c| c.stream_end.clone());
+
+    if let Some(callback) = callback {
+        callback(ChatId::from_ffi(chat_id), stream_type);
+    }
+}
+
+pub(crate) unsafe extern "C" fn upgrade_trampoline(
+    uid: u32,
+    chat_id: i64,
+    state: ntg_media_state_struct,
+) {
+    let state = MediaState::from_ffi(state);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(uid, chat_id, ?state, "media state upgraded");
+
+    let callback = registry().get(&uid).and_then(|c| c.upgrade.clone());
+
+    if let Some(callback) = callback {
+        callback(ChatId::from_ffi(chat_id), state);
+    }
+}
+
+pub(crate) unsafe extern "C" fn disconnect_trampoline(uid: u32, chat_id: i64) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(uid, chat_id, "disconnected");
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_call_end(uid, ChatId::from_ffi(chat_id));
+
+    let chat_id = ChatId::from_ffi(chat_id);
+
+    let callback = registry().get(&uid).and_then(|c| {
+        c.volumes.remove(chat_id);
+        c.disconnect.clone()
+    });
+
+    if let Some(callback) = callback {
+        callback(chat_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn trampolines_dispatch_to_registered_callbacks() {
+        // use a uid that the native library never hands out
+        const UID: u32 = u32::MAX;
+
+        static CALLED: AtomicBool = AtomicBool::new(false);
+
+        update(UID, |c| {
+            c.stream_end = Some(Arc::new(|chat_id, stream_type| {
+                assert_eq!(chat_id, ChatId::from_bot_api(123));
+                assert_eq!(stream_type, StreamType::Video);
+                CALLED.store(true, Ordering::SeqCst);
+            }))
+        });
+
+        unsafe { stream_end_trampoline(UID, 123, StreamType::Video as _) };
+        assert!(CALLED.swap(false, Ordering::SeqCst));
+
+        remove(UID);
+
+        unsafe { stream_end_trampoline(UID, 123, StreamType::Video as _) };
+        assert!(!CALLED.load(Ordering::SeqCst));
+    }
+}
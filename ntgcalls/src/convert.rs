@@ -0,0 +1,752 @@
+//! Converting WAV files and raw PCM to the audio read by NTgCalls.
+//!
+//! With [`InputMode::File`] NTgCalls reads headerless PCM that must exactly match the sample
+//! rate, bits per sample and channel count of the [`AudioDescription`]. A [`Decoder`] reads a
+//! WAV file, or raw PCM in any [`SampleFormat`], and a [`Converter`] resamples it and mixes its
+//! channels to the format of the stream. The result is written to a temporary
+//! [`ConvertedFile`], or streamed with a [`PcmPipe`](crate::pipe::PcmPipe) since [`Converter`]
+//! is a [`PcmSource`]:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     convert::ConvertedFile,
+//!     profiles::AudioProfile,
+//!     structures::{ChatId, MediaDescription},
+//!     NTgCall,
+//! };
+//!
+//! let target = AudioProfile {
+//!     sample_rate: 48000,
+//!     bits_per_sample: 16,
+//!     channel_count: 2,
+//! };
+//! let file = ConvertedFile::from_wav("song.wav", target)?;
+//!
+//! let call = NTgCall::new();
+//! let desc = MediaDescription {
+//!     audio: Some(file.audio_description()?),
+//!     video: None,
+//! };
+//! call.get_params(ChatId::from_channel(1234567890), desc)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+pub use crate::pcm::SampleFormat;
+use crate::{
+    enums::InputMode,
+    errors::{ConvertError, IntoCStringError},
+    pcm::{self, Dither, Ditherer, Resampler},
+    pipe::{PcmFormat, PcmSource},
+    profiles::AudioProfile,
+    structures::AudioDescription,
+    utils,
+};
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+/// The extensible fmt chunk takes 40 bytes, a bigger one is not a format this decoder supports,
+/// so its size is never trusted for an allocation.
+const MAX_FMT_SIZE: u32 = 64;
+/// Highest sample rate of the inputs, above it a rate is a corrupt header rather than audio.
+const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Format of raw PCM, the samples are interleaved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawFormat {
+    /// Encoding of the samples.
+    pub sample_format: SampleFormat,
+    /// Audio sample rate.
+    pub sample_rate: u32,
+    /// Audio channel count.
+    pub channel_count: u16,
+}
+
+impl RawFormat {
+    /// Create a new [`RawFormat`]
+    pub const fn new(sample_format: SampleFormat, sample_rate: u32, channel_count: u16) -> Self {
+        Self {
+            sample_format,
+            sample_rate,
+            channel_count,
+        }
+    }
+
+    /// Size of a frame, one sample for every channel, in bytes.
+    pub const fn frame_size(&self) -> usize {
+        self.sample_format.bytes() * self.channel_count as usize
+    }
+}
+
+/// A source of decoded frames, converted to the format of a stream by a [`Converter`].
+pub trait FrameSource {
+    /// Sample rate of the frames.
+    fn sample_rate(&self) -> u32;
+
+    /// Channel count of the frames.
+    fn channel_count(&self) -> u16;
+
+    /// Read the next frame, one sample between `-1.0` and `1.0` for every channel.
+    ///
+    /// ## Return
+    /// `false` at the end of the input.
+    fn read_frame(&mut self, frame: &mut [f32]) -> Result<bool, ConvertError>;
+}
+
+/// Reads the frames of a WAV file or of raw PCM.
+pub struct Decoder<R> {
+    reader: BufReader<R>,
+    format: RawFormat,
+    /// Bytes left in the data chunk, `None` for raw PCM and for WAV files written
+    /// to a pipe, which don't know their length.
+    remaining: Option<u64>,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Create a new [`Decoder`] reading raw PCM in the given format.
+    ///
+    /// ## Return
+    /// A [`ConvertError::InvalidFormat`] if the sample rate is zero or above 768 kHz,
+    /// or if the channel count is zero.
+    pub fn raw(reader: R, format: RawFormat) -> Result<Self, ConvertError> {
+        Self::new(BufReader::new(reader), format, None)
+    }
+
+    /// Create a new [`Decoder`] reading a WAV file, the reader is left at the start of the samples.
+    ///
+    /// PCM WAV files with 8, 16, 24 or 32 bits per sample and float WAV files with
+    /// 32 or 64 bits per sample are supported.
+    ///
+    /// ## Return
+    /// A [`ConvertError::Wav`] if the header is invalid or the encoding isn't supported, or a
+    /// [`ConvertError::InvalidFormat`] if the sample rate or the channel count is out of range.
+    pub fn wav(reader: R) -> Result<Self, ConvertError> {
+        let mut reader = BufReader::new(reader);
+
+        let mut header = [0; 12];
+        read_wav(&mut reader, &mut header)?;
+        if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+            return Err(ConvertError::Wav("The input is not a WAV file"));
+        }
+
+        let mut format = None;
+        loop {
+            let mut chunk = [0; 8];
+            read_wav(&mut reader, &mut chunk)?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+
+            match &chunk[..4] {
+                b"fmt " => {
+                    if size > MAX_FMT_SIZE {
+                        return Err(ConvertError::Wav("The fmt chunk is too long"));
+                    }
+
+                    let mut fmt = vec![0; size as usize];
+                    read_wav(&mut reader, &mut fmt)?;
+                    skip_wav(&mut reader, u64::from(size & 1))?;
+
+                    format = Some(parse_fmt(&fmt)?);
+                }
+                b"data" => {
+                    let format = format.ok_or(ConvertError::Wav(
+                        "The data chunk comes before the fmt chunk",
+                    ))?;
+                    let remaining = (size != u32::MAX).then_some(u64::from(size));
+
+                    return Self::new(reader, format, remaining);
+                }
+                // chunks are padded to an even size
+                _ => skip_wav(&mut reader, u64::from(size) + u64::from(size & 1))?,
+            }
+        }
+    }
+
+    fn new(
+        reader: BufReader<R>,
+        format: RawFormat,
+        remaining: Option<u64>,
+    ) -> Result<Self, ConvertError> {
+        if format.sample_rate == 0 {
+            return Err(ConvertError::InvalidFormat(
+                "sample_rate must be greater than zero",
+            ));
+        }
+
+        if format.sample_rate > MAX_SAMPLE_RATE {
+            return Err(ConvertError::InvalidFormat(
+                "sample_rate must be at most 768000",
+            ));
+        }
+
+        if format.channel_count == 0 {
+            return Err(ConvertError::InvalidFormat(
+                "channel_count must be greater than zero",
+            ));
+        }
+
+        Ok(Self {
+            reader,
+            format,
+            remaining,
+            bytes: vec![0; format.frame_size()],
+        })
+    }
+
+    /// Format of the decoded audio.
+    pub fn format(&self) -> RawFormat {
+        self.format
+    }
+}
+
+/// A trailing partial frame is ignored.
+impl<R: Read> FrameSource for Decoder<R> {
+    fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    fn channel_count(&self) -> u16 {
+        self.format.channel_count
+    }
+
+    fn read_frame(&mut self, frame: &mut [f32]) -> Result<bool, ConvertError> {
+        let size = self.bytes.len() as u64;
+        if self.remaining.is_some_and(|remaining| remaining < size) {
+            return Ok(false);
+        }
+
+        match self.reader.read_exact(&mut self.bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= size;
+        }
+
+        let sample_format = self.format.sample_format;
+        for (sample, bytes) in frame
+            .iter_mut()
+            .zip(self.bytes.chunks_exact(sample_format.bytes()))
+        {
+            *sample = sample_format.decode(bytes);
+        }
+
+        Ok(true)
+    }
+}
+
+fn read_wav<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), ConvertError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ConvertError::Wav("The WAV header is truncated"),
+        _ => ConvertError::Io(e),
+    })
+}
+
+/// Skip bytes without seeking, so WAV files can be read from pipes.
+fn skip_wav<R: Read>(reader: &mut R, count: u64) -> Result<(), ConvertError> {
+    if io::copy(&mut reader.take(count), &mut io::sink())? != count {
+        return Err(ConvertError::Wav("The WAV header is truncated"));
+    }
+
+    Ok(())
+}
+
+fn parse_fmt(fmt: &[u8]) -> Result<RawFormat, ConvertError> {
+    if fmt.len() < 16 {
+        return Err(ConvertError::Wav("The fmt chunk is too short"));
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+
+    let mut tag = u16_at(0);
+    let channel_count = u16_at(2);
+    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let block_align = u16_at(12);
+    let bits_per_sample = u16_at(14);
+
+    if tag == WAVE_FORMAT_EXTENSIBLE {
+        if fmt.len() < 40 {
+            return Err(ConvertError::Wav("The fmt chunk is too short"));
+        }
+
+        // the sub format GUID starts with the format tag
+        tag = u16_at(24);
+    }
+
+    let sample_format = match (tag, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => SampleFormat::U8,
+        (WAVE_FORMAT_PCM, 16) => SampleFormat::S16,
+        (WAVE_FORMAT_PCM, 24) => SampleFormat::S24,
+        (WAVE_FORMAT_PCM, 32) => SampleFormat::S32,
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32,
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => SampleFormat::F64,
+        (WAVE_FORMAT_PCM | WAVE_FORMAT_IEEE_FLOAT, _) => {
+            return Err(ConvertError::Wav("The bits per sample are not supported"))
+        }
+        _ => {
+            return Err(ConvertError::Wav(
+                "Only PCM and float WAV files are supported",
+            ))
+        }
+    };
+
+    let format = RawFormat::new(sample_format, sample_rate, channel_count);
+    if usize::from(block_align) != format.frame_size() {
+        return Err(ConvertError::Wav(
+            "The block align doesn't match the bits per sample",
+        ));
+    }
+
+    Ok(format)
+}
+
+/// Converts the frames of a [`FrameSource`] to the sample rate and channel count of a stream.
+///
+/// The channels are mixed with [`pcm::remix`] and the sample rate is changed with a
+/// [`Resampler`]. The samples aren't dithered unless [`set_dither`](Self::set_dither) is used.
+///
+/// As a [`PcmSource`] the converter ends at the end of the input or at the first read error.
+pub struct Converter<S> {
+    source: S,
+    /// Whether the source ended.
+    ended: bool,
+    input: Vec<f32>,
+    mixed: Vec<f32>,
+    resampler: Resampler,
+    frame: Vec<f32>,
+    ditherer: Ditherer,
+    samples: Vec<i16>,
+    /// Samples of `samples` already read as a [`PcmSource`].
+    offset: usize,
+}
+
+impl<S: FrameSource> Converter<S> {
+    /// Create a new [`Converter`] producing signed 16-bit samples in the given format.
+    ///
+    /// ## Return
+    /// A [`ConvertError::Target`] if NTgCalls can't stream the format, or a
+    /// [`ConvertError::InvalidFormat`] if the sample rate of the source is zero or above 768 kHz,
+    /// or if its channel count is zero.
+    pub fn new(source: S, format: PcmFormat) -> Result<Self, ConvertError> {
+        AudioProfile {
+            sample_rate: format.sample_rate,
+            bits_per_sample: 16,
+            channel_count: format.channel_count,
+        }
+        .validate()
+        .map_err(ConvertError::Target)?;
+
+        if source.sample_rate() == 0 || source.channel_count() == 0 {
+            return Err(ConvertError::InvalidFormat(
+                "The sample rate and the channel count of the source must be greater than zero",
+            ));
+        }
+
+        if source.sample_rate() > MAX_SAMPLE_RATE {
+            return Err(ConvertError::InvalidFormat(
+                "The sample rate of the source must be at most 768000",
+            ));
+        }
+
+        let channels = usize::from(format.channel_count);
+
+        Ok(Self {
+            ended: false,
+            input: vec![0.0; usize::from(source.channel_count())],
+            mixed: vec![0.0; channels],
+            resampler: Resampler::new(
+                u16::from(format.channel_count),
+                source.sample_rate(),
+                format.sample_rate,
+            ),
+            frame: vec![0.0; channels],
+            ditherer: Ditherer::new(Dither::None),
+            samples: vec![0; channels],
+            offset: channels,
+            source,
+        })
+    }
+
+    /// Change the dither applied to the signed 16-bit samples.
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.ditherer = Ditherer::new(dither);
+    }
+
+    /// Compute the next output frame in `frame`, returns `false` at the end of the input.
+    fn next_frame(&mut self) -> Result<bool, ConvertError> {
+        loop {
+            if self.resampler.next_frame(&mut self.frame) {
+                return Ok(true);
+            }
+
+            if self.ended {
+                return Ok(false);
+            }
+
+            if self.source.read_frame(&mut self.input)? {
+                pcm::remix(&self.input, &mut self.mixed);
+                self.resampler.push(&self.mixed);
+            } else {
+                self.ended = true;
+                self.resampler.finish();
+            }
+        }
+    }
+}
+
+impl<S: FrameSource + Send> PcmSource for Converter<S> {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        let mut written = 0;
+
+        while written < buf.len() {
+            if self.offset == self.samples.len() {
+                match self.next_frame() {
+                    Ok(true) => {
+                        for (sample, &value) in self.samples.iter_mut().zip(&self.frame) {
+                            *sample = self.ditherer.quantize(value);
+                        }
+
+                        self.offset = 0;
+                    }
+                    Ok(false) | Err(_) => break,
+                }
+            }
+
+            let count = (buf.len() - written).min(self.samples.len() - self.offset);
+            buf[written..written + count]
+                .copy_from_slice(&self.samples[self.offset..self.offset + count]);
+
+            written += count;
+            self.offset += count;
+        }
+
+        written
+    }
+}
+
+/// This function allows converting all the frames of a [`FrameSource`] to the format of a stream.
+///
+/// ## Parameters
+/// - `source`: The audio to convert, for example a [`Decoder`].
+/// - `target`: Format of the output, 8-bit samples are unsigned and dithered, 16-bit samples
+///   are signed little endian.
+/// - `writer`: Where the raw PCM is written.
+///
+/// ## Return
+/// The number of bytes written.
+///
+/// ### Possible error values
+/// - [`ConvertError::Target`]: NTgCalls can't stream the target format.
+/// - [`ConvertError::Io`]: Reading the input or writing the output failed.
+pub fn convert<S: FrameSource, W: Write>(
+    source: S,
+    target: AudioProfile,
+    mut writer: W,
+) -> Result<u64, ConvertError> {
+    target.validate().map_err(ConvertError::Target)?;
+
+    let format = PcmFormat::new(target.sample_rate, target.channel_count);
+    let mut converter = Converter::new(source, format)?;
+
+    // 8-bit samples without dither distort the quiet passages
+    let mut ditherer = Ditherer::new(match target.bits_per_sample {
+        8 => Dither::Triangular,
+        _ => Dither::None,
+    });
+
+    let mut written = 0;
+    let mut bytes = Vec::with_capacity(8192);
+
+    loop {
+        let more = converter.next_frame()?;
+
+        if more {
+            for &value in &converter.frame {
+                match target.bits_per_sample {
+                    8 => bytes.push(ditherer.quantize::<u8>(value)),
+                    _ => bytes.extend(ditherer.quantize::<i16>(value).to_le_bytes()),
+                }
+            }
+        }
+
+        if !more || bytes.len() >= 8192 {
+            writer.write_all(&bytes)?;
+            written += bytes.len() as u64;
+            bytes.clear();
+        }
+
+        if !more {
+            writer.flush()?;
+            return Ok(written);
+        }
+    }
+}
+
+/// Raw PCM converted to a file in the temporary directory, the file is removed when
+/// [`ConvertedFile`] is dropped.
+pub struct ConvertedFile {
+    path: PathBuf,
+    format: AudioProfile,
+}
+
+impl ConvertedFile {
+    /// Convert all the frames of a [`FrameSource`] to a new temporary file, see [`convert`].
+    pub fn create<S: FrameSource>(source: S, target: AudioProfile) -> Result<Self, ConvertError> {
+        target.validate().map_err(ConvertError::Target)?;
+
+        // removed by drop if the conversion fails
+        let this = Self {
+            path: utils::temp_path("convert", "raw"),
+            format: target,
+        };
+
+        let file = File::create(&this.path)?;
+        convert(source, target, BufWriter::new(file))?;
+
+        Ok(this)
+    }
+
+    /// Convert a WAV file to a new temporary file, see [`Decoder::wav`].
+    pub fn from_wav<P: AsRef<Path>>(path: P, target: AudioProfile) -> Result<Self, ConvertError> {
+        Self::create(Decoder::wav(File::open(path)?)?, target)
+    }
+
+    /// Path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Format of the samples in the file.
+    pub fn format(&self) -> AudioProfile {
+        self.format
+    }
+
+    /// The [`AudioDescription`] to stream the file with NTgCalls.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] if the path can't be passed to NTgCalls, only on platforms other
+    /// than Unix when the temporary directory is not valid Unicode.
+    pub fn audio_description(&self) -> Result<AudioDescription, IntoCStringError> {
+        AudioDescription::new(
+            InputMode::File,
+            self.path.as_path(),
+            self.format.sample_rate,
+            self.format.bits_per_sample,
+            self.format.channel_count,
+        )
+    }
+}
+
+impl Drop for ConvertedFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{preflight::preflight, structures::MediaDescription};
+
+    /// Build a WAV file with an unknown chunk before the samples.
+    fn wav(tag: u16, channels: u16, sample_rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+
+        let mut fmt = Vec::new();
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(sample_rate.to_le_bytes());
+        fmt.extend(
+            sample_rate
+                .wrapping_mul(u32::from(block_align))
+                .to_le_bytes(),
+        );
+        fmt.extend(block_align.to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF\0\0\0\0WAVE");
+        wav.extend(b"fmt ");
+        wav.extend((fmt.len() as u32).to_le_bytes());
+        wav.extend(fmt);
+        // odd sized chunks are padded
+        wav.extend(b"JUNK\x03\0\0\0abc\0");
+        wav.extend(b"data");
+        wav.extend((data.len() as u32).to_le_bytes());
+        wav.extend(data);
+
+        let size = (wav.len() - 8) as u32;
+        wav[4..8].copy_from_slice(&size.to_le_bytes());
+        wav
+    }
+
+    /// Build a 16-bit PCM WAV file.
+    pub(crate) fn s16_wav(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        wav(WAVE_FORMAT_PCM, channels, sample_rate, 16, &s16(samples))
+    }
+
+    fn s16(samples: &[i16]) -> Vec<u8> {
+        samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+    }
+
+    fn profile(sample_rate: u32, bits_per_sample: u8, channel_count: u8) -> AudioProfile {
+        AudioProfile {
+            sample_rate,
+            bits_per_sample,
+            channel_count,
+        }
+    }
+
+    #[test]
+    fn wav_header() {
+        // two stereo 24-bit frames followed by a trailing byte outside of the data chunk
+        let data = [0, 0, 0x40, 0, 0, 0xc0, 0xff, 0xff, 0x7f, 0, 0, 0];
+        let mut bytes = wav(WAVE_FORMAT_PCM, 2, 8000, 24, &data);
+        bytes.push(1);
+
+        let mut decoder = Decoder::wav(&bytes[..]).unwrap();
+        assert_eq!(decoder.format(), RawFormat::new(SampleFormat::S24, 8000, 2));
+
+        let mut frame = [0.0; 2];
+        assert!(decoder.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, [0.5, -0.5]);
+        assert!(decoder.read_frame(&mut frame).unwrap());
+        assert!(frame[0] > 0.99 && frame[1] == 0.0);
+        assert!(!decoder.read_frame(&mut frame).unwrap());
+
+        // WAV files written to a pipe don't know their length
+        let mut bytes = wav(WAVE_FORMAT_IEEE_FLOAT, 1, 8000, 32, &0.25f32.to_le_bytes());
+        let len = bytes.len();
+        bytes[len - 8..len - 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend(0.75f32.to_le_bytes());
+
+        let mut decoder = Decoder::wav(&bytes[..]).unwrap();
+        let mut frame = [0.0];
+        assert!(decoder.read_frame(&mut frame).unwrap());
+        assert!(decoder.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, [0.75]);
+        assert!(!decoder.read_frame(&mut frame).unwrap());
+    }
+
+    #[test]
+    fn invalid_wav() {
+        assert!(matches!(
+            Decoder::wav(&b"ID3\x04 not a wav file"[..]),
+            Err(ConvertError::Wav(_))
+        ));
+        assert!(matches!(
+            Decoder::wav(&b"RIFF\0\0\0\0WAVE"[..]),
+            Err(ConvertError::Wav(_))
+        ));
+
+        // ADPCM
+        assert!(matches!(
+            Decoder::wav(&wav(0x0002, 1, 8000, 4, &[])[..]),
+            Err(ConvertError::Wav(_))
+        ));
+
+        // the size of the fmt chunk is checked before reading it
+        let mut bytes = s16_wav(1, 8000, &[]);
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Decoder::wav(&bytes[..]),
+            Err(ConvertError::Wav("The fmt chunk is too long"))
+        ));
+
+        let raw = Decoder::raw(&[][..], RawFormat::new(SampleFormat::S16, 8000, 0));
+        assert!(matches!(raw, Err(ConvertError::InvalidFormat(_))));
+
+        // a corrupt sample rate is rejected before anything is allocated for it
+        assert!(matches!(
+            Decoder::wav(&s16_wav(1, u32::MAX, &[])[..]),
+            Err(ConvertError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn resample_and_remix() {
+        let bytes = s16_wav(1, 8000, &[16384; 1000]);
+        let decoder = Decoder::wav(&bytes[..]).unwrap();
+
+        let mut output = Vec::new();
+        convert(decoder, profile(16000, 16, 2), &mut output).unwrap();
+
+        // twice the frames in stereo
+        assert_eq!(output.len(), 2000 * 4);
+
+        // the level is kept once the filter is past the silence before and after the input
+        let samples: Vec<i16> = output
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert!(samples[200..3800].iter().all(|&s| s == 16384));
+
+        // stereo is mixed down by averaging both channels
+        let frame = [0.5f32, -0.25]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect::<Vec<_>>()
+            .repeat(100);
+        let decoder =
+            Decoder::raw(&frame[..], RawFormat::new(SampleFormat::F32, 48000, 2)).unwrap();
+
+        let mut converter = Converter::new(decoder, PcmFormat::new(48000, 1)).unwrap();
+        let mut buf = [0; 64];
+        assert_eq!(converter.read(&mut buf), 64);
+        assert_eq!(buf, [4096; 64]);
+    }
+
+    #[test]
+    fn bit_depth() {
+        let raw = s16(&[16384, -32768, 0]);
+        let decoder = Decoder::raw(&raw[..], RawFormat::new(SampleFormat::S16, 8000, 1)).unwrap();
+
+        let mut output = Vec::new();
+        assert_eq!(
+            convert(decoder, profile(8000, 8, 1), &mut output).unwrap(),
+            3
+        );
+
+        // the samples are dithered by at most one bit
+        for (sample, expected) in output.iter().zip([192, 0, 128]) {
+            assert!(sample.abs_diff(expected) <= 1, "{sample} != {expected}");
+        }
+
+        let decoder = Decoder::raw(&raw[..], RawFormat::new(SampleFormat::S16, 8000, 1)).unwrap();
+        assert!(matches!(
+            convert(decoder, profile(8000, 24, 1), &mut output),
+            Err(ConvertError::Target(_))
+        ));
+    }
+
+    #[test]
+    fn converted_file() {
+        let raw = s16(&[100; 4410]);
+        let decoder = Decoder::raw(&raw[..], RawFormat::new(SampleFormat::S16, 44100, 1)).unwrap();
+
+        let file = ConvertedFile::create(decoder, profile(48000, 16, 2)).unwrap();
+        let desc = MediaDescription {
+            audio: Some(file.audio_description().unwrap()),
+            video: None,
+        };
+        assert!(preflight(&desc).is_ok());
+
+        let size = fs::metadata(file.path()).unwrap().len();
+        assert_eq!(size, 4 * 4800);
+
+        let path = file.path().to_owned();
+        drop(file);
+        assert!(!path.exists());
+    }
+}
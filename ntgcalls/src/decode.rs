@@ -0,0 +1,293 @@
+//! Decoding compressed audio in Rust, without FFmpeg.
+//!
+//! [`InputMode::FFmpeg`](crate::enums::InputMode::FFmpeg) and most
+//! [`InputMode::Shell`](crate::enums::InputMode::Shell) commands need FFmpeg installed on the
+//! host. A [`MediaDecoder`] decodes MP3, FLAC, Ogg/Vorbis, AAC (in MP4/M4A) and WAV files with
+//! [`symphonia`], and since it's a [`FrameSource`] the decoded audio is converted to the format
+//! of the stream like the other sources of the [`convert`](crate::convert) module.
+//!
+//! On Unix [`MediaDecoder::spawn_pipe`] streams the decoded audio while it's being decoded:
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     decode::MediaDecoder,
+//!     pipe::PcmFormat,
+//!     structures::{ChatId, MediaDescription},
+//!     NTgCall,
+//! };
+//!
+//! let pipe = MediaDecoder::open("song.mp3")?.spawn_pipe(PcmFormat::new(48000, 2))?;
+//!
+//! let call = NTgCall::new();
+//! let desc = MediaDescription {
+//!     audio: Some(pipe.audio_description()),
+//!     video: None,
+//! };
+//! call.get_params(ChatId::from_channel(1234567890), desc)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+//!
+//! Keep the pipe alive for the duration of the stream, it's removed when dropped.
+
+use std::{fs::File, io::Read, path::Path};
+
+use symphonia::core::{
+    audio::{SampleBuffer, SignalSpec},
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error,
+    formats::{FormatOptions, FormatReader},
+    io::{MediaSource, MediaSourceStream, ReadOnlySource},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use crate::{convert::FrameSource, errors::ConvertError};
+
+/// Decodes the first audio track of a media file with [`symphonia`].
+///
+/// Corrupted packets are skipped. The sample rate and the channels must stay the same for
+/// the whole track, otherwise reading fails with [`ConvertError::Decode`].
+pub struct MediaDecoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    /// Format of the decoded audio, known once the first packet is decoded.
+    spec: SignalSpec,
+    buffer: Option<SampleBuffer<f32>>,
+    /// Samples of `buffer` already read.
+    offset: usize,
+}
+
+impl MediaDecoder {
+    /// Open a media file, its extension is used as a hint of its format.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ConvertError> {
+        let path = path.as_ref();
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        Self::new(Box::new(File::open(path)?), hint)
+    }
+
+    /// Create a new [`MediaDecoder`] reading from a stream that can't seek, for example
+    /// the body of an HTTP response.
+    ///
+    /// ## Parameters
+    /// - `reader`: The media file.
+    /// - `extension`: Extension of the file if known, used as a hint of its format.
+    pub fn from_reader<R: Read + Send + Sync + 'static>(
+        reader: R,
+        extension: Option<&str>,
+    ) -> Result<Self, ConvertError> {
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+
+        Self::new(Box::new(ReadOnlySource::new(reader)), hint)
+    }
+
+    fn new(source: Box<dyn MediaSource>, hint: Hint) -> Result<Self, ConvertError> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+
+        let reader = probed.format;
+        let track = reader
+            .tracks()
+            .iter()
+            // video and subtitle tracks have no sample rate
+            .find(|track| {
+                track.codec_params.codec != CODEC_TYPE_NULL
+                    && track.codec_params.sample_rate.is_some()
+            })
+            .ok_or(ConvertError::Decode(Error::Unsupported(
+                "The file has no audio track",
+            )))?;
+
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut this = Self {
+            reader,
+            decoder,
+            track_id,
+            spec: SignalSpec::new(0, Default::default()),
+            buffer: None,
+            offset: 0,
+        };
+
+        // the format is only known for sure once a packet is decoded
+        if !this.decode_packet()? {
+            return Err(ConvertError::Decode(Error::Unsupported(
+                "The audio track is empty",
+            )));
+        }
+
+        Ok(this)
+    }
+
+    /// Decode the next packet of the track into `buffer`, returns `false` at the end of the track.
+    fn decode_packet(&mut self) -> Result<bool, ConvertError> {
+        loop {
+            let packet = match self.reader.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(false)
+                }
+                // for example a new stream chained in an Ogg file
+                Err(Error::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) => continue,
+                Err(Error::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            if self.spec.rate == 0 {
+                self.spec = spec;
+            } else if spec != self.spec {
+                return Err(ConvertError::Decode(Error::Unsupported(
+                    "The sample rate or the channels change during the track",
+                )));
+            }
+
+            if decoded.frames() == 0 {
+                continue;
+            }
+
+            let samples = decoded.capacity() * spec.channels.count();
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= samples => buffer,
+                buffer => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+
+            buffer.copy_interleaved_ref(decoded);
+            self.offset = 0;
+            return Ok(true);
+        }
+    }
+
+    /// Stream the decoded audio through a new [`PcmPipe`](crate::pipe::PcmPipe), converted to
+    /// the given format.
+    ///
+    /// ## Return
+    /// A [`ConvertError::Target`] if NTgCalls can't stream the format, or a
+    /// [`ConvertError::Io`] in case the pipe couldn't be created.
+    #[cfg(unix)]
+    pub fn spawn_pipe(
+        self,
+        format: crate::pipe::PcmFormat,
+    ) -> Result<crate::pipe::PcmPipe, ConvertError> {
+        let converter = crate::convert::Converter::new(self, format)?;
+        Ok(crate::pipe::PcmPipe::spawn(converter, format)?)
+    }
+}
+
+impl FrameSource for MediaDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.spec.rate
+    }
+
+    fn channel_count(&self) -> u16 {
+        self.spec.channels.count() as u16
+    }
+
+    fn read_frame(&mut self, frame: &mut [f32]) -> Result<bool, ConvertError> {
+        let decoded = self.buffer.as_ref().map_or(0, SampleBuffer::len);
+        if self.offset == decoded && !self.decode_packet()? {
+            return Ok(false);
+        }
+
+        let Some(buffer) = &self.buffer else {
+            return Ok(false);
+        };
+
+        let samples = &buffer.samples()[self.offset..self.offset + frame.len()];
+        frame.copy_from_slice(samples);
+        self.offset += frame.len();
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        convert::{convert, test::s16_wav, Decoder},
+        profiles::AudioProfile,
+    };
+
+    #[test]
+    fn decode_wav() {
+        let samples: Vec<i16> = (0..4800).map(|i| (i % 200 - 100) * 100).collect();
+        let wav = s16_wav(2, 48000, &samples);
+
+        let mut decoder =
+            MediaDecoder::from_reader(std::io::Cursor::new(wav.clone()), Some("wav")).unwrap();
+        assert_eq!(decoder.sample_rate(), 48000);
+        assert_eq!(decoder.channel_count(), 2);
+
+        let mut frame = [0.0; 2];
+        assert!(decoder.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, [-100.0 * 100.0 / 32768.0, -99.0 * 100.0 / 32768.0]);
+
+        // symphonia decodes the same samples as the WAV decoder of the convert module
+        let target = AudioProfile {
+            sample_rate: 24000,
+            bits_per_sample: 16,
+            channel_count: 1,
+        };
+
+        let mut decoded = Vec::new();
+        let decoder = MediaDecoder::from_reader(std::io::Cursor::new(wav.clone()), None).unwrap();
+        convert(decoder, target, &mut decoded).unwrap();
+
+        let mut expected = Vec::new();
+        convert(Decoder::wav(&wav[..]).unwrap(), target, &mut expected).unwrap();
+
+        assert!(!decoded.is_empty());
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let garbage = std::io::Cursor::new(vec![0x42; 4096]);
+        assert!(matches!(
+            MediaDecoder::from_reader(garbage, Some("mp3")),
+            Err(ConvertError::Decode(_))
+        ));
+
+        assert!(matches!(
+            MediaDecoder::open("/nonexistent/song.mp3"),
+            Err(ConvertError::Io(_))
+        ));
+    }
+}
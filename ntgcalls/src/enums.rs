@@ -1,31 +1,234 @@
-/// Enumeration of input modes for audio and video streams.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-#[repr(i32)]
-pub enum InputMode {
+use std::{
+    fmt::{self, Display},
+    ops::{BitOr, BitOrAssign},
+    str::FromStr,
+};
+
+use libntgcalls_sys::{
+    ntg_input_mode_enum, ntg_input_mode_enum_NTG_FFMPEG, ntg_input_mode_enum_NTG_FILE,
+    ntg_input_mode_enum_NTG_NO_LATENCY, ntg_input_mode_enum_NTG_SHELL, ntg_stream_status_enum,
+    ntg_stream_status_enum_NTG_IDLING, ntg_stream_status_enum_NTG_PAUSED,
+    ntg_stream_status_enum_NTG_PLAYING, ntg_stream_type_enum,
+    ntg_stream_type_enum_NTG_STREAM_VIDEO,
+};
+
+use crate::errors::InputModeError;
+
+/// Input modes for audio and video streams.
+///
+/// A mode is made of exactly one source, [`InputMode::File`], [`InputMode::Shell`] or
+/// [`InputMode::FFmpeg`], optionally combined with [`InputMode::NoLatency`]:
+///
+/// ```
+/// use ntgcalls::enums::InputMode;
+///
+/// let mode = InputMode::FFmpeg | InputMode::NoLatency;
+/// assert!(mode.is_valid());
+/// assert!(mode.contains(InputMode::NoLatency));
+///
+/// assert!(InputMode::new(1 | 2).is_err());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InputMode(i32);
+
+#[allow(non_upper_case_globals)]
+impl InputMode {
     /// Input mode using a file source.
-    File = 1,
+    pub const File: Self = Self(ntg_input_mode_enum_NTG_FILE);
     /// Input mode using a shell command.
-    Shell = 2,
+    pub const Shell: Self = Self(ntg_input_mode_enum_NTG_SHELL);
     /// Input mode using FFmpeg.
-    FFmpeg = 4,
-    /// Exprimental mode
-    NoLatency = 8,
+    pub const FFmpeg: Self = Self(ntg_input_mode_enum_NTG_FFMPEG);
+    /// Exprimental mode, combined with one of the sources.
+    pub const NoLatency: Self = Self(ntg_input_mode_enum_NTG_NO_LATENCY);
+}
+
+impl InputMode {
+    const SOURCES: [(Self, &'static str); 3] = [
+        (Self::File, "File"),
+        (Self::Shell, "Shell"),
+        (Self::FFmpeg, "FFmpeg"),
+    ];
+    const FLAGS: [(Self, &'static str); 4] = [
+        (Self::File, "File"),
+        (Self::Shell, "Shell"),
+        (Self::FFmpeg, "FFmpeg"),
+        (Self::NoLatency, "NoLatency"),
+    ];
+
+    /// Create an input mode from the bits of `ntg_input_mode_enum`.
+    ///
+    /// ## Return
+    /// An [`InputModeError`] if the bits are not exactly one source, optionally combined
+    /// with [`InputMode::NoLatency`].
+    pub fn new(bits: i32) -> Result<Self, InputModeError> {
+        let known = Self::FLAGS
+            .iter()
+            .fold(0, |known, (mode, _)| known | mode.0);
+        if bits & !known != 0 {
+            return Err(InputModeError::UnknownBits(bits & !known));
+        }
+
+        let mode = Self(bits);
+        match Self::SOURCES
+            .iter()
+            .filter(|(source, _)| mode.contains(*source))
+            .count()
+        {
+            0 => Err(InputModeError::MissingSource),
+            1 => Ok(mode),
+            _ => Err(InputModeError::ConflictingSources),
+        }
+    }
+
+    /// The bits of `ntg_input_mode_enum`.
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// Whether all the flags of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the mode is accepted by [`InputMode::new`].
+    ///
+    /// Combining modes with `|` doesn't check them, for example `File | Shell` is not valid.
+    pub fn is_valid(self) -> bool {
+        Self::new(self.0).is_ok()
+    }
+}
+
+impl BitOr for InputMode {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for InputMode {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl TryFrom<i32> for InputMode {
+    type Error = InputModeError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<InputMode> for ntg_input_mode_enum {
+    fn from(value: InputMode) -> Self {
+        value.0
+    }
+}
+
+impl Display for InputMode {
+    /// The names of the flags separated by `|`, like `FFmpeg|NoLatency`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Self::FLAGS
+            .iter()
+            .filter(|(mode, _)| self.contains(*mode))
+            .map(|(_, name)| *name);
+
+        let Some(first) = names.next() else {
+            return write!(f, "{:#x}", self.0);
+        };
+        f.write_str(first)?;
+        names.try_for_each(|name| write!(f, "|{name}"))?;
+
+        let unknown = self.0
+            & !Self::FLAGS
+                .iter()
+                .fold(0, |known, (mode, _)| known | mode.0);
+        if unknown != 0 {
+            write!(f, "|{unknown:#x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for InputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InputMode({self})")
+    }
+}
+
+impl FromStr for InputMode {
+    type Err = InputModeError;
+
+    /// Parse the names of the flags separated by `|`, the format of [`Display`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = s.split('|').map(str::trim).try_fold(0, |bits, name| {
+            Self::FLAGS
+                .iter()
+                .find(|(_, flag)| *flag == name)
+                .map(|(mode, _)| bits | mode.0)
+                .ok_or_else(|| InputModeError::UnknownName(name.to_owned()))
+        })?;
+
+        Self::new(bits)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for InputMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InputMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
 }
 
 /// Enumeration of stream status values.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-#[repr(i32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StreamStatus {
     /// Indicates that the stream is currently playing.
-    Playing = 0,
+    Playing,
     /// Indicates that the stream is currently paused.
-    Paused = 1,
+    Paused,
     /// Indicates that the stream is currently idling (neither playing nor paused).
-    Idling = 2,
+    Idling,
+    /// A status these bindings don't know, reported by a newer version of NTgCalls.
+    Unknown(i32),
+}
+
+impl StreamStatus {
+    pub(crate) fn from_ffi(value: ntg_stream_status_enum) -> Self {
+        Self::try_from(value).unwrap_or(Self::Unknown(value))
+    }
+}
+
+impl TryFrom<i32> for StreamStatus {
+    type Error = i32;
+
+    /// Convert a status returned by NTgCalls, the unknown values are returned as error.
+    #[allow(non_upper_case_globals)]
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            ntg_stream_status_enum_NTG_PLAYING => Ok(Self::Playing),
+            ntg_stream_status_enum_NTG_PAUSED => Ok(Self::Paused),
+            ntg_stream_status_enum_NTG_IDLING => Ok(Self::Idling),
+            value => Err(value),
+        }
+    }
 }
 
 /// Enumeration of stream type values.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(i32)]
 pub enum StreamType {
     /// Indicates that the stream type is audio.
@@ -33,3 +236,91 @@ pub enum StreamType {
     /// Indicates that the stream type is video.
     Video = 1,
 }
+
+impl StreamType {
+    pub(crate) fn from_ffi(value: ntg_stream_type_enum) -> Self {
+        if value == ntg_stream_type_enum_NTG_STREAM_VIDEO {
+            Self::Video
+        } else {
+            Self::Audio
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn input_mode_flags() {
+        let mode = InputMode::FFmpeg | InputMode::NoLatency;
+        assert_eq!(mode.bits(), 4 | 8);
+        assert_eq!(ntg_input_mode_enum::from(mode), 12);
+        assert_eq!(InputMode::new(12), Ok(mode));
+        assert_eq!(InputMode::try_from(1), Ok(InputMode::File));
+
+        assert_eq!(
+            InputMode::new(1 | 2),
+            Err(InputModeError::ConflictingSources)
+        );
+        assert!(!(InputMode::File | InputMode::Shell).is_valid());
+        assert_eq!(InputMode::new(8), Err(InputModeError::MissingSource));
+        assert_eq!(InputMode::new(0), Err(InputModeError::MissingSource));
+        assert_eq!(InputMode::new(4 | 16), Err(InputModeError::UnknownBits(16)));
+
+        let mut mode = InputMode::Shell;
+        mode |= InputMode::NoLatency;
+        assert!(mode.contains(InputMode::Shell));
+        assert!(!mode.contains(InputMode::File));
+    }
+
+    #[test]
+    fn input_mode_names() {
+        assert_eq!(InputMode::File.to_string(), "File");
+        assert_eq!(
+            (InputMode::NoLatency | InputMode::FFmpeg).to_string(),
+            "FFmpeg|NoLatency"
+        );
+        assert_eq!(format!("{:?}", InputMode::Shell), "InputMode(Shell)");
+
+        assert_eq!("Shell".parse(), Ok(InputMode::Shell));
+        assert_eq!(
+            "FFmpeg | NoLatency".parse(),
+            Ok(InputMode::FFmpeg | InputMode::NoLatency)
+        );
+        assert_eq!(
+            "File|Shell".parse::<InputMode>(),
+            Err(InputModeError::ConflictingSources)
+        );
+        assert_eq!(
+            "ffmpeg".parse::<InputMode>(),
+            Err(InputModeError::UnknownName(String::from("ffmpeg")))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn input_mode_serde() {
+        // the single modes are encoded like the variants of the previous enum
+        assert_eq!(
+            serde_json::to_string(&InputMode::FFmpeg).unwrap(),
+            r#""FFmpeg""#
+        );
+        assert_eq!(
+            serde_json::from_str::<InputMode>(r#""File|NoLatency""#).unwrap(),
+            InputMode::File | InputMode::NoLatency
+        );
+        assert!(serde_json::from_str::<InputMode>(r#""File|Shell""#).is_err());
+    }
+
+    #[test]
+    fn stream_status_from_ffi() {
+        assert_eq!(StreamStatus::try_from(0), Ok(StreamStatus::Playing));
+        assert_eq!(StreamStatus::try_from(1), Ok(StreamStatus::Paused));
+        assert_eq!(StreamStatus::try_from(2), Ok(StreamStatus::Idling));
+        assert_eq!(StreamStatus::try_from(3), Err(3));
+
+        assert_eq!(StreamStatus::from_ffi(2), StreamStatus::Idling);
+        assert_eq!(StreamStatus::from_ffi(-7), StreamStatus::Unknown(-7));
+    }
+}
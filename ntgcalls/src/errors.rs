@@ -1,4 +1,10 @@
-use std::{error::Error, fmt::Display};
+use std::{
+    error::Error,
+    ffi::{NulError, OsString},
+    fmt::Display,
+    io,
+    path::PathBuf,
+};
 
 use libntgcalls_sys::{
     NTG_CONNECTION_ALREADY_EXISTS, NTG_CONNECTION_FAILED, NTG_CONNECTION_NOT_FOUND,
@@ -6,6 +12,10 @@ use libntgcalls_sys::{
     NTG_INVALID_UID, NTG_RTMP_NEEDED, NTG_SHELL_ERROR,
 };
 
+use crate::enums::StreamType;
+#[cfg(feature = "grammers")]
+use crate::structures::ChatId;
+
 /// Result type alias for NTgCall errors.
 pub type NTgCallResult<T> = Result<T, NTgCallError>;
 
@@ -35,9 +45,37 @@ pub enum NTgCallError {
     UnknownException,
     /// The provided UID is invalid.
     InvalidUid,
+    /// The instance was destroyed by one of its clones.
+    Destroyed,
+    /// An input mode of the media description doesn't set exactly one source.
+    InvalidInputMode,
+    /// A string passed to NTgCalls can't be converted to a C string, see [`IntoCStringError`].
+    InvalidString,
     // ErrTooSmall -> we handle this as a internal error
 }
 
+impl NTgCallError {
+    /// Name of the variant, a fixed string that can be used as a metric label or in a protocol.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ConnectionAlreadyExists => "ConnectionAlreadyExists",
+            Self::ConnectionNotFound => "ConnectionNotFound",
+            Self::FileNotFound => "FileNotFound",
+            Self::EncoderNotFound => "EncoderNotFound",
+            Self::FFmpegNotFound => "FFmpegNotFound",
+            Self::ShellError => "ShellError",
+            Self::RtmpNeeded => "RtmpNeeded",
+            Self::InvalidTransport => "InvalidTransport",
+            Self::ConnectionFailed => "ConnectionFailed",
+            Self::UnknownException => "UnknownException",
+            Self::InvalidUid => "InvalidUid",
+            Self::Destroyed => "Destroyed",
+            Self::InvalidInputMode => "InvalidInputMode",
+            Self::InvalidString => "InvalidString",
+        }
+    }
+}
+
 impl Error for NTgCallError {}
 
 impl Display for NTgCallError {
@@ -54,6 +92,9 @@ impl Display for NTgCallError {
             Self::ConnectionFailed => "[ConnectionFailed]: The connection to WebRTC failed",
             Self::UnknownException => "[UnknownException]: An unknown exception occurred",
             Self::InvalidUid => "[InvalidUid]: The provided UID is invalid",
+            Self::Destroyed => "[Destroyed]: The instance was destroyed by one of its clones",
+            Self::InvalidInputMode => "[InvalidInputMode]: An input mode must set exactly one of File, Shell or FFmpeg",
+            Self::InvalidString => "[InvalidString]: The string contains a nul byte or is not valid Unicode",
         }
         .fmt(f)
     }
@@ -77,15 +118,529 @@ impl From<i32> for NTgCallError {
     }
 }
 
-/// DestroyError is a struct representing an error that occurs during destruction.
+impl From<IntoCStringError> for NTgCallError {
+    fn from(_: IntoCStringError) -> Self {
+        Self::InvalidString
+    }
+}
+
+/// IntoCStringError represents a string that can't be passed to NTgCalls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntoCStringError {
+    /// The string contains a nul byte.
+    Nul(NulError),
+    /// The string is not valid Unicode, only on platforms other than Unix.
+    NotUnicode(OsString),
+}
+
+impl Error for IntoCStringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Nul(e) => Some(e),
+            Self::NotUnicode(_) => None,
+        }
+    }
+}
+
+impl Display for IntoCStringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nul(e) => write!(
+                f,
+                "[Nul]: The string contains a nul byte at {}",
+                e.nul_position()
+            ),
+            Self::NotUnicode(s) => write!(f, "[NotUnicode]: The string {s:?} is not valid Unicode"),
+        }
+    }
+}
+
+impl From<NulError> for IntoCStringError {
+    fn from(value: NulError) -> Self {
+        Self::Nul(value)
+    }
+}
+
+impl From<IntoCStringError> for io::Error {
+    fn from(value: IntoCStringError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, value)
+    }
+}
+
+/// DestroyError represents an error that occurs during destruction.
 /// This is typically used to represent errors freeing resources during drop.
-#[derive(Debug)]
-pub struct DestroyError;
+#[derive(Debug, PartialEq, Eq)]
+pub enum DestroyError {
+    /// The instance was already destroyed by one of its clones.
+    AlreadyDestroyed,
+    /// NTgCalls failed to clean up the instance.
+    Failed,
+}
 
 impl Error for DestroyError {}
 
 impl Display for DestroyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        "[DestroyError]: NTgCalls cleanup failed".fmt(f)
+        match self {
+            Self::AlreadyDestroyed => "[DestroyError]: The instance was already destroyed",
+            Self::Failed => "[DestroyError]: NTgCalls cleanup failed",
+        }
+        .fmt(f)
+    }
+}
+
+/// InputModeError represents an [`InputMode`](crate::enums::InputMode) that NTgCalls can't use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputModeError {
+    /// None of the sources, `File`, `Shell` or `FFmpeg`, is set.
+    MissingSource,
+    /// More than one source is set, for example `File | Shell`.
+    ConflictingSources,
+    /// Bits unknown to NTgCalls are set.
+    UnknownBits(i32),
+    /// The name of a flag is unknown.
+    UnknownName(String),
+}
+
+impl Error for InputModeError {}
+
+impl Display for InputModeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSource => write!(
+                f,
+                "[MissingSource]: The input mode must set one of File, Shell or FFmpeg"
+            ),
+            Self::ConflictingSources => write!(
+                f,
+                "[ConflictingSources]: The input mode must set only one of File, Shell or FFmpeg"
+            ),
+            Self::UnknownBits(bits) => {
+                write!(
+                    f,
+                    "[UnknownBits]: The input mode bits {bits:#x} are unknown"
+                )
+            }
+            Self::UnknownName(name) => {
+                write!(f, "[UnknownName]: The input mode `{name}` is unknown")
+            }
+        }
+    }
+}
+
+/// ProfileError represents an invalid [`StreamProfile`](crate::profiles::StreamProfile), or a
+/// failure to load [`Profiles`](crate::profiles::Profiles).
+#[derive(Debug)]
+pub enum ProfileError {
+    /// A profile has parameters that NTgCalls doesn't support.
+    Invalid(&'static str),
+    /// A loaded profile is invalid.
+    Profile {
+        /// Name of the profile.
+        name: String,
+        /// Why the profile is invalid.
+        source: Box<ProfileError>,
+    },
+    /// The JSON document couldn't be parsed.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// The TOML document couldn't be parsed.
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+    /// The profiles file couldn't be read.
+    #[cfg(any(feature = "json", feature = "toml"))]
+    Io(std::io::Error),
+    /// The format of the profiles file isn't known from its extension.
+    #[cfg(any(feature = "json", feature = "toml"))]
+    UnknownFormat(PathBuf),
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Invalid(_) => None,
+            Self::Profile { source, .. } => Some(source.as_ref()),
+            #[cfg(feature = "json")]
+            Self::Json(e) => Some(e),
+            #[cfg(feature = "toml")]
+            Self::Toml(e) => Some(e),
+            #[cfg(any(feature = "json", feature = "toml"))]
+            Self::Io(e) => Some(e),
+            #[cfg(any(feature = "json", feature = "toml"))]
+            Self::UnknownFormat(_) => None,
+        }
+    }
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(reason) => write!(f, "[Invalid]: {reason}"),
+            Self::Profile { name, source } => write!(f, "[Profile]: `{name}` {source}"),
+            #[cfg(feature = "json")]
+            Self::Json(e) => write!(f, "[Json]: {e}"),
+            #[cfg(feature = "toml")]
+            Self::Toml(e) => write!(f, "[Toml]: {e}"),
+            #[cfg(any(feature = "json", feature = "toml"))]
+            Self::Io(e) => write!(f, "[Io]: {e}"),
+            #[cfg(any(feature = "json", feature = "toml"))]
+            Self::UnknownFormat(path) => write!(
+                f,
+                "[UnknownFormat]: {} is not a .json or .toml file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// PreflightError lists all the problems found by [`preflight`](crate::preflight::preflight).
+#[derive(Debug)]
+pub struct PreflightError {
+    /// The problems of the sources, audio first.
+    pub problems: Vec<PreflightProblem>,
+}
+
+impl Error for PreflightError {}
+
+impl Display for PreflightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[Preflight]: {} problem(s) found", self.problems.len())?;
+
+        for problem in &self.problems {
+            write!(f, "\n- {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem of the source of a stream, found before calling NTgCalls.
+#[derive(Debug)]
+pub enum PreflightProblem {
+    /// The input mode can't be used by NTgCalls.
+    InvalidInputMode {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Why the input mode is invalid.
+        error: InputModeError,
+    },
+    /// The file doesn't exist, NTgCalls would fail with [`NTgCallError::FileNotFound`].
+    FileNotFound {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Path of the file.
+        path: PathBuf,
+    },
+    /// The file exists but can't be read.
+    FileNotReadable {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Path of the file.
+        path: PathBuf,
+        /// Why the file can't be read.
+        error: std::io::Error,
+    },
+    /// The path is a directory or another kind of file that NTgCalls can't stream.
+    NotAFile {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Path of the file.
+        path: PathBuf,
+    },
+    /// The size of the raw file is not a whole number of frames, the description doesn't
+    /// match the format of the file.
+    MisalignedFile {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Path of the file.
+        path: PathBuf,
+        /// Size of the file in bytes.
+        size: u64,
+        /// Size of a frame in bytes, a sample of every channel for audio and a YUV420p
+        /// picture for video.
+        frame_size: u64,
+    },
+    /// FFmpeg is not found in the system, NTgCalls would fail with [`NTgCallError::FFmpegNotFound`].
+    FFmpegNotFound {
+        /// The stream of the source.
+        stream: StreamType,
+    },
+    /// The shell command can't be parsed, NTgCalls would fail with [`NTgCallError::ShellError`].
+    InvalidShellCommand {
+        /// The stream of the source.
+        stream: StreamType,
+        /// Why the command can't be parsed.
+        reason: String,
+    },
+}
+
+impl PreflightProblem {
+    /// The stream of the source with the problem.
+    pub fn stream(&self) -> StreamType {
+        match self {
+            Self::InvalidInputMode { stream, .. }
+            | Self::FileNotFound { stream, .. }
+            | Self::FileNotReadable { stream, .. }
+            | Self::NotAFile { stream, .. }
+            | Self::MisalignedFile { stream, .. }
+            | Self::FFmpegNotFound { stream }
+            | Self::InvalidShellCommand { stream, .. } => *stream,
+        }
+    }
+}
+
+impl Display for PreflightProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stream = match self.stream() {
+            StreamType::Audio => "audio",
+            StreamType::Video => "video",
+        };
+
+        match self {
+            Self::InvalidInputMode { error, .. } => write!(f, "{stream}: {error}"),
+            Self::FileNotFound { path, .. } => write!(
+                f,
+                "{stream}: [FileNotFound]: The file {} was not found",
+                path.display()
+            ),
+            Self::FileNotReadable { path, error, .. } => write!(
+                f,
+                "{stream}: [FileNotReadable]: The file {} can't be read: {error}",
+                path.display()
+            ),
+            Self::NotAFile { path, .. } => write!(
+                f,
+                "{stream}: [NotAFile]: {} is not a file",
+                path.display()
+            ),
+            Self::MisalignedFile {
+                path,
+                size,
+                frame_size,
+                ..
+            } => write!(
+                f,
+                "{stream}: [MisalignedFile]: The size of {} ({size} bytes) is not a multiple of the frame size ({frame_size} bytes)",
+                path.display()
+            ),
+            Self::FFmpegNotFound { .. } => write!(
+                f,
+                "{stream}: [FFmpegNotFound]: FFmpeg is not found in the system"
+            ),
+            Self::InvalidShellCommand { reason, .. } => {
+                write!(f, "{stream}: [InvalidShellCommand]: {reason}")
+            }
+        }
+    }
+}
+
+/// GroupCallError represents an error of the [`GroupCalls`](crate::group_call::GroupCalls) helper,
+/// either NTgCalls or the MTProto transport failed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GroupCallError<E> {
+    /// NTgCalls failed.
+    NTgCall(NTgCallError),
+    /// The MTProto request failed.
+    Transport(E),
+}
+
+impl<E: Error + 'static> Error for GroupCallError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NTgCall(e) => Some(e),
+            Self::Transport(e) => Some(e),
+        }
+    }
+}
+
+impl<E: Display> Display for GroupCallError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NTgCall(e) => e.fmt(f),
+            Self::Transport(e) => write!(f, "[Transport]: {e}"),
+        }
+    }
+}
+
+impl<E> From<NTgCallError> for GroupCallError<E> {
+    fn from(value: NTgCallError) -> Self {
+        Self::NTgCall(value)
+    }
+}
+
+/// GrammersError represents an error of the [`GrammersTransport`](crate::grammers::GrammersTransport).
+#[cfg(feature = "grammers")]
+#[derive(Debug)]
+pub enum GrammersError {
+    /// The MTProto request failed.
+    Invocation(grammers_client::InvocationError),
+    /// The chat was not added with [`GrammersTransport::add_peer`](crate::grammers::GrammersTransport::add_peer).
+    ChatNotFound(ChatId),
+    /// The peer is a user, only basic groups, supergroups and channels have group calls.
+    NotAGroup,
+    /// The chat has no active group call.
+    NoGroupCall(ChatId),
+    /// Telegram didn't send the connection params of the joined group call.
+    NoConnectionParams(ChatId),
+}
+
+#[cfg(feature = "grammers")]
+impl Error for GrammersError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Invocation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "grammers")]
+impl Display for GrammersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invocation(e) => write!(f, "[Invocation]: {e}"),
+            Self::ChatNotFound(chat_id) => write!(
+                f,
+                "[ChatNotFound]: The chat {chat_id} was not added to the transport"
+            ),
+            Self::NotAGroup => write!(f, "[NotAGroup]: Users don't have group calls"),
+            Self::NoGroupCall(chat_id) => {
+                write!(f, "[NoGroupCall]: The chat {chat_id} has no active group call")
+            }
+            Self::NoConnectionParams(chat_id) => write!(
+                f,
+                "[NoConnectionParams]: Telegram didn't send the connection params of the group call of {chat_id}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "grammers")]
+impl From<grammers_client::InvocationError> for GrammersError {
+    fn from(value: grammers_client::InvocationError) -> Self {
+        Self::Invocation(value)
+    }
+}
+
+/// RtmpError represents an error of the [`RtmpStreams`](crate::rtmp::RtmpStreams) transport.
+#[derive(Debug)]
+pub enum RtmpError {
+    /// FFmpeg is not found in the system.
+    FFmpegNotFound,
+    /// A stream for the specified chat already exists.
+    StreamAlreadyExists,
+    /// The stream for the specified chat was not found.
+    StreamNotFound,
+    /// The media description can't be streamed over RTMP.
+    Unsupported(&'static str),
+    /// Spawning or stopping the FFmpeg process failed.
+    Io(std::io::Error),
+    /// The FFmpeg process exited with an error.
+    Exited {
+        /// Exit code of the process, [`None`] if it was killed by a signal.
+        code: Option<i32>,
+        /// Last lines written by FFmpeg on the standard error.
+        stderr: String,
+    },
+}
+
+impl Error for RtmpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RtmpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FFmpegNotFound => {
+                write!(f, "[FFmpegNotFound]: FFmpeg is not found in the system")
+            }
+            Self::StreamAlreadyExists => write!(
+                f,
+                "[StreamAlreadyExists]: A stream for the specified chat already exists"
+            ),
+            Self::StreamNotFound => write!(
+                f,
+                "[StreamNotFound]: The stream for the specified chat was not found"
+            ),
+            Self::Unsupported(reason) => write!(f, "[Unsupported]: {reason}"),
+            Self::Io(e) => write!(f, "[Io]: {e}"),
+            Self::Exited {
+                code: Some(code),
+                stderr,
+            } => {
+                write!(f, "[Exited]: FFmpeg exited with code {code}: {stderr}")
+            }
+            Self::Exited { code: None, stderr } => {
+                write!(f, "[Exited]: FFmpeg was killed: {stderr}")
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for RtmpError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// ConvertError represents an error of the [`convert`](crate::convert) module.
+#[derive(Debug)]
+pub enum ConvertError {
+    /// Reading the input or writing the output failed.
+    Io(std::io::Error),
+    /// The input is not a WAV file or uses an encoding that can't be converted.
+    Wav(&'static str),
+    /// The format of the raw input is invalid.
+    InvalidFormat(&'static str),
+    /// The output format can't be used by NTgCalls.
+    Target(ProfileError),
+    /// The input couldn't be decoded.
+    #[cfg(feature = "symphonia")]
+    Decode(symphonia::core::errors::Error),
+}
+
+impl Error for ConvertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Target(e) => Some(e),
+            #[cfg(feature = "symphonia")]
+            Self::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "[Io]: {e}"),
+            Self::Wav(reason) => write!(f, "[Wav]: {reason}"),
+            Self::InvalidFormat(reason) => write!(f, "[InvalidFormat]: {reason}"),
+            Self::Target(e) => write!(f, "[Target]: {e}"),
+            #[cfg(feature = "symphonia")]
+            Self::Decode(e) => write!(f, "[Decode]: {e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ConvertError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[cfg(feature = "symphonia")]
+impl From<symphonia::core::errors::Error> for ConvertError {
+    fn from(value: symphonia::core::errors::Error) -> Self {
+        match value {
+            symphonia::core::errors::Error::IoError(e) => Self::Io(e),
+            e => Self::Decode(e),
+        }
     }
 }
@@ -0,0 +1,487 @@
+//! Marshalling of the values passed to and read from NTgCalls.
+//!
+//! The FFI structs only hold pointers to the Rust values they were created from. The
+//! helpers of this module own the FFI structs and strings for exactly the duration of
+//! a call, so no pointer handed to NTgCalls outlives the data it points to and nothing
+//! is leaked once the call returns.
+
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_int},
+    ptr,
+    string::FromUtf8Error,
+};
+
+use libntgcalls_sys::{ntg_group_call_struct, ntg_media_description_struct};
+
+use crate::structures::{AudioDescription, GroupCall, MediaDescription, VideoDescription};
+
+/// The NTgCalls functions called by the bindings.
+#[cfg(not(test))]
+pub(crate) use libntgcalls_sys as backend;
+
+/// The NTgCalls functions called by the bindings, replaceable by fake functions in the tests
+/// so the methods of [`NTgCall`](crate::NTgCall) can run under Miri without the native library.
+#[cfg(test)]
+pub(crate) mod backend {
+    use std::{
+        cell::Cell,
+        os::raw::{c_char, c_int},
+    };
+
+    use libntgcalls_sys::{
+        ntg_disconnect_callback, ntg_group_call_struct, ntg_media_description_struct,
+        ntg_media_state_struct, ntg_stream_callback, ntg_upgrade_callback,
+    };
+
+    macro_rules! backend {
+        ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+            /// The functions called on the current thread.
+            #[derive(Clone, Copy)]
+            pub(crate) struct Backend {
+                $(pub(crate) $name: unsafe extern "C" fn($($ty),*) -> $ret,)*
+            }
+
+            impl Backend {
+                /// The functions of the native library.
+                pub(crate) const NATIVE: Self = Self {
+                    $($name: libntgcalls_sys::$name,)*
+                };
+            }
+
+            $(
+                pub(crate) unsafe fn $name($($arg: $ty),*) -> $ret {
+                    (BACKEND.get().$name)($($arg),*)
+                }
+            )*
+        };
+    }
+
+    backend! {
+        ntg_init() -> u32;
+        ntg_destroy(uid: u32) -> c_int;
+        ntg_get_params(
+            uid: u32,
+            chat_id: i64,
+            desc: ntg_media_description_struct,
+            buffer: *mut c_char,
+            size: c_int
+        ) -> c_int;
+        ntg_connect(uid: u32, chat_id: i64, params: *mut c_char) -> c_int;
+        ntg_change_stream(uid: u32, chat_id: i64, desc: ntg_media_description_struct) -> c_int;
+        ntg_pause(uid: u32, chat_id: i64) -> c_int;
+        ntg_resume(uid: u32, chat_id: i64) -> c_int;
+        ntg_mute(uid: u32, chat_id: i64) -> c_int;
+        ntg_unmute(uid: u32, chat_id: i64) -> c_int;
+        ntg_stop(uid: u32, chat_id: i64) -> c_int;
+        ntg_time(uid: u32, chat_id: i64) -> i64;
+        ntg_get_state(uid: u32, chat_id: i64, state: *mut ntg_media_state_struct) -> c_int;
+        ntg_calls(uid: u32, buffer: *mut ntg_group_call_struct, size: c_int) -> c_int;
+        ntg_calls_count(uid: u32) -> c_int;
+        ntg_on_stream_end(uid: u32, callback: ntg_stream_callback) -> c_int;
+        ntg_on_upgrade(uid: u32, callback: ntg_upgrade_callback) -> c_int;
+        ntg_on_disconnect(uid: u32, callback: ntg_disconnect_callback) -> c_int;
+        ntg_get_version(buffer: *mut c_char, size: c_int) -> c_int;
+        ntg_cpu_usage(uid: u32, buffer: *mut f64) -> c_int;
+    }
+
+    thread_local! {
+        static BACKEND: Cell<Backend> = const { Cell::new(Backend::NATIVE) };
+    }
+
+    /// Run `f` with the given functions called instead of the native ones on the current thread.
+    pub(crate) fn with<R>(backend: Backend, f: impl FnOnce() -> R) -> R {
+        struct Restore(Backend);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                BACKEND.set(self.0);
+            }
+        }
+
+        let _restore = Restore(BACKEND.replace(backend));
+        f()
+    }
+}
+
+/// Call `f` with the FFI representation of `desc`.
+///
+/// The audio and video structs live on the stack of this function until `f` returns.
+pub(crate) fn with_media_description<R>(
+    desc: &MediaDescription,
+    f: impl FnOnce(ntg_media_description_struct) -> R,
+) -> R {
+    let audio = desc.audio.as_ref().map(AudioDescription::to_ffi);
+    let video = desc.video.as_ref().map(VideoDescription::to_ffi);
+
+    f(ntg_media_description_struct {
+        audio: audio.as_ref().map_or(ptr::null(), ptr::from_ref),
+        video: video.as_ref().map_or(ptr::null(), ptr::from_ref),
+    })
+}
+
+/// Call `f` with a pointer to a nul terminated copy of `s`, freed when `f` returns.
+///
+/// The functions of NTgCalls take mutable pointers even for their inputs, the pointer
+/// points to an owned buffer so writing through it is sound.
+pub(crate) fn with_c_string<R>(s: CString, f: impl FnOnce(*mut c_char) -> R) -> R {
+    let mut bytes = s.into_bytes_with_nul();
+
+    f(bytes.as_mut_ptr().cast())
+}
+
+/// A buffer for the strings written by NTgCalls.
+pub(crate) struct StringBuffer(Vec<u8>);
+
+impl StringBuffer {
+    /// Create a new buffer of `size` bytes, including the nul terminator.
+    pub(crate) fn new(size: usize) -> Self {
+        Self(vec![0; size])
+    }
+
+    /// Pointer to pass to NTgCalls.
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut c_char {
+        self.0.as_mut_ptr().cast()
+    }
+
+    /// Size of the buffer to pass to NTgCalls.
+    pub(crate) fn size(&self) -> c_int {
+        c_int::try_from(self.0.len()).unwrap_or(c_int::MAX)
+    }
+
+    /// The string written by NTgCalls, without the nul terminator and the unused bytes.
+    pub(crate) fn into_string(mut self) -> Result<String, FromUtf8Error> {
+        if let Some(end) = self.0.iter().position(|&b| b == 0) {
+            self.0.truncate(end);
+        }
+
+        String::from_utf8(self.0)
+    }
+}
+
+/// Marks the entries of a [`CallsBuffer`] that NTgCalls didn't write, no chat has this id.
+const UNWRITTEN: ntg_group_call_struct = ntg_group_call_struct {
+    chatId: i64::MIN,
+    status: -1,
+};
+
+/// A buffer for the calls written by `ntg_calls`.
+///
+/// NTgCalls writes the calls at the start of the buffer, when calls end between counting
+/// them and reading them the remaining entries are left untouched and are skipped.
+pub(crate) struct CallsBuffer(Vec<ntg_group_call_struct>);
+
+impl CallsBuffer {
+    /// Create a new buffer for `count` calls.
+    pub(crate) fn new(count: usize) -> Self {
+        Self(vec![UNWRITTEN; count])
+    }
+
+    /// Pointer to pass to NTgCalls.
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut ntg_group_call_struct {
+        self.0.as_mut_ptr()
+    }
+
+    /// Size of the buffer, in calls, to pass to NTgCalls.
+    pub(crate) fn size(&self) -> c_int {
+        c_int::try_from(self.0.len()).unwrap_or(c_int::MAX)
+    }
+
+    /// The calls written by NTgCalls.
+    pub(crate) fn into_calls(self) -> Vec<GroupCall> {
+        self.0
+            .iter()
+            .take_while(|call| call.chatId != UNWRITTEN.chatId)
+            .map(GroupCall::from_ffi)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    //! These tests call fake NTgCalls functions instead of the native library so they run
+    //! under Miri, which reports any dangling pointer or leaked allocation:
+    //! `cargo +nightly miri test -p ntgcalls ffi::`
+
+    use std::{
+        ffi::CStr,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use libntgcalls_sys::{
+        ntg_audio_description_struct, ntg_disconnect_callback, ntg_video_description_struct,
+    };
+
+    use super::{backend::Backend, *};
+    use crate::{
+        enums::{InputMode, StreamStatus},
+        structures::ChatId,
+        NTgCall,
+    };
+
+    /// Copy a nul terminated string to a buffer, like NTgCalls does.
+    unsafe fn write(s: &[u8], buffer: *mut c_char, size: c_int) -> c_int {
+        if s.len() >= size as usize {
+            return libntgcalls_sys::NTG_ERR_TOO_SMALL;
+        }
+
+        ptr::copy_nonoverlapping(s.as_ptr().cast(), buffer, s.len());
+        *buffer.add(s.len()) = 0;
+        0
+    }
+
+    /// A fake `ntg_get_params` writing the inputs of the description to the buffer.
+    unsafe extern "C" fn fake_get_params(
+        _uid: u32,
+        _chat_id: i64,
+        desc: ntg_media_description_struct,
+        buffer: *mut c_char,
+        size: c_int,
+    ) -> c_int {
+        let audio = desc
+            .audio
+            .as_ref()
+            .map(|audio: &ntg_audio_description_struct| CStr::from_ptr(audio.input));
+        let video = desc
+            .video
+            .as_ref()
+            .map(|video: &ntg_video_description_struct| CStr::from_ptr(video.input));
+
+        let params = format!("{audio:?} {video:?}");
+        write(params.as_bytes(), buffer, size)
+    }
+
+    /// A fake `ntg_connect` overwriting the params it reads, like a C `char *` parameter allows.
+    unsafe extern "C" fn fake_connect(_uid: u32, _chat_id: i64, params: *mut c_char) -> c_int {
+        let len = CStr::from_ptr(params).to_bytes().len();
+        if len > 0 {
+            *params = b'x' as c_char;
+        }
+        len as c_int
+    }
+
+    /// A fake `ntg_init`, its uids are never handed out by the native library.
+    unsafe extern "C" fn fake_init() -> u32 {
+        static UID: AtomicU32 = AtomicU32::new(u32::MAX / 2);
+        UID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    unsafe extern "C" fn fake_destroy(_uid: u32) -> c_int {
+        0
+    }
+
+    unsafe extern "C" fn fake_on_disconnect(_uid: u32, callback: ntg_disconnect_callback) -> c_int {
+        assert!(callback.is_some());
+        0
+    }
+
+    /// A fake `ntg_change_stream` reading the inputs of the description.
+    unsafe extern "C" fn fake_change_stream(
+        _uid: u32,
+        _chat_id: i64,
+        desc: ntg_media_description_struct,
+    ) -> c_int {
+        assert!(desc.video.is_null());
+        CStr::from_ptr((*desc.audio).input).to_bytes().len() as c_int
+    }
+
+    /// The native functions, with fake ones for the functions used by [`NTgCall`] methods under test.
+    const FAKE: Backend = Backend {
+        ntg_init: fake_init,
+        ntg_destroy: fake_destroy,
+        ntg_on_disconnect: fake_on_disconnect,
+        ntg_get_params: fake_get_params,
+        ntg_connect: fake_connect,
+        ntg_change_stream: fake_change_stream,
+        ..Backend::NATIVE
+    };
+
+    fn desc() -> MediaDescription {
+        MediaDescription {
+            audio: Some(AudioDescription::new(InputMode::File, "audio.raw", 48000, 16, 2).unwrap()),
+            video: Some(
+                VideoDescription::new(
+                    InputMode::Shell,
+                    String::from("ffmpeg -i video.mp4"),
+                    1280,
+                    720,
+                    30,
+                )
+                .unwrap(),
+            ),
+        }
+    }
+
+    #[test]
+    fn ntgcall_methods() {
+        backend::with(FAKE, || {
+            let call = NTgCall::new();
+            let chat_id = ChatId::from_bot_api(-1001);
+
+            assert_eq!(
+                call.get_params(chat_id, desc()).unwrap(),
+                r#"Some("audio.raw") Some("ffmpeg -i video.mp4")"#
+            );
+            call.connect(chat_id, "{\"ufrag\":\"a\"}").unwrap();
+            assert_eq!(
+                call.connect(chat_id, "{\"ufrag\":\"a\0\"}"),
+                Err(crate::errors::NTgCallError::InvalidString)
+            );
+
+            let audio_only = MediaDescription {
+                video: None,
+                ..desc()
+            };
+            call.change_stream(chat_id, audio_only).unwrap();
+
+            // the modes are checked before NTgCalls gets them
+            let mut invalid = desc();
+            if let Some(video) = invalid.video.as_mut() {
+                video.input_mode = InputMode::File | InputMode::Shell;
+            }
+            assert_eq!(
+                call.change_stream(chat_id, invalid),
+                Err(crate::errors::NTgCallError::InvalidInputMode)
+            );
+
+            call.destroy().unwrap();
+        });
+    }
+
+    unsafe extern "C" fn fake_calls_count(_uid: u32) -> c_int {
+        1
+    }
+
+    /// A fake `ntg_calls` with a new call started before every read.
+    unsafe extern "C" fn fake_calls_too_small(
+        _uid: u32,
+        _buffer: *mut ntg_group_call_struct,
+        _size: c_int,
+    ) -> c_int {
+        libntgcalls_sys::NTG_ERR_TOO_SMALL
+    }
+
+    #[test]
+    fn calls_retries() {
+        // the second read gets a bigger buffer than the count
+        let backend = Backend {
+            ntg_calls_count: fake_calls_count,
+            ntg_calls: fake_calls,
+            ..FAKE
+        };
+        backend::with(backend, || {
+            let call = NTgCall::new();
+            assert_eq!(call.calls().unwrap().len(), 2);
+        });
+
+        // and the reads are bounded
+        let backend = Backend {
+            ntg_calls_count: fake_calls_count,
+            ntg_calls: fake_calls_too_small,
+            ..FAKE
+        };
+        backend::with(backend, || {
+            let call = NTgCall::new();
+            assert_eq!(
+                call.calls().unwrap_err(),
+                crate::errors::NTgCallError::UnknownException
+            );
+        });
+    }
+
+    #[test]
+    fn media_description() {
+        let desc = desc();
+
+        let mut buffer = StringBuffer::new(512);
+        let result = with_media_description(&desc, |raw| unsafe {
+            fake_get_params(0, 1, raw, buffer.as_mut_ptr(), buffer.size())
+        });
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            buffer.into_string().unwrap(),
+            r#"Some("audio.raw") Some("ffmpeg -i video.mp4")"#
+        );
+
+        let mut buffer = StringBuffer::new(512);
+        with_media_description(&MediaDescription::default(), |raw| unsafe {
+            assert!(raw.audio.is_null());
+            assert!(raw.video.is_null());
+            fake_get_params(0, 1, raw, buffer.as_mut_ptr(), buffer.size())
+        });
+        assert_eq!(buffer.into_string().unwrap(), "None None");
+    }
+
+    #[test]
+    fn c_string() {
+        let params = CString::new("{\"ufrag\":\"a\"}").unwrap();
+        let result = with_c_string(params, |ptr| unsafe { fake_connect(0, 1, ptr) });
+        assert_eq!(result, 13);
+
+        let result = with_c_string(CString::default(), |ptr| unsafe { fake_connect(0, 1, ptr) });
+        assert_eq!(result, 0);
+    }
+
+    /// A fake `ntg_calls` with two calls, the second one with a status unknown to the bindings.
+    unsafe extern "C" fn fake_calls(
+        _uid: u32,
+        buffer: *mut ntg_group_call_struct,
+        size: c_int,
+    ) -> c_int {
+        let calls = [
+            ntg_group_call_struct {
+                chatId: -1001,
+                status: libntgcalls_sys::ntg_stream_status_enum_NTG_PAUSED,
+            },
+            ntg_group_call_struct {
+                chatId: -1002,
+                status: 42,
+            },
+        ];
+
+        if (size as usize) < calls.len() {
+            return libntgcalls_sys::NTG_ERR_TOO_SMALL;
+        }
+
+        ptr::copy_nonoverlapping(calls.as_ptr(), buffer, calls.len());
+        0
+    }
+
+    #[test]
+    fn calls_buffer() {
+        let mut buffer = CallsBuffer::new(1);
+        let result = unsafe { fake_calls(0, buffer.as_mut_ptr(), buffer.size()) };
+        assert_eq!(result, libntgcalls_sys::NTG_ERR_TOO_SMALL);
+
+        // a call ended after counting them
+        let mut buffer = CallsBuffer::new(3);
+        let result = unsafe { fake_calls(0, buffer.as_mut_ptr(), buffer.size()) };
+        assert_eq!(result, 0);
+
+        let calls = buffer.into_calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].chat_id, ChatId::from_bot_api(-1001));
+        assert_eq!(calls[0].stream_status, StreamStatus::Paused);
+        assert_eq!(calls[1].chat_id, ChatId::from_bot_api(-1002));
+        assert_eq!(calls[1].stream_status, StreamStatus::Unknown(42));
+
+        assert!(CallsBuffer::new(0).into_calls().is_empty());
+    }
+
+    #[test]
+    fn string_buffer() {
+        let mut buffer = StringBuffer::new(8);
+        assert_eq!(buffer.size(), 8);
+
+        let result = unsafe { write(b"1.2.0", buffer.as_mut_ptr(), buffer.size()) };
+        assert_eq!(result, 0);
+        // the unused bytes after the nul terminator are not part of the string
+        assert_eq!(buffer.into_string().unwrap(), "1.2.0");
+
+        let mut buffer = StringBuffer::new(4);
+        let result = unsafe { write(b"1.2.0", buffer.as_mut_ptr(), buffer.size()) };
+        assert_eq!(result, libntgcalls_sys::NTG_ERR_TOO_SMALL);
+        assert_eq!(buffer.into_string().unwrap(), "");
+    }
+}
@@ -0,0 +1,488 @@
+//! Audio filters for the PCM sources produced in Rust.
+//!
+//! Wrap a [`PcmSource`] with [`Filtered`] to apply an [`AudioFilter`], or a [`FilterChain`]
+//! of them, while it plays. Since the source isn't restarted the changes are instant and
+//! the played time of the stream isn't reset, for example the volume of a chat can be changed
+//! with [`NTgCall::set_volume`](crate::NTgCall::set_volume) when its source is wrapped with a
+//! [`Gain`] created from [`NTgCall::volume`](crate::NTgCall::volume).
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use crate::{
+    pcm::Sample,
+    pipe::{PcmFormat, PcmSource},
+    structures::ChatId,
+};
+
+/// How long the volume takes to reach a new value, so changes don't click.
+const VOLUME_RAMP: Duration = Duration::from_millis(10);
+
+/// Time constant of [`Normalize`], how fast it follows the loudness of the source.
+const NORMALIZE_SPEED: Duration = Duration::from_secs(1);
+
+/// A filter applied to PCM audio.
+pub trait AudioFilter: Send {
+    /// Process interleaved samples in place, the samples are between `-1.0` and `1.0`.
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat);
+}
+
+impl<F: AudioFilter + ?Sized> AudioFilter for Box<F> {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        (**self).process(samples, format)
+    }
+}
+
+/// A volume shared between the [`Gain`] filters using it and the code changing it.
+///
+/// The volume is linear, `1.0` keeps the audio unchanged and `0.0` mutes it.
+#[derive(Debug, Clone)]
+pub struct Volume(Arc<AtomicU32>);
+
+impl Volume {
+    /// Create a new [`Volume`]
+    pub fn new(volume: f32) -> Self {
+        let this = Self(Arc::default());
+        this.set(volume);
+        this
+    }
+
+    /// The current volume.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Change the volume, negative values are treated as `0.0` and non finite ones are ignored.
+    pub fn set(&self, volume: f32) {
+        if volume.is_finite() {
+            self.0.store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// The [`Volume`] of each chat.
+///
+/// A volume is kept until its chat is stopped or disconnected. The volumes that are not used
+/// by a [`Gain`] and were not changed are dropped when a new chat is added, so looking up
+/// chats that never get a call doesn't grow the store.
+#[derive(Debug, Default)]
+pub(crate) struct Volumes(Mutex<HashMap<ChatId, Volume>>);
+
+impl Volumes {
+    fn volumes(&self) -> MutexGuard<'_, HashMap<ChatId, Volume>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn get(&self, chat_id: ChatId) -> Volume {
+        let mut volumes = self.volumes();
+
+        if let Some(volume) = volumes.get(&chat_id) {
+            return volume.clone();
+        }
+
+        // dropping them loses nothing, the chat gets the same default volume next time
+        volumes.retain(|_, volume| Arc::strong_count(&volume.0) > 1 || volume.get() != 1.0);
+
+        volumes.entry(chat_id).or_default().clone()
+    }
+
+    pub(crate) fn remove(&self, chat_id: ChatId) {
+        self.volumes().remove(&chat_id);
+    }
+
+    /// Move the volume of a chat, if any, to another store.
+    pub(crate) fn move_to(&self, chat_id: ChatId, other: &Volumes) {
+        if let Some(volume) = self.volumes().remove(&chat_id) {
+            other.volumes().insert(chat_id, volume);
+        }
+    }
+}
+
+/// Multiplies the samples by a [`Volume`], smoothly following its changes.
+pub struct Gain {
+    volume: Volume,
+    current: f32,
+}
+
+impl Gain {
+    /// Create a new [`Gain`] following the given volume.
+    pub fn new(volume: Volume) -> Self {
+        Self {
+            current: volume.get(),
+            volume,
+        }
+    }
+
+    /// Create a new [`Gain`] with a volume that never changes.
+    pub fn fixed(gain: f32) -> Self {
+        Self::new(Volume::new(gain))
+    }
+}
+
+impl AudioFilter for Gain {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        let target = self.volume.get();
+        let step = 1.0 / format.samples(VOLUME_RAMP).max(1) as f32;
+
+        for sample in samples {
+            if self.current != target {
+                self.current = if self.current < target {
+                    (self.current + step).min(target)
+                } else {
+                    (self.current - step).max(target)
+                };
+            }
+
+            *sample *= self.current;
+        }
+    }
+}
+
+/// Fades the audio in from silence when it starts.
+pub struct FadeIn {
+    length: Duration,
+    frame: u64,
+}
+
+impl FadeIn {
+    /// Create a new [`FadeIn`] lasting `length`.
+    pub fn new(length: Duration) -> Self {
+        Self { length, frame: 0 }
+    }
+}
+
+impl AudioFilter for FadeIn {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        let length = format.samples(self.length) / usize::from(format.channel_count.max(1));
+
+        for frame in samples.chunks_mut(usize::from(format.channel_count.max(1))) {
+            let level = match length {
+                0 => 1.0,
+                _ => (self.frame as f32 / length as f32).min(1.0),
+            };
+
+            frame.iter_mut().for_each(|sample| *sample *= level);
+            self.frame += 1;
+        }
+    }
+}
+
+/// Fades the audio out to silence, the audio stays silent after the fade.
+pub struct FadeOut {
+    start: Duration,
+    length: Duration,
+    frame: u64,
+}
+
+impl FadeOut {
+    /// Create a new [`FadeOut`] starting `start` after the beginning of the audio and lasting `length`.
+    pub fn new(start: Duration, length: Duration) -> Self {
+        Self {
+            start,
+            length,
+            frame: 0,
+        }
+    }
+}
+
+impl AudioFilter for FadeOut {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        let channels = usize::from(format.channel_count.max(1));
+        let start = (format.samples(self.start) / channels) as u64;
+        let length = (format.samples(self.length) / channels) as u64;
+
+        for frame in samples.chunks_mut(channels) {
+            let level = match self.frame.checked_sub(start) {
+                None => 1.0,
+                Some(elapsed) if elapsed >= length => 0.0,
+                Some(elapsed) => 1.0 - elapsed as f32 / length as f32,
+            };
+
+            frame.iter_mut().for_each(|sample| *sample *= level);
+            self.frame += 1;
+        }
+    }
+}
+
+/// Slowly adjusts the gain so the loudness (RMS) of the audio stays close to a target.
+pub struct Normalize {
+    target: f32,
+    max_gain: f32,
+    gain: f32,
+}
+
+impl Normalize {
+    /// Create a new [`Normalize`]
+    ///
+    /// ## Parameters
+    /// - `target`: The target RMS level, between `0.0` and `1.0`, `0.1` is about -20 dBFS.
+    /// - `max_gain`: The maximum gain applied to quiet audio, so silence isn't amplified to noise.
+    pub fn new(target: f32, max_gain: f32) -> Self {
+        Self {
+            target,
+            max_gain,
+            gain: 1.0,
+        }
+    }
+}
+
+impl AudioFilter for Normalize {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        if samples.is_empty() {
+            return;
+        }
+
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+        let previous = self.gain;
+
+        // keep the gain during silence
+        if rms > 1e-4 {
+            let desired = (self.target / rms).min(self.max_gain);
+            let speed = samples.len() as f32 / format.samples(NORMALIZE_SPEED).max(1) as f32;
+            self.gain += (desired - self.gain) * speed.min(1.0);
+        }
+
+        // ramp between the previous and the new gain over the chunk
+        let step = (self.gain - previous) / samples.len() as f32;
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample *= previous + step * (i + 1) as f32;
+        }
+    }
+}
+
+/// Reduces the gain when the peaks go above a threshold, so the audio never clips.
+pub struct Limiter {
+    threshold: f32,
+    release: Duration,
+    gain: f32,
+}
+
+impl Limiter {
+    /// Create a new [`Limiter`]
+    ///
+    /// ## Parameters
+    /// - `threshold`: The maximum peak level, between `0.0` and `1.0`.
+    /// - `release`: How long the gain takes to go back to `1.0` after a peak.
+    pub fn new(threshold: f32, release: Duration) -> Self {
+        Self {
+            threshold,
+            release,
+            gain: 1.0,
+        }
+    }
+}
+
+impl AudioFilter for Limiter {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        let channels = usize::from(format.channel_count.max(1));
+        let release = 1.0 / (format.samples(self.release) / channels).max(1) as f32;
+
+        for frame in samples.chunks_mut(channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+            self.gain = (self.gain + release).min(1.0);
+            if peak * self.gain > self.threshold {
+                self.gain = self.threshold / peak;
+            }
+
+            frame.iter_mut().for_each(|sample| *sample *= self.gain);
+        }
+    }
+}
+
+/// Multiple filters applied in order.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn AudioFilter>>,
+}
+
+impl FilterChain {
+    /// Create a new empty [`FilterChain`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter at the end of the chain.
+    #[must_use]
+    pub fn with<F: AudioFilter + 'static>(mut self, filter: F) -> Self {
+        self.push(filter);
+        self
+    }
+
+    /// Add a filter at the end of the chain.
+    pub fn push<F: AudioFilter + 'static>(&mut self, filter: F) {
+        self.filters.push(Box::new(filter));
+    }
+}
+
+impl AudioFilter for FilterChain {
+    fn process(&mut self, samples: &mut [f32], format: PcmFormat) {
+        for filter in &mut self.filters {
+            filter.process(samples, format);
+        }
+    }
+}
+
+/// A [`PcmSource`] with an [`AudioFilter`] applied to it.
+pub struct Filtered<S, F> {
+    source: S,
+    filter: F,
+    format: PcmFormat,
+    samples: Vec<f32>,
+}
+
+impl<S: PcmSource, F: AudioFilter> Filtered<S, F> {
+    /// Create a new [`Filtered`], `format` is the format of the source.
+    pub fn new(source: S, filter: F, format: PcmFormat) -> Self {
+        Self {
+            source,
+            filter,
+            format,
+            samples: Vec::new(),
+        }
+    }
+
+    /// The filter applied to the source.
+    pub fn filter_mut(&mut self) -> &mut F {
+        &mut self.filter
+    }
+}
+
+impl<S: PcmSource, F: AudioFilter> PcmSource for Filtered<S, F> {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        let read = self.source.read(buf);
+
+        self.samples.clear();
+        self.samples.extend(buf[..read].iter().map(|&s| s.to_f32()));
+
+        self.filter.process(&mut self.samples, self.format);
+
+        for (sample, &filtered) in buf.iter_mut().zip(&self.samples) {
+            *sample = i16::from_f32(filtered);
+        }
+
+        read
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipe::test::Constant;
+
+    const FORMAT: PcmFormat = PcmFormat::new(1000, 1);
+
+    #[test]
+    fn gain_follows_volume() {
+        let volume = Volume::new(1.0);
+        let mut gain = Gain::new(volume.clone());
+
+        let mut samples = [0.5; 20];
+        gain.process(&mut samples, FORMAT);
+        assert_eq!(samples, [0.5; 20]);
+
+        // the 10 ms ramp lasts 10 samples at 1000 Hz
+        volume.set(0.0);
+        let mut samples = [0.5; 20];
+        gain.process(&mut samples, FORMAT);
+        assert!(samples[0] > 0.4);
+        assert_eq!(samples[10..], [0.0; 10]);
+
+        volume.set(f32::NAN);
+        volume.set(-1.0);
+        assert_eq!(volume.get(), 0.0);
+    }
+
+    #[test]
+    fn unused_volumes_are_dropped() {
+        let volumes = Volumes::default();
+
+        let used = volumes.get(ChatId::from_bot_api(-1));
+        volumes.get(ChatId::from_bot_api(-2)).set(0.5);
+        for id in 3..100 {
+            volumes.get(ChatId::from_bot_api(-id));
+        }
+
+        // the last chat, the one used by a gain and the changed one
+        assert_eq!(volumes.volumes().len(), 3);
+
+        used.set(0.25);
+        assert_eq!(volumes.get(ChatId::from_bot_api(-1)).get(), 0.25);
+        assert_eq!(volumes.get(ChatId::from_bot_api(-2)).get(), 0.5);
+    }
+
+    #[test]
+    fn fades() {
+        let mut fade_in = FadeIn::new(Duration::from_millis(10));
+        let mut samples = [1.0; 20];
+        fade_in.process(&mut samples, FORMAT);
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[5], 0.5);
+        assert_eq!(samples[10..], [1.0; 10]);
+
+        let mut fade_out = FadeOut::new(Duration::from_millis(5), Duration::from_millis(10));
+        let mut samples = [1.0; 20];
+        fade_out.process(&mut samples, FORMAT);
+        assert_eq!(samples[..5], [1.0; 5]);
+        assert_eq!(samples[10], 0.5);
+        assert_eq!(samples[15..], [0.0; 5]);
+    }
+
+    #[test]
+    fn limiter_never_clips() {
+        let mut limiter = Limiter::new(0.5, Duration::from_millis(50));
+        let mut samples: Vec<f32> = (0..100).map(|i| (i as f32 / 10.0).sin()).collect();
+        limiter.process(&mut samples, FORMAT);
+
+        assert!(samples.iter().all(|s| s.abs() <= 0.5 + f32::EPSILON));
+        assert!(samples.iter().any(|s| s.abs() > 0.45));
+    }
+
+    #[test]
+    fn normalize_raises_quiet_audio() {
+        let mut normalize = Normalize::new(0.2, 10.0);
+        let mut samples = [0.05; 1000];
+
+        for _ in 0..5 {
+            samples = [0.05; 1000];
+            normalize.process(&mut samples, FORMAT);
+        }
+
+        assert!((samples[999] - 0.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn filtered_source() {
+        let volume = Volume::new(0.5);
+        let chain = FilterChain::new()
+            .with(Gain::new(volume.clone()))
+            .with(Limiter::new(0.25, Duration::from_millis(10)));
+
+        let source = Constant {
+            sample: 16384,
+            remaining: Some(30),
+        };
+        let mut filtered = Filtered::new(source, chain, FORMAT);
+
+        let mut buf = [0; 20];
+        assert_eq!(filtered.read(&mut buf), 20);
+        assert_eq!(buf, [8192; 20]);
+
+        // the limiter catches the volume going up
+        volume.set(2.0);
+        assert_eq!(filtered.read(&mut buf), 10);
+        assert!(buf[..10].iter().all(|&s| s <= 8192));
+    }
+}
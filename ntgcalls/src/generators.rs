@@ -0,0 +1,711 @@
+//! Deterministic test signals for audio and video.
+//!
+//! The generators produce the same content on every run, so an end-to-end test of a deployment
+//! doesn't depend on someone's media files:
+//! - [`AudioGenerator`] is a [`PcmSource`] of an [`AudioSignal`]: a sine tone, a frequency
+//!   sweep, white noise or silence.
+//! - [`VideoGenerator`] is a [`VideoSource`] of a [`VideoPattern`]: SMPTE color bars, a moving
+//!   test pattern or the time of the frame burned into the picture.
+//!
+//! Read them directly in tests, or stream them to a call on Unix, the pipes give the matching
+//! [`AudioDescription`](crate::structures::AudioDescription) and
+//! [`VideoDescription`](crate::structures::VideoDescription):
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     generators::{AudioGenerator, AudioSignal, VideoGenerator, VideoPattern},
+//!     pipe::PcmFormat,
+//!     profiles::StreamProfile,
+//!     structures::{ChatId, MediaDescription},
+//!     NTgCall,
+//! };
+//!
+//! let tone = AudioSignal::Sine {
+//!     frequency: 1000.0,
+//!     amplitude: 0.5,
+//! };
+//! let audio = AudioGenerator::new(tone, PcmFormat::new(48000, 2)).spawn_pipe()?;
+//!
+//! let profile = StreamProfile::VIDEO_720P.video.unwrap();
+//! let video = VideoGenerator::new(VideoPattern::ColorBars, profile)?.spawn_pipe()?;
+//!
+//! let call = NTgCall::new();
+//! let desc = MediaDescription {
+//!     audio: Some(audio.audio_description()),
+//!     video: Some(video.video_description()),
+//! };
+//! call.get_params(ChatId::from_channel(1234567890), desc)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+//!
+//! Keep the pipes alive for the duration of the stream, they're removed when dropped.
+
+use std::{f64::consts::TAU, time::Duration};
+
+use crate::{
+    errors::ProfileError,
+    pcm::{Random, Sample},
+    pipe::{self, PcmFormat, PcmSource, VideoSource},
+    profiles::VideoProfile,
+};
+
+/// A test signal of an [`AudioGenerator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AudioSignal {
+    /// A sine tone.
+    Sine {
+        /// Frequency of the tone in Hz.
+        frequency: f64,
+        /// Peak amplitude, between `0.0` and `1.0`.
+        amplitude: f32,
+    },
+    /// A sine tone going linearly from one frequency to another, then starting over.
+    Sweep {
+        /// Frequency at the start of the sweep in Hz.
+        from: f64,
+        /// Frequency at the end of the sweep in Hz.
+        to: f64,
+        /// Duration of one sweep.
+        period: Duration,
+        /// Peak amplitude, between `0.0` and `1.0`.
+        amplitude: f32,
+    },
+    /// Uniform white noise, pseudo-random with a fixed seed.
+    WhiteNoise {
+        /// Peak amplitude, between `0.0` and `1.0`.
+        amplitude: f32,
+    },
+    /// Digital silence.
+    Silence,
+}
+
+/// A [`PcmSource`] generating an [`AudioSignal`], the same signal on every channel.
+#[derive(Debug, Clone)]
+pub struct AudioGenerator {
+    signal: AudioSignal,
+    format: PcmFormat,
+    /// Frames generated since the start.
+    frame: u64,
+    /// Phase of the tone, in turns.
+    phase: f64,
+    random: Random,
+    /// Sample of the current frame and the next channel to write it to.
+    current: i16,
+    channel: u8,
+    /// Samples left before the end, `None` for an endless signal.
+    remaining: Option<usize>,
+}
+
+impl AudioGenerator {
+    /// Create a new endless [`AudioGenerator`]
+    ///
+    /// ## Parameters
+    /// - `signal`: The signal to generate.
+    /// - `format`: Format of the generated samples.
+    pub fn new(signal: AudioSignal, format: PcmFormat) -> Self {
+        Self {
+            signal,
+            format,
+            frame: 0,
+            phase: 0.0,
+            random: Random::new(),
+            current: 0,
+            channel: 0,
+            remaining: None,
+        }
+    }
+
+    /// End the signal after `duration`.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.remaining = Some(self.format.samples(duration));
+        self
+    }
+
+    /// Format of the generated samples.
+    pub fn format(&self) -> PcmFormat {
+        self.format
+    }
+
+    /// Stream the signal through a new [`PcmPipe`](crate::pipe::PcmPipe).
+    ///
+    /// ## Return
+    /// An [`std::io::Error`] in case the pipe couldn't be created.
+    #[cfg(unix)]
+    pub fn spawn_pipe(self) -> std::io::Result<crate::pipe::PcmPipe> {
+        let format = self.format;
+        crate::pipe::PcmPipe::spawn(self, format)
+    }
+
+    /// Value of the next frame, between `-1.0` and `1.0`.
+    fn next_value(&mut self) -> f32 {
+        let sample_rate = f64::from(self.format.sample_rate.max(1));
+
+        let value = match self.signal {
+            AudioSignal::Sine {
+                frequency,
+                amplitude,
+            } => {
+                let value = amplitude * (TAU * self.phase).sin() as f32;
+                self.advance(frequency / sample_rate);
+                value
+            }
+            AudioSignal::Sweep {
+                from,
+                to,
+                period,
+                amplitude,
+            } => {
+                let frames = (period.as_secs_f64() * sample_rate).max(1.0) as u64;
+                let progress = (self.frame % frames) as f64 / frames as f64;
+
+                let value = amplitude * (TAU * self.phase).sin() as f32;
+                self.advance((from + (to - from) * progress) / sample_rate);
+                value
+            }
+            AudioSignal::WhiteNoise { amplitude } => {
+                amplitude * (self.random.next_f32() * 2.0 - 1.0)
+            }
+            AudioSignal::Silence => 0.0,
+        };
+
+        self.frame += 1;
+        value
+    }
+
+    fn advance(&mut self, turns: f64) {
+        // keep the phase small, so it stays precise
+        self.phase = (self.phase + turns).fract();
+    }
+}
+
+impl PcmSource for AudioGenerator {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        let count = self.remaining.map_or(buf.len(), |r| r.min(buf.len()));
+
+        for sample in &mut buf[..count] {
+            if self.channel == 0 {
+                self.current = i16::from_f32(self.next_value());
+            }
+
+            *sample = self.current;
+            self.channel = (self.channel + 1) % self.format.channel_count.max(1);
+        }
+
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= count;
+        }
+
+        count
+    }
+}
+
+/// A test pattern of a [`VideoGenerator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoPattern {
+    /// The SMPTE color bars at 75%, with the PLUGE bars at the bottom.
+    ColorBars,
+    /// A scrolling color gradient with a white square bouncing around, to check that the
+    /// video is smooth.
+    Moving,
+    /// The time of the frame and its number, in white on a dark background.
+    Timestamp,
+}
+
+/// A [`VideoSource`] generating a [`VideoPattern`].
+#[derive(Debug, Clone)]
+pub struct VideoGenerator {
+    pattern: VideoPattern,
+    profile: VideoProfile,
+    /// Frames generated since the start.
+    frame: u64,
+    /// Frames left before the end, `None` for an endless video.
+    remaining: Option<u64>,
+}
+
+impl VideoGenerator {
+    /// Create a new endless [`VideoGenerator`]
+    ///
+    /// ## Parameters
+    /// - `pattern`: The pattern to generate.
+    /// - `profile`: Size and rate of the frames.
+    ///
+    /// ## Return
+    /// A [`ProfileError`] if the profile is invalid or its size is odd, YUV420p frames
+    /// have even sizes.
+    pub fn new(pattern: VideoPattern, profile: VideoProfile) -> Result<Self, ProfileError> {
+        profile.validate()?;
+
+        if !profile.width.is_multiple_of(2) || !profile.height.is_multiple_of(2) {
+            return Err(ProfileError::Invalid("width and height must be even"));
+        }
+
+        Ok(Self {
+            pattern,
+            profile,
+            frame: 0,
+            remaining: None,
+        })
+    }
+
+    /// End the video after `duration`.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        let frames = u128::from(self.profile.fps) * duration.as_micros() / 1_000_000;
+        self.remaining = Some(frames as u64);
+        self
+    }
+
+    /// Size and rate of the frames.
+    pub fn profile(&self) -> VideoProfile {
+        self.profile
+    }
+
+    /// Stream the video through a new [`VideoPipe`](crate::pipe::VideoPipe).
+    ///
+    /// ## Return
+    /// An [`std::io::Error`] in case the pipe couldn't be created.
+    #[cfg(unix)]
+    pub fn spawn_pipe(self) -> std::io::Result<crate::pipe::VideoPipe> {
+        let profile = self.profile;
+        crate::pipe::VideoPipe::spawn(self, profile)
+    }
+
+    fn color_bars(&self, frame: &mut [u8]) {
+        let (width, height) = self.size();
+
+        paint(frame, width, height, |x, y| {
+            // the positions of the SMPTE bars, in 84ths of the width
+            let column = x * 84 / width;
+
+            if y < height * 2 / 3 {
+                BARS[column / 12]
+            } else if y < height * 3 / 4 {
+                REVERSE_BARS[column / 12]
+            } else {
+                match column {
+                    0..=14 => MINUS_I,
+                    15..=29 => WHITE,
+                    30..=44 => PLUS_Q,
+                    60..=63 => SUPER_BLACK,
+                    68..=71 => GRAY_4,
+                    _ => BLACK,
+                }
+            }
+        });
+    }
+
+    fn moving(&self, frame: &mut [u8]) {
+        let (width, height) = self.size();
+        // the square fits narrow pictures too
+        let side = (height / 8).max(2).min(width).min(height);
+
+        // the square crosses the picture in two seconds
+        let speed = (width / (2 * usize::from(self.profile.fps))).max(1);
+        let left = bounce(self.frame as usize * speed, width - side);
+        let top = bounce(self.frame as usize * speed * 2 / 3, height - side);
+        let scroll = self.frame as usize * 4;
+
+        paint(frame, width, height, |x, y| {
+            if (left..left + side).contains(&x) && (top..top + side).contains(&y) {
+                return WHITE;
+            }
+
+            Yuv {
+                y: 16 + ((x + y + scroll) % 220) as u8,
+                u: (16 + x * 224 / width) as u8,
+                v: (16 + y * 224 / height) as u8,
+            }
+        });
+    }
+
+    fn timestamp(&self, frame: &mut [u8]) {
+        let (width, height) = self.size();
+        let fps = u64::from(self.profile.fps);
+
+        let millis = self.frame * 1000 / fps;
+        let lines = [
+            format!(
+                "{:02}:{:02}:{:02}.{:03}",
+                millis / 3_600_000,
+                millis / 60_000 % 60,
+                millis / 1000 % 60,
+                millis % 1000
+            ),
+            format!("{:06}", self.frame),
+        ];
+        let lines = lines.map(|line| line.bytes().map(glyph).collect::<Vec<_>>());
+
+        // every character takes 6x8 cells, and the text takes up to 3/4 of the picture
+        let columns = lines.iter().map(Vec::len).max().unwrap_or(1);
+        let scale = (width * 3 / 4 / (columns * 6))
+            .min(height * 3 / 4 / (lines.len() * 8))
+            .max(1);
+
+        let left = width.saturating_sub(columns * 6 * scale) / 2;
+        let top = height.saturating_sub(lines.len() * 8 * scale) / 2;
+
+        paint(frame, width, height, |x, y| {
+            let (Some(x), Some(y)) = (x.checked_sub(left), y.checked_sub(top)) else {
+                return DARK_GRAY;
+            };
+
+            let (column, row) = (x / scale, y / scale);
+            let glyph = lines.get(row / 8).and_then(|line| line.get(column / 6));
+
+            match glyph {
+                Some(glyph) if column % 6 < 5 && row % 8 < 7 => {
+                    if glyph[row % 8] & (0x10 >> (column % 6)) != 0 {
+                        WHITE
+                    } else {
+                        DARK_GRAY
+                    }
+                }
+                _ => DARK_GRAY,
+            }
+        });
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (
+            usize::from(self.profile.width),
+            usize::from(self.profile.height),
+        )
+    }
+}
+
+impl VideoSource for VideoGenerator {
+    fn read_frame(&mut self, frame: &mut [u8]) -> bool {
+        debug_assert_eq!(frame.len(), pipe::frame_size(self.profile));
+
+        match &mut self.remaining {
+            Some(0) => return false,
+            Some(remaining) => *remaining -= 1,
+            None => {}
+        }
+
+        match self.pattern {
+            VideoPattern::ColorBars => self.color_bars(frame),
+            VideoPattern::Moving => self.moving(frame),
+            VideoPattern::Timestamp => self.timestamp(frame),
+        }
+
+        self.frame += 1;
+        true
+    }
+}
+
+/// A color in the limited range of BT.601.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Yuv {
+    y: u8,
+    u: u8,
+    v: u8,
+}
+
+const fn yuv(y: u8, u: u8, v: u8) -> Yuv {
+    Yuv { y, u, v }
+}
+
+const WHITE: Yuv = yuv(235, 128, 128);
+const BLACK: Yuv = yuv(16, 128, 128);
+const DARK_GRAY: Yuv = yuv(40, 128, 128);
+/// 4 IRE below and above black, for the PLUGE bars.
+const SUPER_BLACK: Yuv = yuv(7, 128, 128);
+const GRAY_4: Yuv = yuv(25, 128, 128);
+const MINUS_I: Yuv = yuv(61, 153, 99);
+const PLUS_Q: Yuv = yuv(35, 174, 152);
+
+/// White, yellow, cyan, green, magenta, red and blue at 75%.
+const BARS: [Yuv; 7] = [
+    yuv(180, 128, 128),
+    yuv(162, 44, 142),
+    yuv(131, 156, 44),
+    yuv(112, 72, 58),
+    yuv(84, 184, 198),
+    yuv(65, 100, 212),
+    yuv(35, 212, 114),
+];
+
+/// Blue, black, magenta, black, cyan, black and white at 75%.
+const REVERSE_BARS: [Yuv; 7] = [BARS[6], BLACK, BARS[4], BLACK, BARS[2], BLACK, BARS[0]];
+
+/// Fill a YUV420p frame with the color of every pixel, the chroma of a 2x2 block is the one
+/// of its top left pixel.
+fn paint<F: Fn(usize, usize) -> Yuv>(frame: &mut [u8], width: usize, height: usize, color: F) {
+    let (luma, chroma) = frame.split_at_mut(width * height);
+    let (u, v) = chroma.split_at_mut(chroma.len() / 2);
+
+    for (y, row) in luma.chunks_exact_mut(width).enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = color(x, y).y;
+        }
+    }
+
+    let rows = u
+        .chunks_exact_mut(width / 2)
+        .zip(v.chunks_exact_mut(width / 2));
+    for (y, (u, v)) in rows.enumerate() {
+        for (x, (u, v)) in u.iter_mut().zip(v).enumerate() {
+            let color = color(x * 2, y * 2);
+            *u = color.u;
+            *v = color.v;
+        }
+    }
+}
+
+/// Position after moving `distance` back and forth between `0` and `max`.
+fn bounce(distance: usize, max: usize) -> usize {
+    if max == 0 {
+        return 0;
+    }
+
+    let position = distance % (2 * max);
+    if position > max {
+        2 * max - position
+    } else {
+        position
+    }
+}
+
+/// The 5x7 bitmap of a character, one byte per row with the leftmost pixel in the fifth bit.
+fn glyph(character: u8) -> [u8; 7] {
+    match character {
+        b'0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        b'1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        b'2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        b'3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        b'4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        b'5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        b'6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        b'7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        b'8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        b'9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        b':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        b'.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        _ => [0; 7],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pcm::test::level;
+
+    fn generate(signal: AudioSignal, samples: usize) -> Vec<f32> {
+        let mut generator = AudioGenerator::new(signal, PcmFormat::new(48000, 1));
+        let mut buf = vec![0; samples];
+        assert_eq!(generator.read(&mut buf), samples);
+
+        buf.into_iter().map(Sample::to_f32).collect()
+    }
+
+    const PROFILE: VideoProfile = VideoProfile {
+        width: 168,
+        height: 96,
+        fps: 25,
+    };
+
+    fn frames(pattern: VideoPattern, count: usize) -> Vec<Vec<u8>> {
+        let mut generator = VideoGenerator::new(pattern, PROFILE).unwrap();
+
+        (0..count)
+            .map(|_| {
+                let mut frame = vec![0; pipe::frame_size(PROFILE)];
+                assert!(generator.read_frame(&mut frame));
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine() {
+        let samples = generate(
+            AudioSignal::Sine {
+                frequency: 1000.0,
+                amplitude: 0.5,
+            },
+            4800,
+        );
+
+        assert!((level(&samples, 1000.0, 48000) - 0.5).abs() < 0.01);
+        assert!(level(&samples, 3000.0, 48000) < 0.01);
+        assert!(samples.iter().all(|s| s.abs() <= 0.5));
+    }
+
+    #[test]
+    fn sweep() {
+        let signal = AudioSignal::Sweep {
+            from: 100.0,
+            to: 10000.0,
+            period: Duration::from_secs(1),
+            amplitude: 0.5,
+        };
+        let samples = generate(signal, 96000);
+
+        // low frequencies at the start of every sweep, high ones at the end
+        for start in [0, 48000] {
+            let first = &samples[start..start + 4800];
+            let last = &samples[start + 43200..start + 48000];
+            assert!(level(first, 300.0, 48000) > level(first, 9500.0, 48000) * 4.0);
+            assert!(level(last, 9500.0, 48000) > level(last, 300.0, 48000) * 4.0);
+        }
+    }
+
+    #[test]
+    fn white_noise() {
+        let signal = AudioSignal::WhiteNoise { amplitude: 0.25 };
+        let samples = generate(signal, 48000);
+
+        // deterministic
+        assert_eq!(samples, generate(signal, 48000));
+
+        assert!(samples.iter().all(|s| s.abs() <= 0.25));
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.01);
+        assert!(samples.iter().any(|&s| s > 0.2) && samples.iter().any(|&s| s < -0.2));
+    }
+
+    #[test]
+    fn silence_with_duration() {
+        let format = PcmFormat::new(8000, 2);
+        let mut generator =
+            AudioGenerator::new(AudioSignal::Silence, format).with_duration(Duration::from_secs(1));
+        assert_eq!(generator.format(), format);
+
+        let mut buf = [1; 10000];
+        assert_eq!(generator.read(&mut buf), 10000);
+        assert_eq!(generator.read(&mut buf), 6000);
+        assert_eq!(generator.read(&mut buf), 0);
+        assert!(buf.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn same_sample_on_every_channel() {
+        let signal = AudioSignal::Sine {
+            frequency: 440.0,
+            amplitude: 1.0,
+        };
+        let mut generator = AudioGenerator::new(signal, PcmFormat::new(48000, 2));
+
+        // a read can end in the middle of a frame
+        let mut buf = [0; 101];
+        generator.read(&mut buf[..51]);
+        generator.read(&mut buf[51..]);
+
+        assert!(buf[..100].chunks_exact(2).all(|frame| frame[0] == frame[1]));
+        assert!(buf[..100].chunks_exact(2).any(|frame| frame[0] != 0));
+    }
+
+    #[test]
+    fn color_bars() {
+        let frames = frames(VideoPattern::ColorBars, 2);
+        assert_eq!(frames[0], frames[1]);
+
+        let frame = &frames[0];
+        let (width, height) = (168, 96);
+        let (luma, chroma) = frame.split_at(width * height);
+        let (u, v) = chroma.split_at(chroma.len() / 2);
+
+        // the top bars are 24 pixels wide
+        for (i, bar) in BARS.iter().enumerate() {
+            let x = i * 24 + 12;
+            assert_eq!(luma[10 * width + x], bar.y);
+            assert_eq!(u[5 * width / 2 + x / 2], bar.u);
+            assert_eq!(v[5 * width / 2 + x / 2], bar.v);
+        }
+
+        // the reverse bars and the PLUGE
+        assert_eq!(luma[68 * width + 30], BLACK.y);
+        assert_eq!(luma[68 * width + 60], BARS[4].y);
+        assert_eq!(luma[90 * width], MINUS_I.y);
+        assert_eq!(luma[90 * width + 121], SUPER_BLACK.y);
+        assert_eq!(luma[90 * width + 137], GRAY_4.y);
+    }
+
+    #[test]
+    fn moving() {
+        let moving = frames(VideoPattern::Moving, 3);
+        assert_ne!(moving[0], moving[1]);
+        assert_ne!(moving[1], moving[2]);
+        assert_eq!(moving, frames(VideoPattern::Moving, 3));
+
+        // the square is in the top left corner at the start
+        assert_eq!(moving[0][0], WHITE.y);
+        assert_ne!(moving[0][167], WHITE.y);
+    }
+
+    #[test]
+    fn tiny_profiles() {
+        let profiles = [(2, 64), (64, 2), (2, 2)].map(|(width, height)| VideoProfile {
+            width,
+            height,
+            fps: 25,
+        });
+
+        for profile in profiles {
+            for pattern in [
+                VideoPattern::ColorBars,
+                VideoPattern::Moving,
+                VideoPattern::Timestamp,
+            ] {
+                let mut generator = VideoGenerator::new(pattern, profile).unwrap();
+                let mut frame = vec![0; pipe::frame_size(profile)];
+
+                for _ in 0..30 {
+                    assert!(generator.read_frame(&mut frame));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn timestamp() {
+        let timestamps = frames(VideoPattern::Timestamp, 26);
+        assert_ne!(timestamps[0], timestamps[1]);
+        assert_ne!(timestamps[0], timestamps[25]);
+        assert_eq!(timestamps, frames(VideoPattern::Timestamp, 26));
+
+        // white text on a dark background
+        let luma = 168 * 96;
+        assert!(timestamps[0][..luma].contains(&WHITE.y));
+        assert!(timestamps[0][..luma]
+            .iter()
+            .all(|&y| y == WHITE.y || y == DARK_GRAY.y));
+        assert!(timestamps[0][luma..].iter().all(|&c| c == 128));
+    }
+
+    #[test]
+    fn video_duration() {
+        let mut generator = VideoGenerator::new(VideoPattern::Moving, PROFILE)
+            .unwrap()
+            .with_duration(Duration::from_millis(200));
+        assert_eq!(generator.profile(), PROFILE);
+
+        let mut frame = vec![0; pipe::frame_size(PROFILE)];
+        assert_eq!(
+            std::iter::from_fn(|| generator.read_frame(&mut frame).then_some(())).count(),
+            5
+        );
+    }
+
+    #[test]
+    fn invalid_profile() {
+        let odd = VideoProfile {
+            width: 641,
+            ..PROFILE
+        };
+        assert!(matches!(
+            VideoGenerator::new(VideoPattern::ColorBars, odd),
+            Err(ProfileError::Invalid(_))
+        ));
+
+        let still = VideoProfile { fps: 0, ..PROFILE };
+        assert!(VideoGenerator::new(VideoPattern::Timestamp, still).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! [`GroupCallTransport`] implementation for the [`grammers`](https://github.com/Lonami/grammers)
+//! MTProto client.
+//!
+//! With the `grammers` feature a [`Client`] wrapped in a [`GrammersTransport`] can be passed to
+//! [`GroupCalls`](crate::group_call::GroupCalls). The chats are added to the transport as peers,
+//! for example the peer of a message, and the calls are joined as the account:
+//!
+//! ```no_run
+//! # fn run(client: grammers_client::Client, peer: grammers_client::session::types::PeerRef) -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     grammers::GrammersTransport, group_call::GroupCalls, structures::MediaDescription, NTgCall,
+//! };
+//!
+//! let transport = GrammersTransport::new(client)?;
+//! let chat_id = transport.add_peer(peer)?;
+//!
+//! let calls = GroupCalls::new(NTgCall::new(), transport)?;
+//! calls.join(chat_id, MediaDescription::default())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! grammers is async while the transport is blocking: the requests are sent from a runtime
+//! owned by the transport, the connection itself keeps running on the runtime of the `SenderPool`.
+//! Call [`GroupCalls::join`](crate::group_call::GroupCalls::join) and
+//! [`GroupCalls::leave`](crate::group_call::GroupCalls::leave) outside of any async context,
+//! for example with `tokio::task::spawn_blocking`.
+
+use std::{collections::HashMap, io, sync::Mutex};
+
+use grammers_client::{
+    session::types::{PeerKind, PeerRef},
+    tl, Client,
+};
+use tokio::runtime::Runtime;
+
+use crate::{
+    errors::GrammersError,
+    group_call::GroupCallTransport,
+    structures::{ChatId, EditParticipantParams, MediaState},
+};
+
+/// A grammers [`Client`] sending the group call requests of [`GroupCalls`](crate::group_call::GroupCalls).
+pub struct GrammersTransport {
+    client: Client,
+    runtime: Runtime,
+    /// The chats added with [`GrammersTransport::add_peer`].
+    peers: Mutex<HashMap<ChatId, PeerRef>>,
+    /// The group calls joined, resolved on join and kept until leave.
+    calls: Mutex<HashMap<ChatId, tl::enums::InputGroupCall>>,
+}
+
+impl GrammersTransport {
+    /// Create a new transport sending the requests with the given client.
+    ///
+    /// ## Return
+    /// An [`io::Error`] if the runtime the requests are sent from can't be created.
+    pub fn new(client: Client) -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()?;
+
+        Ok(Self {
+            client,
+            runtime,
+            peers: Mutex::default(),
+            calls: Mutex::default(),
+        })
+    }
+
+    /// Add a basic group, a supergroup or a channel whose group calls can be joined.
+    ///
+    /// ## Return
+    /// The [`ChatId`] of the peer, or a [`GrammersError::NotAGroup`] if the peer is a user.
+    pub fn add_peer<P: Into<PeerRef>>(&self, peer: P) -> Result<ChatId, GrammersError> {
+        let peer = peer.into();
+        let chat_id = chat_id(peer).ok_or(GrammersError::NotAGroup)?;
+
+        self.peers().insert(chat_id, peer);
+
+        Ok(chat_id)
+    }
+
+    /// The underlying client.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn peers(&self) -> std::sync::MutexGuard<'_, HashMap<ChatId, PeerRef>> {
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn calls(&self) -> std::sync::MutexGuard<'_, HashMap<ChatId, tl::enums::InputGroupCall>> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Resolve the active group call of an added chat.
+    fn resolve(&self, chat_id: ChatId) -> Result<tl::enums::InputGroupCall, GrammersError> {
+        let peer = self
+            .peers()
+            .get(&chat_id)
+            .copied()
+            .ok_or(GrammersError::ChatNotFound(chat_id))?;
+
+        self.runtime
+            .block_on(group_call(&self.client, peer))?
+            .ok_or(GrammersError::NoGroupCall(chat_id))
+    }
+
+    /// The group call of a joined chat, resolved again if it was joined by another client.
+    fn joined(&self, chat_id: ChatId) -> Result<tl::enums::InputGroupCall, GrammersError> {
+        match self.calls().get(&chat_id) {
+            Some(call) => Ok(call.clone()),
+            None => self.resolve(chat_id),
+        }
+    }
+}
+
+impl GroupCallTransport for GrammersTransport {
+    type Error = GrammersError;
+
+    fn join_group_call(
+        &self,
+        chat_id: ChatId,
+        params: &str,
+        state: &MediaState,
+    ) -> Result<String, Self::Error> {
+        // the call may have been restarted since the last join
+        let call = self.resolve(chat_id)?;
+
+        let updates = self.runtime.block_on(
+            self.client.invoke(&tl::functions::phone::JoinGroupCall {
+                muted: state.muted,
+                video_stopped: state.video_stopped,
+                call: call.clone(),
+                join_as: tl::enums::InputPeer::PeerSelf,
+                invite_hash: None,
+                public_key: None,
+                block: None,
+                params: tl::types::DataJson {
+                    data: params.to_owned(),
+                }
+                .into(),
+            }),
+        )?;
+
+        let params =
+            connection_params(updates).ok_or(GrammersError::NoConnectionParams(chat_id))?;
+        self.calls().insert(chat_id, call);
+
+        Ok(params)
+    }
+
+    fn leave_group_call(&self, chat_id: ChatId) -> Result<(), Self::Error> {
+        let call = self.joined(chat_id)?;
+
+        self.runtime.block_on(
+            self.client
+                .invoke(&tl::functions::phone::LeaveGroupCall { call, source: 0 }),
+        )?;
+        self.calls().remove(&chat_id);
+
+        Ok(())
+    }
+
+    fn edit_group_call_participant(
+        &self,
+        chat_id: ChatId,
+        params: &EditParticipantParams,
+    ) -> Result<(), Self::Error> {
+        let call = self.joined(chat_id)?;
+
+        self.runtime.block_on(self.client.invoke(
+            &tl::functions::phone::EditGroupCallParticipant {
+                call,
+                participant: tl::enums::InputPeer::PeerSelf,
+                muted: params.muted,
+                volume: None,
+                raise_hand: None,
+                video_stopped: params.video_stopped,
+                video_paused: params.video_paused,
+                presentation_paused: None,
+            },
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// Find the active group call of a basic group, a supergroup or a channel.
+///
+/// ## Return
+/// [`None`] if the chat has no active group call.
+pub async fn group_call(
+    client: &Client,
+    peer: PeerRef,
+) -> Result<Option<tl::enums::InputGroupCall>, GrammersError> {
+    let full_chat = match peer.id.kind() {
+        PeerKind::Channel => {
+            client
+                .invoke(&tl::functions::channels::GetFullChannel {
+                    channel: (&peer).into(),
+                })
+                .await?
+        }
+        PeerKind::Chat => {
+            client
+                .invoke(&tl::functions::messages::GetFullChat {
+                    chat_id: peer.id.bare_id().unwrap_or_default(),
+                })
+                .await?
+        }
+        PeerKind::User => return Err(GrammersError::NotAGroup),
+    };
+
+    let tl::enums::messages::ChatFull::Full(full_chat) = full_chat;
+    let call = match full_chat.full_chat {
+        tl::enums::ChatFull::Full(chat) => chat.call,
+        tl::enums::ChatFull::ChannelFull(channel) => channel.call,
+    };
+
+    Ok(call)
+}
+
+/// The [`ChatId`] of a peer, [`None`] if it's a user.
+fn chat_id(peer: PeerRef) -> Option<ChatId> {
+    match peer.id.kind() {
+        PeerKind::Chat | PeerKind::Channel => peer.id.bot_api_dialog_id().map(ChatId::from_bot_api),
+        PeerKind::User => None,
+    }
+}
+
+/// The params of the `updateGroupCallConnection` sent in response to `phone.joinGroupCall`.
+fn connection_params(updates: tl::enums::Updates) -> Option<String> {
+    let updates = match updates {
+        tl::enums::Updates::Updates(updates) => updates.updates,
+        tl::enums::Updates::Combined(updates) => updates.updates,
+        tl::enums::Updates::UpdateShort(update) => vec![update.update],
+        _ => return None,
+    };
+
+    updates.into_iter().find_map(|update| match update {
+        tl::enums::Update::GroupCallConnection(connection) if !connection.presentation => {
+            let tl::enums::DataJson::Json(params) = connection.params;
+            Some(params.data)
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn connection(presentation: bool, data: &str) -> tl::enums::Update {
+        tl::types::UpdateGroupCallConnection {
+            presentation,
+            params: tl::types::DataJson {
+                data: String::from(data),
+            }
+            .into(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn connection_params_of_the_stream() {
+        let updates = tl::types::Updates {
+            updates: vec![connection(true, "screen"), connection(false, "stream")],
+            users: Vec::new(),
+            chats: Vec::new(),
+            date: 0,
+            seq: 0,
+        };
+
+        assert_eq!(
+            connection_params(updates.into()),
+            Some(String::from("stream"))
+        );
+        assert_eq!(connection_params(tl::enums::Updates::TooLong), None);
+    }
+
+    #[test]
+    fn chat_id_of_peers() {
+        let channel = PeerRef::from(tl::types::InputPeerChannel {
+            channel_id: 1234567890,
+            access_hash: 1,
+        });
+        assert_eq!(chat_id(channel), Some(ChatId::from_channel(1234567890)));
+
+        let chat = PeerRef::from(tl::types::InputPeerChat { chat_id: 123456 });
+        assert_eq!(chat_id(chat), Some(ChatId::from_chat(123456)));
+
+        let user = PeerRef::from(tl::types::InputPeerUser {
+            user_id: 777,
+            access_hash: 1,
+        });
+        assert_eq!(chat_id(user), None);
+    }
+}
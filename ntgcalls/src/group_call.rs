@@ -0,0 +1,393 @@
+//! Glue between NTgCalls and an MTProto client.
+//!
+//! Joining a group call is a round trip through Telegram: the params returned by
+//! [`NTgCall::get_params`] are sent with [`phone.joinGroupCall`], the params of the
+//! `updateGroupCallConnection` it returns are passed to [`NTgCall::connect`], every
+//! [`MediaState`] change must be sent with [`phone.editGroupCallParticipant`] and
+//! the call is left with [`phone.leaveGroupCall`].
+//!
+//! [`GroupCalls`] does all of that for any MTProto client implementing [`GroupCallTransport`].
+//!
+//! [`phone.joinGroupCall`]: https://core.telegram.org/method/phone.joinGroupCall
+//! [`phone.editGroupCallParticipant`]: https://core.telegram.org/method/phone.editGroupCallParticipant
+//! [`phone.leaveGroupCall`]: https://core.telegram.org/method/phone.leaveGroupCall
+
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    callbacks,
+    errors::{GroupCallError, NTgCallResult},
+    structures::{ChatId, EditParticipantParams, MediaDescription, MediaState},
+    NTgCall,
+};
+
+/// The MTProto requests needed to take part in a group call.
+///
+/// Implement it on top of the MTProto client of your choice, resolving the chat id to the
+/// `InputGroupCall` and the peer to join as. The methods are blocking, async clients can
+/// block on their runtime since the methods are never called from inside of it.
+pub trait GroupCallTransport: Send + Sync + 'static {
+    /// Error of the MTProto requests.
+    type Error: Send + 'static;
+
+    /// Send [`phone.joinGroupCall`](https://core.telegram.org/method/phone.joinGroupCall).
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of the chat.
+    /// - `params`: The params to send as the `DataJSON` of the request.
+    /// - `state`: The initial media state, for the `muted` and `video_stopped` flags.
+    ///
+    /// ## Return
+    /// The `DataJSON` params of the `updateGroupCallConnection` update sent by Telegram.
+    fn join_group_call(
+        &self,
+        chat_id: ChatId,
+        params: &str,
+        state: &MediaState,
+    ) -> Result<String, Self::Error>;
+
+    /// Send [`phone.leaveGroupCall`](https://core.telegram.org/method/phone.leaveGroupCall).
+    fn leave_group_call(&self, chat_id: ChatId) -> Result<(), Self::Error>;
+
+    /// Send [`phone.editGroupCallParticipant`](https://core.telegram.org/method/phone.editGroupCallParticipant)
+    /// with the flags of the media state that changed, `params` is never empty.
+    fn edit_group_call_participant(
+        &self,
+        chat_id: ChatId,
+        params: &EditParticipantParams,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when forwarding a media state change failed, since the changes are
+    /// forwarded in the background there is no caller to return the error to.
+    ///
+    /// The error is ignored by default.
+    fn edit_failed(&self, chat_id: ChatId, error: Self::Error) {
+        let _ = (chat_id, error);
+    }
+}
+
+/// A media state change of a chat, sent to the forwarding thread.
+type StateChange = (ChatId, MediaState);
+
+struct Shared<T> {
+    transport: T,
+    /// Last state sent for each chat joined through the helper,
+    /// the state changes of other chats are not forwarded.
+    chats: Mutex<HashMap<ChatId, MediaState>>,
+}
+
+impl<T> Shared<T> {
+    fn chats(&self) -> MutexGuard<'_, HashMap<ChatId, MediaState>> {
+        self.chats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Joins and leaves the group calls of a [`NTgCall`] through a [`GroupCallTransport`].
+///
+/// The helper registers the [`NTgCall::on_upgrade`] callback of the instance and forwards
+/// the media state changes of the joined chats with [`GroupCallTransport::edit_group_call_participant`]
+/// from a background thread so the NTgCalls threads are never blocked by the network. Only the
+/// flags that changed since the previous state are sent, see [`EditParticipantParams::diff`].
+///
+/// The callback registered before the helper is created keeps being called after the helper's,
+/// but registering a callback once the helper exists replaces the helper's and stops the forwarding.
+///
+/// Dropping the helper stops the background thread, waiting for the request being sent if any.
+pub struct GroupCalls<T: GroupCallTransport> {
+    call: NTgCall,
+    shared: Arc<Shared<T>>,
+    /// Sender of the state changes to the background thread, taken on drop to stop it.
+    sender: Arc<Mutex<Option<Sender<StateChange>>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Initialization and accessors
+impl<T: GroupCallTransport> GroupCalls<T> {
+    /// Create a new helper for the given instance.
+    ///
+    /// ## Return
+    /// A [`NTgCallError`](crate::errors::NTgCallError) in case registering the callback failed.
+    ///
+    /// ## Panics
+    /// If the OS fails to create the forwarding thread.
+    pub fn new(call: NTgCall, transport: T) -> NTgCallResult<Self> {
+        let shared = Arc::new(Shared {
+            transport,
+            chats: Mutex::default(),
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(Some(sender)));
+
+        let previous = callbacks::upgrade(call.inner.uid);
+        let forwarded = sender.clone();
+        call.on_upgrade(move |chat_id, state| {
+            if let Some(sender) = &*forwarded.lock().unwrap_or_else(|e| e.into_inner()) {
+                let _ = sender.send((chat_id, state));
+            }
+
+            if let Some(previous) = &previous {
+                previous(chat_id, state);
+            }
+        })?;
+
+        // the thread stops when the sender is taken by `drop`
+        let worker = shared.clone();
+        let worker = thread::Builder::new()
+            .name(String::from("ntgcalls-group-call"))
+            .spawn(move || forward(&worker, receiver))
+            .expect("failed to spawn the group call thread");
+
+        Ok(Self {
+            call,
+            shared,
+            sender,
+            worker: Some(worker),
+        })
+    }
+
+    /// The instance used by the helper.
+    pub fn call(&self) -> &NTgCall {
+        &self.call
+    }
+
+    /// The transport used by the helper.
+    pub fn transport(&self) -> &T {
+        &self.shared.transport
+    }
+
+    /// Whether the chat was joined through the helper and not left yet.
+    pub fn is_joined(&self, chat_id: ChatId) -> bool {
+        self.shared.chats().contains_key(&chat_id)
+    }
+}
+
+/// Group call methods
+impl<T: GroupCallTransport> GroupCalls<T> {
+    /// This method allows joining the group call of a chat, it creates the connection,
+    /// joins the call on Telegram and connects to it.
+    ///
+    /// If any step fails the connection is stopped and, if it was already joined, the call is left.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of the chat.
+    /// - `desc`: Media parameters of the stream.
+    ///
+    /// ## Return
+    /// A [`GroupCallError`] in case of failure, see [`NTgCall::get_params`] and
+    /// [`NTgCall::connect`] for the possible NTgCalls errors.
+    pub fn join(
+        &self,
+        chat_id: ChatId,
+        desc: MediaDescription,
+    ) -> Result<(), GroupCallError<T::Error>> {
+        let transport = &self.shared.transport;
+
+        let params = self.call.get_params(chat_id, desc)?;
+
+        let joined = self
+            .call
+            .get_state(chat_id)
+            .map_err(GroupCallError::NTgCall)
+            .and_then(|state| {
+                transport
+                    .join_group_call(chat_id, &params, &state)
+                    .map(|response| (response, state))
+                    .map_err(GroupCallError::Transport)
+            });
+
+        let (response, state) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                let _ = self.call.stop(chat_id);
+                return Err(e);
+            }
+        };
+
+        // track the chat before connecting so the first state changes are forwarded
+        self.shared.chats().insert(chat_id, state);
+
+        if let Err(e) = self.call.connect(chat_id, response) {
+            self.shared.chats().remove(&chat_id);
+            let _ = self.call.stop(chat_id);
+            let _ = transport.leave_group_call(chat_id);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// This method allows leaving the group call of a chat, it stops the connection
+    /// and leaves the call on Telegram.
+    ///
+    /// The call is left on Telegram even if stopping the connection fails.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of the chat.
+    ///
+    /// ## Return
+    /// A [`GroupCallError`] in case of failure, see [`NTgCall::stop`] for the possible NTgCalls errors.
+    pub fn leave(&self, chat_id: ChatId) -> Result<(), GroupCallError<T::Error>> {
+        self.shared.chats().remove(&chat_id);
+
+        let stopped = self.call.stop(chat_id);
+
+        self.shared
+            .transport
+            .leave_group_call(chat_id)
+            .map_err(GroupCallError::Transport)?;
+
+        Ok(stopped?)
+    }
+}
+
+impl<T: GroupCallTransport> Drop for GroupCalls<T> {
+    fn drop(&mut self) {
+        // the callback stays registered on the instance but stops forwarding
+        self.sender.lock().unwrap_or_else(|e| e.into_inner()).take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Forward the state changes of the joined chats until the sender is dropped.
+fn forward<T: GroupCallTransport>(shared: &Shared<T>, receiver: Receiver<StateChange>) {
+    for (chat_id, state) in receiver {
+        let params = {
+            let mut chats = shared.chats();
+
+            let Some(previous) = chats.get_mut(&chat_id) else {
+                continue;
+            };
+
+            let params = EditParticipantParams::diff(previous, &state);
+            *previous = state;
+            params
+        };
+
+        if params.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = shared
+            .transport
+            .edit_group_call_participant(chat_id, &params)
+        {
+            shared.transport.edit_failed(chat_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A transport recording the requests, failing the ones for chat `0`.
+    #[derive(Default)]
+    pub(crate) struct StubTransport {
+        pub(crate) requests: Mutex<Vec<String>>,
+        pub(crate) failed: Mutex<Vec<ChatId>>,
+    }
+
+    impl StubTransport {
+        fn record(&self, chat_id: ChatId, request: String) -> Result<(), &'static str> {
+            self.requests
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(request);
+
+            match chat_id.bot_api() {
+                0 => Err("CHAT_INVALID"),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl GroupCallTransport for StubTransport {
+        type Error = &'static str;
+
+        fn join_group_call(
+            &self,
+            chat_id: ChatId,
+            params: &str,
+            state: &MediaState,
+        ) -> Result<String, Self::Error> {
+            self.record(chat_id, format!("join {chat_id} muted={}", state.muted))?;
+            assert!(!params.is_empty());
+            Ok(String::from("{}"))
+        }
+
+        fn leave_group_call(&self, chat_id: ChatId) -> Result<(), Self::Error> {
+            self.record(chat_id, format!("leave {chat_id}"))
+        }
+
+        fn edit_group_call_participant(
+            &self,
+            chat_id: ChatId,
+            params: &EditParticipantParams,
+        ) -> Result<(), Self::Error> {
+            self.record(chat_id, format!("edit {chat_id} {params:?}"))
+        }
+
+        fn edit_failed(&self, chat_id: ChatId, _error: Self::Error) {
+            self.failed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(chat_id);
+        }
+    }
+
+    #[test]
+    fn forward_joined_chats() {
+        let state = |muted| MediaState {
+            muted,
+            video_paused: false,
+            video_stopped: false,
+        };
+
+        let shared = Shared {
+            transport: StubTransport::default(),
+            chats: Mutex::new(HashMap::from([
+                (ChatId::from_bot_api(0), state(true)),
+                (ChatId::from_bot_api(1), state(false)),
+            ])),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        sender.send((ChatId::from_bot_api(1), state(true))).unwrap();
+        sender.send((ChatId::from_bot_api(1), state(true))).unwrap();
+        sender.send((ChatId::from_bot_api(2), state(true))).unwrap();
+        sender
+            .send((ChatId::from_bot_api(0), state(false)))
+            .unwrap();
+        drop(sender);
+
+        forward(&shared, receiver);
+
+        // the unchanged state isn't sent again, chat 2 wasn't joined and the edit of chat 0 failed
+        let muted = |muted| EditParticipantParams {
+            muted: Some(muted),
+            ..Default::default()
+        };
+        assert_eq!(
+            *shared.transport.requests.lock().unwrap(),
+            [
+                format!("edit 1 {:?}", muted(true)),
+                format!("edit 0 {:?}", muted(false)),
+            ]
+        );
+        assert_eq!(
+            *shared.transport.failed.lock().unwrap(),
+            [ChatId::from_bot_api(0)]
+        );
+    }
+}
@@ -1,31 +1,126 @@
 use std::{
-    mem::{transmute, MaybeUninit},
-    ptr,
-    sync::Arc,
+    mem::MaybeUninit,
+    sync::{Arc, OnceLock, RwLock, RwLockReadGuard},
+    thread::{self, ThreadId},
 };
 
-use libntgcalls_sys::{
-    ntg_calls, ntg_calls_count, ntg_change_stream, ntg_connect, ntg_destroy, ntg_get_params,
-    ntg_get_state, ntg_get_version, ntg_init, ntg_media_description_struct, ntg_mute, ntg_pause,
-    ntg_resume, ntg_stop, ntg_time, ntg_unmute, NTG_ERR_TOO_SMALL,
-};
+use libntgcalls_sys::NTG_ERR_TOO_SMALL;
 
+use enums::StreamType;
 use errors::{DestroyError, NTgCallError, NTgCallResult};
-use structures::{GroupCall, MediaDescription, MediaState};
+use ffi::{
+    backend::{
+        ntg_calls, ntg_calls_count, ntg_change_stream, ntg_connect, ntg_cpu_usage, ntg_destroy,
+        ntg_get_params, ntg_get_state, ntg_get_version, ntg_init, ntg_mute, ntg_on_disconnect,
+        ntg_on_stream_end, ntg_on_upgrade, ntg_pause, ntg_resume, ntg_stop, ntg_time, ntg_unmute,
+    },
+    CallsBuffer, StringBuffer,
+};
+use filters::{Volume, Volumes};
+use structures::{ChatId, GroupCall, MediaDescription, MediaState};
 use utils::IntoCString;
 
-use crate::structures::{AudioDescription, VideoDescription};
+#[macro_use]
+mod macros;
 
+pub mod actor;
+mod callbacks;
+pub mod convert;
+#[cfg(feature = "symphonia")]
+pub mod decode;
 pub mod enums;
 pub mod errors;
+mod ffi;
+pub mod filters;
+pub mod generators;
+#[cfg(feature = "grammers")]
+pub mod grammers;
+pub mod group_call;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mixer;
+pub mod pcm;
+pub mod pipe;
+pub mod pool;
+pub mod preflight;
+pub mod profiles;
+pub mod rtmp;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod structures;
+pub mod transport;
 pub mod utils;
 
-struct NTgCallInner(u32);
+struct NTgCallInner {
+    uid: u32,
+    /// Whether the instance was destroyed, calls hold a read lock for their whole
+    /// duration so the instance can't be destroyed while it's being used.
+    destroyed: RwLock<bool>,
+    /// Volumes of the PCM sources produced in Rust, see [`NTgCall::volume`].
+    /// Shared with the disconnect callback, which resets the volumes of the disconnected chats.
+    volumes: Arc<Volumes>,
+    /// The only thread allowed to use the instance, set for the instances owned by an
+    /// [`actor::NTgCallActor`].
+    owner: OnceLock<ThreadId>,
+}
+
+impl NTgCallInner {
+    /// Lock the instance for the duration of a call.
+    ///
+    /// This never blocks, if the instance is being destroyed or was already destroyed
+    /// it fails with [`NTgCallError::Destroyed`].
+    ///
+    /// ## Panics
+    /// If the instance is owned by an [`actor::NTgCallActor`] and this isn't its worker thread.
+    fn lock(&self) -> NTgCallResult<RwLockReadGuard<'_, bool>> {
+        if let Some(&owner) = self.owner.get() {
+            assert_eq!(
+                owner,
+                thread::current().id(),
+                "the NTgCall of an NTgCallActor can only be used on its worker thread"
+            );
+        }
+
+        match self.destroyed.try_read() {
+            Ok(destroyed) if !*destroyed => Ok(destroyed),
+            _ => Err(NTgCallError::Destroyed),
+        }
+    }
+
+    /// Destroy the native instance, this only calls into NTgCalls the first time.
+    fn destroy(&self) -> Result<(), DestroyError> {
+        // wait for all the calls in progress to finish
+        let mut destroyed = self.destroyed.write().unwrap_or_else(|e| e.into_inner());
+
+        if *destroyed {
+            return Err(DestroyError::AlreadyDestroyed);
+        }
+
+        *destroyed = true;
+        callbacks::remove(self.uid);
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_destroy(self.uid)) };
+
+        if result != 0 {
+            return Err(DestroyError::Failed);
+        }
+
+        Ok(())
+    }
+}
 
 /// NTgCall is a wrapper struct that encapsulates an instance of
 /// the NTgCalls API. It contains the identifier UID for the NTgCalls
 /// instance.
+///
+/// ## Thread safety
+/// [`NTgCall`] is [`Send`] and [`Sync`] and all its clones share the same native instance,
+/// the instance is destroyed when the last clone is dropped or when [`NTgCall::destroy`] is called.
+/// NTgCalls doesn't document whether concurrent calls on the same instance are safe, so
+/// if you need to use one instance from multiple threads either synchronize the calls
+/// yourself or use [`actor::NTgCallActor`] which executes all the calls on a single owned thread.
+/// The callbacks are called from NTgCalls threads and must not block for a long time.
 #[derive(Clone)]
 pub struct NTgCall {
     inner: Arc<NTgCallInner>,
@@ -38,40 +133,79 @@ impl NTgCall {
     /// ## Note
     /// If you need multiple instance of [`NTgCall`] for multiple threads its better
     /// to create all the instance in your main thread and then just pass them to other threads.
+    /// To shard many chats across multiple instances use [`pool::NTgCallPool`].
     ///
     /// ## Return
     /// A new instance of the [`NTgCall`]
     #[allow(clippy::new_without_default)]
     #[must_use]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", fields(uid = tracing::field::Empty))
+    )]
     pub fn new() -> Self {
+        // SAFETY: the function only takes plain values.
+        let uid = unsafe { ffi_call!(ntg_init()) };
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("uid", uid);
+
+        // the disconnect trampoline is always registered so the volumes of the disconnected
+        // chats are reset even without a callback, `on_disconnect` only adds the callback
+        let volumes = Arc::<Volumes>::default();
+        callbacks::update(uid, |c| c.volumes = volumes.clone());
+        // SAFETY: the trampoline has the signature of the callback and outlives the instance.
+        let _ = unsafe {
+            ffi_call!(ntg_on_disconnect(
+                uid,
+                Some(callbacks::disconnect_trampoline as _)
+            ))
+        };
+
         Self {
-            inner: Arc::new(NTgCallInner(unsafe { ntg_init() })),
+            inner: Arc::new(NTgCallInner {
+                uid,
+                destroyed: RwLock::new(false),
+                volumes,
+                owner: OnceLock::new(),
+            }),
         }
     }
 
     /// Clean up and release the resources used by NTgCalls
+    ///
+    /// The native instance is shared by all the clones, after calling this method every
+    /// other clone fails with [`NTgCallError::Destroyed`] and dropping them is a no-op.
+    ///
+    /// ## Return
+    /// A [`DestroyError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`DestroyError::AlreadyDestroyed`]
+    /// - [`DestroyError::Failed`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
     pub fn destroy(self) -> Result<(), DestroyError> {
-        let result = unsafe { ntg_destroy(self.inner.0) };
-
-        if result != 0 {
-            return Err(DestroyError);
-        }
-
-        Ok(())
+        self.inner.destroy()
     }
 
     /// Retrieve the version of NTgCalls library.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug"))]
     pub fn version() -> String {
-        let mut buf = [0u8; 8];
+        let mut buf = StringBuffer::new(8);
 
-        let result = unsafe { ntg_get_version(transmute(buf.as_mut_ptr()), buf.len() as _) };
+        // SAFETY: the buffer is valid for writes of `buf.size()` bytes.
+        let result = unsafe { ffi_call!(ntg_get_version(buf.as_mut_ptr(), buf.size())) };
 
         assert_ne!(
             result, NTG_ERR_TOO_SMALL,
             "Version buffer is too small, NTgCalls expected a bigger buffer, this is a internal error, report it!"
         );
 
-        String::from_utf8_lossy(&buf).into_owned()
+        buf.into_string()
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
     }
 }
 
@@ -88,6 +222,8 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    /// - [`NTgCallError::InvalidString`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::RtmpNeeded`]
     /// - [`NTgCallError::InvalidTransport`]
@@ -95,20 +231,36 @@ impl NTgCall {
     /// - [`NTgCallError::UnknownException`]
     ///
     /// [JoinGroupCall]: https://core.telegram.org/method/phone.joinGroupCall
-    pub fn connect<S: IntoCString>(&self, chat_id: i64, params: S) -> NTgCallResult<()> {
-        let params = params.into_c_string();
-
-        let result = unsafe { ntg_connect(self.inner.0, chat_id, params.into_raw()) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, params), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn connect<S: IntoCString>(&self, chat_id: ChatId, params: S) -> NTgCallResult<()> {
+        let params = params.try_into_c_string()?;
+        let _lock = self.inner.lock()?;
+
+        let result = ffi::with_c_string(params, |params| {
+            // SAFETY: `params` is a nul terminated string that lives until the closure returns.
+            unsafe { ffi_call!(ntg_connect(self.inner.uid, chat_id.to_ffi(), params)) }
+        });
+
+        let result = if result.is_negative() {
+            Err(NTgCallError::from(result))
+        } else {
+            Ok(())
+        };
 
-        if result.is_negative() {
-            return Err(NTgCallError::from(result));
-        }
+        #[cfg(feature = "metrics")]
+        metrics::record_connect(self.inner.uid, &result);
 
-        Ok(())
+        result
     }
 
     /// This method allows getting connection params for Telegram WebRTC connection.
     ///
+    /// The problems of the sources are reported by NTgCalls only with an error code, use
+    /// [`preflight::preflight`] to find all of them beforehand.
+    ///
     /// ## Parameters
     /// - `chat_id`: Unique identifier of a chat.
     /// - `desc`: Media parameters of the stream.
@@ -118,32 +270,38 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    /// - [`NTgCallError::InvalidInputMode`]
     /// - [`NTgCallError::ConnectionAlreadyExists`]
     /// - [`NTgCallError::FileNotFound`]
     /// - [`NTgCallError::EncoderNotFound`]
     /// - [`NTgCallError::FFmpegNotFound`]
     /// - [`NTgCallError::ShellError`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn get_params(&self, chat_id: i64, desc: MediaDescription) -> NTgCallResult<String> {
-        let mut buf = vec![0; 512];
-
-        let audio = desc.audio.as_ref().map(AudioDescription::to_ffi);
-        let video = desc.video.as_ref().map(VideoDescription::to_ffi);
-
-        let ffi_desc = ntg_media_description_struct {
-            audio: audio.map_or(ptr::null(), |n| &n as *const _),
-            video: video.map_or(ptr::null(), |n| &n as *const _),
-        };
-
-        let result = unsafe {
-            ntg_get_params(
-                self.inner.0,
-                chat_id,
-                ffi_desc,
-                transmute(buf.as_mut_ptr()),
-                512,
-            )
-        };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, desc), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn get_params(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<String> {
+        desc.check_input_modes()?;
+
+        let _lock = self.inner.lock()?;
+
+        let mut buf = StringBuffer::new(512);
+
+        let result = ffi::with_media_description(&desc, |desc| {
+            // SAFETY: `desc` and its strings live until the closure returns and the buffer is
+            // valid for writes of `buf.size()` bytes.
+            unsafe {
+                ffi_call!(ntg_get_params(
+                    self.inner.uid,
+                    chat_id.to_ffi(),
+                    desc,
+                    buf.as_mut_ptr(),
+                    buf.size(),
+                ))
+            }
+        });
 
         assert_ne!(
             result, NTG_ERR_TOO_SMALL,
@@ -154,7 +312,7 @@ impl NTgCall {
             return Err(NTgCallError::from(result));
         }
 
-        Ok(String::from_utf8(buf).expect(
+        Ok(buf.into_string().expect(
             "GetParams function returned invalid string. this is a internal error, report it",
         ))
     }
@@ -169,15 +327,28 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn stop(&self, chat_id: i64) -> NTgCallResult<()> {
-        let result = unsafe { ntg_stop(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn stop(&self, chat_id: ChatId) -> NTgCallResult<()> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_stop(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
         }
 
+        self.inner.volumes.remove(chat_id);
+
+        #[cfg(feature = "metrics")]
+        metrics::record_call_end(self.inner.uid, chat_id);
+
         Ok(())
     }
 }
@@ -195,22 +366,27 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    /// - [`NTgCallError::InvalidInputMode`]
     /// - [`NTgCallError::ConnectionAlreadyExists`]
     /// - [`NTgCallError::FileNotFound`]
     /// - [`NTgCallError::EncoderNotFound`]
     /// - [`NTgCallError::FFmpegNotFound`]
     /// - [`NTgCallError::ShellError`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn change_stream(&self, chat_id: i64, desc: MediaDescription) -> NTgCallResult<()> {
-        let audio = desc.audio.as_ref().map(AudioDescription::to_ffi);
-        let video = desc.video.as_ref().map(VideoDescription::to_ffi);
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, desc), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<()> {
+        desc.check_input_modes()?;
 
-        let ffi_desc = ntg_media_description_struct {
-            audio: audio.map_or(ptr::null(), |n| &n as *const _),
-            video: video.map_or(ptr::null(), |n| &n as *const _),
-        };
+        let _lock = self.inner.lock()?;
 
-        let result = unsafe { ntg_change_stream(self.inner.0, chat_id, ffi_desc) };
+        let result = ffi::with_media_description(&desc, |desc| {
+            // SAFETY: `desc` and its strings live until the closure returns.
+            unsafe { ffi_call!(ntg_change_stream(self.inner.uid, chat_id.to_ffi(), desc)) }
+        });
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -231,10 +407,18 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn mute(&self, chat_id: i64) -> NTgCallResult<bool> {
-        let result = unsafe { ntg_mute(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn mute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_mute(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -255,10 +439,18 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn pause(&self, chat_id: i64) -> NTgCallResult<bool> {
-        let result = unsafe { ntg_pause(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn pause(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_pause(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -279,10 +471,18 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn resume(&self, chat_id: i64) -> NTgCallResult<bool> {
-        let result = unsafe { ntg_resume(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn resume(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_resume(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -301,10 +501,18 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn played_time(&self, chat_id: i64) -> NTgCallResult<i64> {
-        let result = unsafe { ntg_time(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn played_time(&self, chat_id: ChatId) -> NTgCallResult<i64> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_time(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result as i32));
@@ -325,10 +533,18 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn unmute(&self, chat_id: i64) -> NTgCallResult<bool> {
-        let result = unsafe { ntg_unmute(self.inner.0, chat_id) };
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn unmute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        let _lock = self.inner.lock()?;
+
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_unmute(self.inner.uid, chat_id.to_ffi())) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -338,6 +554,55 @@ impl NTgCall {
     }
 }
 
+/// Volume methods
+impl NTgCall {
+    /// This method allows getting the [`Volume`] of a chat.
+    ///
+    /// NTgCalls can't change the volume of the sources it reads, wrap the PCM source
+    /// of the chat with a [`filters::Gain`] following this volume to control it with
+    /// [`NTgCall::set_volume`]. The volume is reset when the chat is stopped or disconnected.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of a chat.
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case failure and the volume in case of success.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::Destroyed`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn volume(&self, chat_id: ChatId) -> NTgCallResult<Volume> {
+        let _lock = self.inner.lock()?;
+
+        Ok(self.inner.volumes.get(chat_id))
+    }
+
+    /// This method allows changing the volume of a chat, the change is applied right away
+    /// to the sources using [`NTgCall::volume`] without restarting them.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of a chat.
+    /// - `volume`: The linear volume, `1.0` keeps the source unchanged and `0.0` mutes it.
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case failure.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::Destroyed`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn set_volume(&self, chat_id: ChatId, volume: f32) -> NTgCallResult<()> {
+        self.volume(chat_id)?.set(volume);
+
+        Ok(())
+    }
+}
+
 /// Advance methods
 impl NTgCall {
     /// This function returns the number of active group calls that NTgCalls is connected to.
@@ -347,9 +612,22 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::UnknownException`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
     pub fn count_calls(&self) -> NTgCallResult<i32> {
-        let result = unsafe { ntg_calls_count(self.inner.0) };
+        let _lock = self.inner.lock()?;
+
+        self.calls_count()
+    }
+
+    /// [`NTgCall::count_calls`] for the callers already holding the lock.
+    fn calls_count(&self) -> NTgCallResult<i32> {
+        // SAFETY: the function only takes plain values.
+        let result = unsafe { ffi_call!(ntg_calls_count(self.inner.uid)) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -358,31 +636,86 @@ impl NTgCall {
         Ok(result)
     }
 
-    /// This function returns a list of [`GroupCall`] instances, each containing information
-    /// about an active group call associated with the channel or group.
+    /// This function returns the CPU usage of the NTgCalls instance.
     ///
     /// ## Return
-    /// A [`NTgCallError`] in case failure and a list of [`GroupCall`] in case of success.
+    /// A [`NTgCallError`] in case failure and the CPU usage in case of success.
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::UnknownException`]
-    pub fn calls(&self) -> NTgCallResult<Vec<GroupCall>> {
-        let count = self.count_calls()?;
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn cpu_usage(&self) -> NTgCallResult<f64> {
+        let _lock = self.inner.lock()?;
 
-        let mut buffer = Vec::with_capacity(count as usize);
+        let mut buffer = 0.0;
 
-        let result = unsafe { ntg_calls(self.inner.0, buffer.as_mut_ptr(), count) };
+        // SAFETY: `buffer` is a valid `f64` for writes.
+        let result = unsafe { ffi_call!(ntg_cpu_usage(self.inner.uid, &mut buffer)) };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
         }
 
-        unsafe {
-            buffer.set_len(count as usize);
-            // this should be ok because both ntg_group_call_start and group call have the same layout...
-            Ok(transmute(buffer))
+        Ok(buffer)
+    }
+
+    /// This function returns a list of [`GroupCall`] instances, each containing information
+    /// about an active group call associated with the channel or group.
+    ///
+    /// A status unknown to these bindings is returned as [`StreamStatus::Unknown`](enums::StreamStatus::Unknown).
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case failure and a list of [`GroupCall`] in case of success.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    /// - [`NTgCallError::UnknownException`], also when calls keep starting faster than they can be read.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn calls(&self) -> NTgCallResult<Vec<GroupCall>> {
+        /// Number of reads before giving up on the calls started while reading them.
+        const ATTEMPTS: usize = 8;
+
+        let _lock = self.inner.lock()?;
+
+        let mut capacity = 0;
+
+        for _ in 0..ATTEMPTS {
+            // the buffer at least doubles after every failed read
+            capacity = (self.calls_count()? as usize).max(capacity * 2);
+
+            let mut buffer = CallsBuffer::new(capacity);
+
+            // SAFETY: the buffer is valid for writes of `buffer.size()` calls.
+            let result = unsafe {
+                ffi_call!(ntg_calls(
+                    self.inner.uid,
+                    buffer.as_mut_ptr(),
+                    buffer.size()
+                ))
+            };
+
+            // a call started since it was counted
+            if result == NTG_ERR_TOO_SMALL {
+                continue;
+            }
+
+            if result.is_negative() {
+                return Err(NTgCallError::from(result));
+            }
+
+            return Ok(buffer.into_calls());
         }
+
+        Err(NTgCallError::UnknownException)
     }
 
     /// This method allows retrieving GroupCall MTProto Configurations for [`EditGroupCallParticipant`].
@@ -397,14 +730,28 @@ impl NTgCall {
     ///
     /// ### Possible error values
     /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
     /// - [`NTgCallError::ConnectionNotFound`]
     /// - [`NTgCallError::UnknownException`]
     ///
     /// [`EditGroupCallParticipant`]: https://core.telegram.org/method/phone.editGroupCallParticipant
-    pub fn get_state(&self, chat_id: i64) -> NTgCallResult<MediaState> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn get_state(&self, chat_id: ChatId) -> NTgCallResult<MediaState> {
+        let _lock = self.inner.lock()?;
+
         let mut buffer = MaybeUninit::uninit();
 
-        let result = unsafe { ntg_get_state(self.inner.0, chat_id, buffer.as_mut_ptr()) };
+        // SAFETY: `buffer` is valid for writes of a media state.
+        let result = unsafe {
+            ffi_call!(ntg_get_state(
+                self.inner.uid,
+                chat_id.to_ffi(),
+                buffer.as_mut_ptr()
+            ))
+        };
 
         if result.is_negative() {
             return Err(NTgCallError::from(result));
@@ -412,30 +759,177 @@ impl NTgCall {
 
         let state = unsafe { buffer.assume_init() };
 
-        Ok(MediaState {
-            muted: state.muted,
-            video_paused: state.videoPaused,
-            video_stopped: state.videoStopped,
-        })
+        Ok(MediaState::from_ffi(state))
+    }
+}
+
+/// Callback methods
+impl NTgCall {
+    /// This method allows registering a callback that is called when a stream ends.
+    ///
+    /// Registering a new callback replaces the previous one.
+    ///
+    /// ## Parameters
+    /// - `callback`: Function called with the chat id and the [`StreamType`] of the ended stream.
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn on_stream_end<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, StreamType) + Send + Sync + 'static,
+    {
+        let _lock = self.inner.lock()?;
+
+        callbacks::update(self.inner.uid, |c| c.stream_end = Some(Arc::new(callback)));
+
+        // SAFETY: the trampoline has the signature of the callback and outlives the instance.
+        let result = unsafe {
+            ffi_call!(ntg_on_stream_end(
+                self.inner.uid,
+                Some(callbacks::stream_end_trampoline as _)
+            ))
+        };
+
+        if result.is_negative() {
+            return Err(NTgCallError::from(result));
+        }
+
+        Ok(())
+    }
+
+    /// This method allows registering a callback that is called when the [`MediaState`]
+    /// of a call changes, the new state should be sent to Telegram with [`EditGroupCallParticipant`],
+    /// see [`structures::EditParticipantParams`] for the parameters of the request.
+    ///
+    /// Registering a new callback replaces the previous one.
+    ///
+    /// ## Parameters
+    /// - `callback`: Function called with the chat id and the new [`MediaState`].
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    ///
+    /// [`EditGroupCallParticipant`]: https://core.telegram.org/method/phone.editGroupCallParticipant
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn on_upgrade<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, MediaState) + Send + Sync + 'static,
+    {
+        let _lock = self.inner.lock()?;
+
+        callbacks::update(self.inner.uid, |c| c.upgrade = Some(Arc::new(callback)));
+
+        // SAFETY: the trampoline has the signature of the callback and outlives the instance.
+        let result = unsafe {
+            ffi_call!(ntg_on_upgrade(
+                self.inner.uid,
+                Some(callbacks::upgrade_trampoline as _)
+            ))
+        };
+
+        if result.is_negative() {
+            return Err(NTgCallError::from(result));
+        }
+
+        Ok(())
+    }
+
+    /// This method allows registering a callback that is called when a call gets disconnected.
+    ///
+    /// Registering a new callback replaces the previous one.
+    ///
+    /// ## Parameters
+    /// - `callback`: Function called with the chat id of the disconnected call.
+    ///
+    /// ## Return
+    /// A [`NTgCallError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`NTgCallError::InvalidUid`]
+    /// - [`NTgCallError::Destroyed`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uid = self.inner.uid), err(Display))
+    )]
+    pub fn on_disconnect<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId) + Send + Sync + 'static,
+    {
+        let _lock = self.inner.lock()?;
+
+        callbacks::update(self.inner.uid, |c| c.disconnect = Some(Arc::new(callback)));
+
+        // SAFETY: the trampoline has the signature of the callback and outlives the instance.
+        let result = unsafe {
+            ffi_call!(ntg_on_disconnect(
+                self.inner.uid,
+                Some(callbacks::disconnect_trampoline as _)
+            ))
+        };
+
+        if result.is_negative() {
+            return Err(NTgCallError::from(result));
+        }
+
+        Ok(())
     }
 }
 
 impl Drop for NTgCallInner {
     // Clean up and release all the resource allocated by the instance.
     fn drop(&mut self) {
+        // the instance was explicitly destroyed by one of the clones
+        if *self.destroyed.get_mut().unwrap_or_else(|e| e.into_inner()) {
+            return;
+        }
+
         // if this ever fail we leak memory...
-        let _ = unsafe { ntg_destroy(self.0) };
+        let _ = self.destroy();
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{structures::MediaDescription, NTgCall};
+    use libntgcalls_sys::{ntg_calls_count, NTG_INVALID_UID};
+    use static_assertions::assert_impl_all;
+
+    use crate::{
+        actor::NTgCallActor,
+        errors::{DestroyError, NTgCallError},
+        pool::NTgCallPool,
+        structures::{ChatId, MediaDescription},
+        NTgCall,
+    };
+
+    assert_impl_all!(NTgCall: Send, Sync, Clone);
+    assert_impl_all!(NTgCallActor: Send, Sync, Clone);
+    assert_impl_all!(NTgCallPool: Send, Sync);
 
     #[test]
     fn test_all_sequential() {
         binding_working();
         clone();
+        destroy();
+        pool();
+        actor();
+        group_call();
+        #[cfg(feature = "server")]
+        crate::server::test::endpoints();
     }
 
     /// A test to make sure the binding works
@@ -445,9 +939,9 @@ mod test {
         let call2 = NTgCall::new();
         let version = NTgCall::version();
 
-        assert_eq!(call0.inner.0, 0);
-        assert_eq!(call1.inner.0, 1);
-        assert_eq!(call2.inner.0, 2);
+        assert_eq!(call0.inner.uid, 0);
+        assert_eq!(call1.inner.uid, 1);
+        assert_eq!(call2.inner.uid, 2);
 
         call0.destroy().unwrap();
         call1.destroy().unwrap();
@@ -457,6 +951,10 @@ mod test {
             !version.is_empty(),
             "Version can't be empty, there was a problem in binding"
         );
+        assert!(
+            !version.contains('\0'),
+            "Version must not contain the unused bytes of the buffer"
+        );
     }
 
     fn clone() {
@@ -469,11 +967,268 @@ mod test {
         drop(call);
 
         // check if the instance is still valid
-        let params = call2.get_params(123, MediaDescription::default()).unwrap();
+        let params = call2
+            .get_params(ChatId::from_bot_api(123), MediaDescription::default())
+            .unwrap();
         assert!(!params.is_empty());
+        assert!(!params.contains('\0'));
 
         call3.destroy().unwrap();
 
-        assert!(call2.destroy().is_err());
+        // every other clone should fail fast now
+        assert_eq!(
+            call2.get_params(ChatId::from_bot_api(456), MediaDescription::default()),
+            Err(NTgCallError::Destroyed)
+        );
+        assert_eq!(call2.destroy(), Err(DestroyError::AlreadyDestroyed));
+    }
+
+    fn destroy() {
+        // dropping the last clone destroys the instance
+        let call = NTgCall::new();
+        let uid = call.inner.uid;
+        let call2 = call.clone();
+
+        drop(call);
+        assert!(call2.count_calls().is_ok());
+
+        drop(call2);
+        assert_eq!(unsafe { ntg_calls_count(uid) }, NTG_INVALID_UID);
+
+        // dropping the clones of a destroyed instance shouldn't call into NTgCalls again
+        let call = NTgCall::new();
+        let call2 = call.clone();
+
+        call.destroy().unwrap();
+        let other = NTgCall::new();
+        drop(call2);
+        assert!(other.count_calls().is_ok());
+
+        // destroying while other clones are used from other threads
+        let call = NTgCall::new();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let call = call.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        match call.count_calls() {
+                            Ok(_) | Err(NTgCallError::Destroyed) => {}
+                            Err(e) => panic!("unexpected error: {e}"),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        call.destroy().unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    fn pool() {
+        let pool = NTgCallPool::new(2);
+
+        // the volume set before the assignment is handed to the instance
+        let volume = pool.volume(ChatId::from_bot_api(1));
+        pool.set_volume(ChatId::from_bot_api(1), 0.5);
+
+        pool.get_params(ChatId::from_bot_api(1), MediaDescription::default())
+            .unwrap();
+
+        let instance = pool.instance_for(ChatId::from_bot_api(1)).unwrap();
+        instance.set_volume(ChatId::from_bot_api(1), 0.25).unwrap();
+        assert_eq!(volume.get(), 0.25);
+        assert_eq!(pool.volume(ChatId::from_bot_api(1)).get(), 0.25);
+
+        pool.get_params(ChatId::from_bot_api(2), MediaDescription::default())
+            .unwrap();
+        pool.set_volume(ChatId::from_bot_api(2), 0.5);
+
+        // chats should be spread across the instances
+        let first = pool
+            .instance_for(ChatId::from_bot_api(1))
+            .unwrap()
+            .inner
+            .uid;
+        let second = pool
+            .instance_for(ChatId::from_bot_api(2))
+            .unwrap()
+            .inner
+            .uid;
+        assert_ne!(first, second);
+
+        assert_eq!(pool.count_calls().unwrap(), 2);
+        assert_eq!(pool.calls().unwrap().len(), 2);
+        assert!(pool.get_state(ChatId::from_bot_api(1)).is_ok());
+
+        pool.stop(ChatId::from_bot_api(1)).unwrap();
+        assert!(pool.instance_for(ChatId::from_bot_api(1)).is_none());
+        assert_eq!(
+            pool.mute(ChatId::from_bot_api(1)),
+            Err(NTgCallError::ConnectionNotFound)
+        );
+
+        // a disconnected chat is released before the callback of the pool is called
+        let (sender, receiver) = std::sync::mpsc::channel();
+        pool.on_disconnect(move |chat_id| sender.send(chat_id).unwrap())
+            .unwrap();
+
+        unsafe {
+            crate::callbacks::disconnect_trampoline(second, ChatId::from_bot_api(2).to_ffi())
+        };
+        assert_eq!(receiver.try_recv(), Ok(ChatId::from_bot_api(2)));
+        assert!(pool.instance_for(ChatId::from_bot_api(2)).is_none());
+
+        // and its volume is reset
+        assert_eq!(pool.volume(ChatId::from_bot_api(2)).get(), 1.0);
+        assert_eq!(
+            pool.instances()
+                .iter()
+                .find(|call| call.inner.uid == second)
+                .unwrap()
+                .volume(ChatId::from_bot_api(2))
+                .unwrap()
+                .get(),
+            1.0
+        );
+    }
+
+    fn actor() {
+        let actor = NTgCallActor::spawn().unwrap();
+        let worker = actor.execute(|_| std::thread::current().id());
+
+        // every command should run on the same worker thread
+        let handles: Vec<_> = (0..4)
+            .map(|chat_id| {
+                let actor = actor.clone();
+                std::thread::spawn(move || {
+                    actor
+                        .get_params(ChatId::from_bot_api(chat_id), MediaDescription::default())
+                        .unwrap();
+                    actor.execute(|_| std::thread::current().id())
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), worker);
+        }
+
+        assert_eq!(actor.count_calls().unwrap(), 4);
+
+        // a panicking command shouldn't stop the worker
+        let panicked = std::panic::catch_unwind(|| actor.execute(|_| panic!("boom")));
+        assert!(panicked.is_err());
+        assert_eq!(actor.calls().unwrap().len(), 4);
+
+        // the instance can't escape the worker thread
+        let escaped = actor.execute(|call| call.clone());
+        let panicked = std::panic::catch_unwind(|| escaped.count_calls());
+        assert!(panicked.is_err());
+
+        // calling the actor from the worker panics instead of deadlocking
+        let nested = actor.clone();
+        let panicked = std::panic::catch_unwind(|| {
+            actor.execute(move |_| nested.execute(|call| call.count_calls()))
+        });
+        assert!(panicked.is_err());
+        assert_eq!(actor.count_calls().unwrap(), 4);
+
+        // the worker destroys the instance even if a clone escaped
+        let uid = escaped.inner.uid;
+        drop(actor);
+        let start = std::time::Instant::now();
+        while !*escaped.inner.destroyed.read().unwrap() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(unsafe { ntg_calls_count(uid) }, NTG_INVALID_UID);
+    }
+
+    fn group_call() {
+        use std::{
+            sync::{Arc, Mutex},
+            thread,
+            time::Duration,
+        };
+
+        use crate::{
+            callbacks,
+            errors::GroupCallError,
+            group_call::{test::StubTransport, GroupCalls},
+            structures::EditParticipantParams,
+        };
+
+        // the callback registered before the helper keeps being called
+        let call = NTgCall::new();
+        let upgrades = Arc::new(Mutex::new(Vec::new()));
+        {
+            let upgrades = upgrades.clone();
+            call.on_upgrade(move |chat_id, _| upgrades.lock().unwrap().push(chat_id))
+                .unwrap();
+        }
+        let uid = call.inner.uid;
+
+        let calls = GroupCalls::new(call, StubTransport::default()).unwrap();
+
+        calls
+            .join(ChatId::from_bot_api(-1001), MediaDescription::default())
+            .unwrap();
+        assert!(calls.is_joined(ChatId::from_bot_api(-1001)));
+        assert_eq!(calls.call().count_calls().unwrap(), 1);
+
+        // a failed join doesn't leave the connection behind
+        assert_eq!(
+            calls.join(ChatId::from_bot_api(0), MediaDescription::default()),
+            Err(GroupCallError::Transport("CHAT_INVALID"))
+        );
+        assert!(!calls.is_joined(ChatId::from_bot_api(0)));
+        assert_eq!(calls.call().count_calls().unwrap(), 1);
+
+        let muted = libntgcalls_sys::ntg_media_state_struct {
+            muted: true,
+            videoPaused: false,
+            videoStopped: false,
+        };
+        unsafe { callbacks::upgrade_trampoline(uid, -1001, muted) };
+        assert_eq!(*upgrades.lock().unwrap(), [ChatId::from_bot_api(-1001)]);
+
+        // the change is forwarded from the background thread
+        let edited = |calls: &GroupCalls<StubTransport>| {
+            let requests = calls.transport().requests.lock().unwrap();
+            requests.iter().any(|r| r.starts_with("edit -1001"))
+        };
+        for _ in 0..100 {
+            if edited(&calls) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(edited(&calls));
+
+        calls.leave(ChatId::from_bot_api(-1001)).unwrap();
+        assert!(!calls.is_joined(ChatId::from_bot_api(-1001)));
+        assert_eq!(calls.call().count_calls().unwrap(), 0);
+
+        assert_eq!(
+            *calls.transport().requests.lock().unwrap(),
+            [
+                String::from("join -1001 muted=false"),
+                String::from("join 0 muted=false"),
+                format!(
+                    "edit -1001 {:?}",
+                    EditParticipantParams {
+                        muted: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                String::from("leave -1001"),
+            ]
+        );
+
+        // dropping the helper returns once the forwarding thread stopped
+        drop(calls);
     }
 }
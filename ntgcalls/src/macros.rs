@@ -0,0 +1,39 @@
+/// Call a raw NTgCalls function and return its result code.
+///
+/// When the `tracing` feature is enabled the latency and the result code of the
+/// call are recorded as an event inside the current span.
+///
+/// ## Safety
+/// The macro calls the function directly, so it must be used in an `unsafe` block with a
+/// `SAFETY` comment stating why the arguments are valid for the duration of the call:
+///
+/// ```ignore
+/// // SAFETY: the buffer is valid for writes of `buf.size()` bytes.
+/// let result = unsafe { ffi_call!(ntg_get_version(buf.as_mut_ptr(), buf.size())) };
+/// ```
+macro_rules! ffi_call {
+    ($func:ident($($arg:expr),* $(,)?)) => {
+        ffi_call!(@bind $func [] $($arg,)*)
+    };
+    // every expansion binds one argument, hygiene keeps the `arg` locals distinct
+    (@bind $func:ident [$($bound:ident)*] $arg:expr, $($rest:expr,)*) => {{
+        let arg = $arg;
+        ffi_call!(@bind $func [$($bound)* arg] $($rest,)*)
+    }};
+    (@bind $func:ident [$($bound:ident)*]) => {{
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+
+        let result = $func($($bound),*);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            function = stringify!($func),
+            code = result,
+            elapsed_us = start.elapsed().as_micros() as u64,
+            "ffi call returned"
+        );
+
+        result
+    }};
+}
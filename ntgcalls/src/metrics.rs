@@ -0,0 +1,193 @@
+//! Call health metrics exposed through the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Connect results and ended streams are recorded automatically, while the gauges
+//! need to be refreshed periodically with [`record_snapshot`]. Install any recorder
+//! (for example `metrics-exporter-prometheus`) to export them.
+//!
+//! All the metrics are labeled with the `uid` of the [`NTgCall`] instance. The played time
+//! of a chat is set to zero when its call is stopped or disconnected, or when it's missing
+//! from the next snapshot.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+use ::metrics::{counter, describe_counter, describe_gauge, gauge, Unit};
+
+use crate::{enums::StreamType, errors::NTgCallResult, structures::ChatId, NTgCall};
+
+/// Number of active group calls of an instance.
+pub const ACTIVE_CALLS: &str = "ntgcalls_active_calls";
+/// Number of `connect` calls, labeled by `result` (`ok` or the [name](crate::errors::NTgCallError::name) of the error).
+pub const CONNECTS: &str = "ntgcalls_connects_total";
+/// Number of ended streams, labeled by `stream_type`.
+pub const STREAM_ENDS: &str = "ntgcalls_stream_ends_total";
+/// CPU usage of an instance as reported by NTgCalls.
+pub const CPU_USAGE: &str = "ntgcalls_cpu_usage";
+/// Played time of the stream of a chat, labeled by `chat_id`.
+pub const PLAYED_TIME: &str = "ntgcalls_played_time";
+
+/// Register the description of all the metrics with the installed recorder.
+pub fn describe() {
+    describe_gauge!(ACTIVE_CALLS, "Number of active group calls.");
+    describe_counter!(CONNECTS, "Number of connect attempts by result.");
+    describe_counter!(STREAM_ENDS, "Number of ended streams by stream type.");
+    describe_gauge!(CPU_USAGE, Unit::Percent, "CPU usage reported by NTgCalls.");
+    describe_gauge!(PLAYED_TIME, "Played time of the stream of a chat.");
+}
+
+/// Refresh the active calls, CPU usage and played time gauges of the given instance.
+///
+/// ## Return
+/// A [`NTgCallError`](crate::errors::NTgCallError) in case NTgCalls failed to report any of the values.
+pub fn record_snapshot(call: &NTgCall) -> NTgCallResult<()> {
+    let uid = call.inner.uid;
+
+    gauge!(ACTIVE_CALLS, "uid" => uid.to_string()).set(f64::from(call.count_calls()?));
+    gauge!(CPU_USAGE, "uid" => uid.to_string()).set(call.cpu_usage()?);
+
+    let mut chats = HashSet::new();
+
+    for group_call in call.calls()? {
+        // the call may end between listing and querying it, that's not an error
+        if let Ok(time) = call.played_time(group_call.chat_id) {
+            played_time(uid, group_call.chat_id).set(time as f64);
+            chats.insert(group_call.chat_id);
+        }
+    }
+
+    // the calls that ended since the last snapshot
+    let mut played = played_chats();
+    let ended: Vec<_> = played
+        .iter()
+        .filter(|(u, chat_id)| *u == uid && !chats.contains(chat_id))
+        .copied()
+        .collect();
+
+    for (uid, chat_id) in ended {
+        played.remove(&(uid, chat_id));
+        played_time(uid, chat_id).set(0.0);
+    }
+
+    played.extend(chats.into_iter().map(|chat_id| (uid, chat_id)));
+
+    Ok(())
+}
+
+/// Chats with a played time gauge, by instance.
+fn played_chats() -> MutexGuard<'static, BTreeSet<(u32, ChatId)>> {
+    static PLAYED: Mutex<BTreeSet<(u32, ChatId)>> = Mutex::new(BTreeSet::new());
+    PLAYED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn played_time(uid: u32, chat_id: ChatId) -> ::metrics::Gauge {
+    gauge!(
+        PLAYED_TIME,
+        "uid" => uid.to_string(),
+        "chat_id" => chat_id.to_string()
+    )
+}
+
+/// Zero the played time of a call that was stopped or disconnected.
+pub(crate) fn record_call_end(uid: u32, chat_id: ChatId) {
+    if played_chats().remove(&(uid, chat_id)) {
+        played_time(uid, chat_id).set(0.0);
+    }
+}
+
+pub(crate) fn record_connect(uid: u32, result: &NTgCallResult<()>) {
+    let result = match result {
+        Ok(()) => "ok",
+        Err(e) => e.name(),
+    };
+
+    counter!(CONNECTS, "uid" => uid.to_string(), "result" => result).increment(1);
+}
+
+pub(crate) fn record_stream_end(uid: u32, stream_type: StreamType) {
+    let stream_type = match stream_type {
+        StreamType::Audio => "audio",
+        StreamType::Video => "video",
+    };
+
+    counter!(STREAM_ENDS, "uid" => uid.to_string(), "stream_type" => stream_type).increment(1);
+}
+
+#[cfg(test)]
+mod test {
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        MetricKind,
+    };
+
+    use super::*;
+    use crate::errors::NTgCallError;
+
+    #[test]
+    fn counters_are_labeled() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            record_connect(7, &Ok(()));
+            record_connect(7, &Err(NTgCallError::RtmpNeeded));
+            record_connect(7, &Err(NTgCallError::RtmpNeeded));
+            record_stream_end(7, StreamType::Video);
+        });
+
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        let count = |name: &str, label: (&str, &str)| {
+            snapshot
+                .iter()
+                .find(|(key, _)| {
+                    key.kind() == MetricKind::Counter
+                        && key.key().name() == name
+                        && key.key().labels().any(|l| (l.key(), l.value()) == label)
+                })
+                .map(|(_, (_, _, value))| value)
+        };
+
+        assert_eq!(
+            count(CONNECTS, ("result", "ok")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            count(CONNECTS, ("result", "RtmpNeeded")),
+            Some(&DebugValue::Counter(2))
+        );
+        assert_eq!(
+            count(STREAM_ENDS, ("stream_type", "video")),
+            Some(&DebugValue::Counter(1))
+        );
+    }
+
+    #[test]
+    fn played_time_is_zeroed() {
+        // use a uid that the native library never hands out
+        const UID: u32 = u32::MAX;
+        let chat_id = ChatId::from_channel(42);
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        let gauge = || {
+            snapshotter
+                .snapshot()
+                .into_vec()
+                .into_iter()
+                .find(|(key, _, _, _)| key.key().name() == PLAYED_TIME)
+                .map(|(_, _, _, value)| value)
+        };
+
+        ::metrics::with_local_recorder(&recorder, || {
+            played_time(UID, chat_id).set(12.0);
+            played_chats().insert((UID, chat_id));
+        });
+        assert_eq!(gauge(), Some(DebugValue::Gauge(12.0.into())));
+
+        ::metrics::with_local_recorder(&recorder, || record_call_end(UID, chat_id));
+        assert_eq!(gauge(), Some(DebugValue::Gauge(0.0.into())));
+        assert!(!played_chats().contains(&(UID, chat_id)));
+    }
+}
@@ -0,0 +1,336 @@
+//! Mixing multiple PCM sources into a single stream.
+//!
+//! NTgCalls plays a single audio source for each call, [`Mixer`] combines any number of
+//! [`PcmSource`]s into one so background music and announcements can play together.
+//! Feed it to NTgCalls with a [`PcmPipe`](crate::pipe::PcmPipe):
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     mixer::{Mixer, SourceOptions},
+//!     pipe::{PcmFormat, PcmPipe, RawPcm},
+//!     structures::{ChatId, MediaDescription},
+//!     NTgCall,
+//! };
+//!
+//! let format = PcmFormat::new(48000, 2);
+//! let mixer = Mixer::new(format);
+//! let pipe = PcmPipe::spawn(mixer.clone(), format)?;
+//!
+//! let call = NTgCall::new();
+//! let desc = MediaDescription {
+//!     audio: Some(pipe.audio_description()),
+//!     video: None,
+//! };
+//! call.get_params(ChatId::from_channel(1234567890), desc)?;
+//!
+//! mixer.add(RawPcm::new(std::fs::File::open("music.raw")?));
+//! // the music is ducked while the announcement plays
+//! mixer.add_with(
+//!     RawPcm::new(std::fs::File::open("announcement.raw")?),
+//!     SourceOptions {
+//!         gain: 1.0,
+//!         ducks_others: true,
+//!     },
+//! );
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use crate::pipe::{PcmFormat, PcmSource};
+
+/// Default level of the ducked sources, see [`Mixer::set_ducking`].
+pub const DEFAULT_DUCKING: f32 = 0.3;
+
+/// How long the ducking takes to fade in and out.
+const DUCKING_RAMP: Duration = Duration::from_millis(100);
+
+/// Identifier of a source added to a [`Mixer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(u64);
+
+/// How a source is mixed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceOptions {
+    /// Linear gain applied to the source, `1.0` keeps it unchanged.
+    pub gain: f32,
+    /// Whether the other sources are ducked while this one plays, for example for announcements.
+    pub ducks_others: bool,
+}
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            ducks_others: false,
+        }
+    }
+}
+
+struct Entry {
+    id: SourceId,
+    source: Box<dyn PcmSource>,
+    options: SourceOptions,
+}
+
+struct State {
+    sources: Vec<Entry>,
+    next_id: u64,
+    /// Level of the ducked sources while a ducking source plays.
+    ducking: f32,
+    /// Current level of the ducked sources, it moves towards the target by `ramp_step` per sample.
+    level: f32,
+    ramp_step: f32,
+    scratch: Vec<i16>,
+    levels: Vec<f32>,
+    mix: Vec<f32>,
+}
+
+/// Mixes multiple [`PcmSource`]s with per source gain and ducking.
+///
+/// All the sources must produce samples in the format given to [`Mixer::new`].
+/// Sources can be added and removed while the mixer is playing, the ended sources
+/// are removed automatically. The mixer itself never ends, it plays silence when
+/// there are no sources so the call keeps streaming.
+///
+/// The clones of a [`Mixer`] control the same mix.
+///
+/// The sources are read while the mix is locked, so a source must not block, for example
+/// on a network read, and must not call the [`Mixer`] it was added to, or the call stalls
+/// or deadlocks. Decode or buffer slow inputs in another thread and add the buffered samples.
+#[derive(Clone)]
+pub struct Mixer {
+    state: Arc<Mutex<State>>,
+}
+
+impl Mixer {
+    /// Create a new empty mixer for sources in the given format.
+    pub fn new(format: PcmFormat) -> Self {
+        let ramp = format.samples(DUCKING_RAMP).max(1) as f32;
+
+        Self {
+            state: Arc::new(Mutex::new(State {
+                sources: Vec::new(),
+                next_id: 0,
+                ducking: DEFAULT_DUCKING,
+                level: 1.0,
+                ramp_step: 1.0 / ramp,
+                scratch: Vec::new(),
+                levels: Vec::new(),
+                mix: Vec::new(),
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add a source with the default [`SourceOptions`].
+    pub fn add<S: PcmSource + 'static>(&self, source: S) -> SourceId {
+        self.add_with(source, SourceOptions::default())
+    }
+
+    /// Add a source, it starts playing with the next samples read from the mixer.
+    ///
+    /// The source must not block nor use the mixer while it's read, see [`Mixer`].
+    pub fn add_with<S: PcmSource + 'static>(&self, source: S, options: SourceOptions) -> SourceId {
+        let mut state = self.state();
+
+        let id = SourceId(state.next_id);
+        state.next_id += 1;
+        state.sources.push(Entry {
+            id,
+            source: Box::new(source),
+            options,
+        });
+
+        id
+    }
+
+    /// Remove a source, returns whether it was still playing.
+    pub fn remove(&self, id: SourceId) -> bool {
+        let mut state = self.state();
+        let before = state.sources.len();
+        state.sources.retain(|entry| entry.id != id);
+        state.sources.len() != before
+    }
+
+    /// Change the gain of a source, returns whether it was still playing.
+    pub fn set_gain(&self, id: SourceId, gain: f32) -> bool {
+        self.state()
+            .sources
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.options.gain = gain)
+            .is_some()
+    }
+
+    /// Change the level, between `0.0` and `1.0`, of the other sources while a source
+    /// that [`ducks_others`](SourceOptions::ducks_others) plays.
+    pub fn set_ducking(&self, level: f32) {
+        self.state().ducking = level.clamp(0.0, 1.0);
+    }
+
+    /// Identifiers of the sources that are still playing.
+    pub fn sources(&self) -> Vec<SourceId> {
+        self.state().sources.iter().map(|entry| entry.id).collect()
+    }
+}
+
+impl PcmSource for Mixer {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        let mut state = self.state();
+        let State {
+            sources,
+            ducking,
+            level,
+            ramp_step,
+            scratch,
+            levels,
+            mix,
+            ..
+        } = &mut *state;
+
+        scratch.resize(buf.len(), 0);
+        mix.clear();
+        mix.resize(buf.len(), 0.0);
+
+        let ducked = sources.iter().any(|entry| entry.options.ducks_others);
+        let target = if ducked { *ducking } else { 1.0 };
+
+        // the ducking level of every sample of this chunk
+        levels.clear();
+        levels.extend((0..buf.len()).map(|_| {
+            *level = if *level < target {
+                (*level + *ramp_step).min(target)
+            } else {
+                (*level - *ramp_step).max(target)
+            };
+            *level
+        }));
+
+        sources.retain_mut(|entry| {
+            let read = entry.source.read(scratch);
+
+            for (i, (mix, &sample)) in mix.iter_mut().zip(&scratch[..read]).enumerate() {
+                let level = if entry.options.ducks_others {
+                    1.0
+                } else {
+                    levels[i]
+                };
+
+                *mix += f32::from(sample) * entry.options.gain * level;
+            }
+
+            read == scratch.len()
+        });
+
+        for (sample, mix) in buf.iter_mut().zip(mix.iter()) {
+            *sample = mix.round().clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+        }
+
+        buf.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipe::test::Constant;
+
+    fn constant(sample: i16) -> Constant {
+        Constant {
+            sample,
+            remaining: None,
+        }
+    }
+
+    #[test]
+    fn mix_with_gain() {
+        let mut mixer = Mixer::new(PcmFormat::new(1000, 1));
+        let mut buf = [0; 10];
+
+        // silence without sources
+        assert_eq!(mixer.read(&mut buf), 10);
+        assert_eq!(buf, [0; 10]);
+
+        let music = mixer.add(constant(1000));
+        let voice = mixer.add_with(
+            constant(2000),
+            SourceOptions {
+                gain: 0.5,
+                ducks_others: false,
+            },
+        );
+        mixer.read(&mut buf);
+        assert_eq!(buf, [2000; 10]);
+
+        assert!(mixer.set_gain(music, 2.0));
+        mixer.read(&mut buf);
+        assert_eq!(buf, [3000; 10]);
+
+        assert!(mixer.remove(voice));
+        assert!(!mixer.remove(voice));
+        mixer.read(&mut buf);
+        assert_eq!(buf, [2000; 10]);
+
+        // the mix is clipped instead of wrapping around
+        mixer.add(constant(i16::MAX));
+        mixer.read(&mut buf);
+        assert_eq!(buf, [i16::MAX; 10]);
+    }
+
+    #[test]
+    fn ended_sources_are_removed() {
+        let mut mixer = Mixer::new(PcmFormat::new(1000, 1));
+        mixer.add(Constant {
+            sample: 100,
+            remaining: Some(5),
+        });
+
+        let mut buf = [0; 10];
+        assert_eq!(mixer.read(&mut buf), 10);
+        assert_eq!(buf, [100, 100, 100, 100, 100, 0, 0, 0, 0, 0]);
+        assert!(mixer.sources().is_empty());
+    }
+
+    #[test]
+    fn ducking() {
+        // the ramp lasts 100 samples at 1000 Hz
+        let mut mixer = Mixer::new(PcmFormat::new(1000, 1));
+        mixer.add(constant(1000));
+        mixer.set_ducking(0.5);
+
+        let announcement = mixer.add_with(
+            Constant {
+                sample: 0,
+                remaining: None,
+            },
+            SourceOptions {
+                gain: 1.0,
+                ducks_others: true,
+            },
+        );
+
+        let mut buf = [0; 100];
+        mixer.read(&mut buf);
+        assert!(buf[0] < 1000 && buf[0] > 500);
+        assert_eq!(buf[99], 500);
+
+        // the level goes back up once the announcement is removed
+        mixer.remove(announcement);
+        mixer.read(&mut buf);
+        assert!(buf[0] > 500);
+        assert_eq!(buf[99], 1000);
+    }
+}
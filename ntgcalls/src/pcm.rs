@@ -0,0 +1,801 @@
+//! Sample conversions, resampling, channel remixing and dithering of PCM audio.
+//!
+//! NTgCalls reads interleaved PCM with exactly the sample rate, channel count and bits per
+//! sample of the [`AudioDescription`](crate::structures::AudioDescription). The building blocks
+//! of this module turn any audio produced in Rust into that layout:
+//! - [`Sample`] converts between `f32`, `i16` and `u8` samples, and [`SampleFormat`] decodes
+//!   the bytes of raw PCM.
+//! - [`remix`] changes the channel count of a frame.
+//! - [`Resampler`] changes the sample rate with a windowed sinc filter.
+//! - [`Ditherer`] quantizes to `i16` or `u8` with optional triangular dither.
+//!
+//! [`SamplesExt`] chains them on any iterator of interleaved `f32` samples, [`ReadSamples`]
+//! reads the samples from any [`Read`], and [`IterSource`] streams the result with a
+//! [`PcmPipe`](crate::pipe::PcmPipe):
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use ntgcalls::{
+//!     pcm::{Dither, IterSource, ReadSamples, SampleFormat, SamplesExt},
+//!     pipe::{PcmFormat, PcmPipe},
+//! };
+//!
+//! // mono 32-bit float samples at 22050 Hz, for example from a text-to-speech engine
+//! let samples = ReadSamples::new(std::fs::File::open("speech.f32")?, SampleFormat::F32)
+//!     .remix(1, 2)
+//!     .resample(2, 22050, 48000)
+//!     .quantize::<i16>(Dither::Triangular);
+//!
+//! let pipe = PcmPipe::spawn(IterSource::new(samples), PcmFormat::new(48000, 2))?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use std::{
+    f64::consts::PI,
+    io::{BufReader, Read},
+    marker::PhantomData,
+};
+
+use crate::pipe::PcmSource;
+
+/// Half of the length of the resampling filter, in input frames when upsampling.
+const HALF_TAPS: usize = 16;
+
+/// Number of precomputed filter phases between two input frames.
+const PHASES: usize = 256;
+
+/// Largest downsampling ratio the resampling filter is stretched to, from 768 kHz to 8 kHz,
+/// so the filter table stays bounded whatever the rates.
+const MAX_RATIO: f64 = 96.0;
+
+/// Cutoff of the resampling filter as a fraction of the lowest Nyquist frequency,
+/// the rest of the band is left for the transition of the filter.
+const CUTOFF: f64 = 0.95;
+
+/// A PCM sample.
+pub trait Sample: Copy + Send + 'static {
+    /// Value of the least significant bit, as a fraction of the range from `-1.0` to `1.0`.
+    const LSB: f32;
+
+    /// Convert the sample to a value between `-1.0` and `1.0`.
+    fn to_f32(self) -> f32;
+
+    /// Convert a value between `-1.0` and `1.0` to the nearest sample, clipping the values
+    /// out of range.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for f32 {
+    const LSB: f32 = 0.0;
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+/// Signed 16-bit samples.
+impl Sample for i16 {
+    const LSB: f32 = 1.0 / 32768.0;
+
+    fn to_f32(self) -> f32 {
+        f32::from(self) / 32768.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+    }
+}
+
+/// Unsigned 8-bit samples, silence is `128`.
+impl Sample for u8 {
+    const LSB: f32 = 1.0 / 128.0;
+
+    fn to_f32(self) -> f32 {
+        (f32::from(self) - 128.0) / 128.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+/// Encoding of the samples of raw PCM, all the encodings are little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SampleFormat {
+    /// Unsigned 8-bit.
+    U8,
+    /// Signed 16-bit.
+    S16,
+    /// Signed 24-bit, packed in 3 bytes.
+    S24,
+    /// Signed 32-bit.
+    S32,
+    /// 32-bit float, between `-1.0` and `1.0`.
+    F32,
+    /// 64-bit float, between `-1.0` and `1.0`.
+    F64,
+}
+
+impl SampleFormat {
+    /// Size of a sample in bytes.
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::S16 => 2,
+            Self::S24 => 3,
+            Self::S32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Decode the first [`bytes`](Self::bytes) bytes of `b` to a value between `-1.0` and `1.0`.
+    ///
+    /// ## Panics
+    /// If `b` is shorter than a sample.
+    pub fn decode(self, b: &[u8]) -> f32 {
+        match self {
+            Self::U8 => b[0].to_f32(),
+            Self::S16 => i16::from_le_bytes([b[0], b[1]]).to_f32(),
+            Self::S24 => (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0,
+            Self::S32 => {
+                (f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])) / 2_147_483_648.0) as f32
+            }
+            Self::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            Self::F64 => {
+                f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+            }
+        }
+    }
+}
+
+/// This function allows mixing a frame to the channel count of `output`.
+///
+/// Every output channel is the average of the input channels with the same position modulo
+/// the output channel count, so stereo is mixed down to mono by averaging both channels and
+/// mono is copied to both channels of stereo.
+///
+/// ## Parameters
+/// - `input`: One sample for every input channel.
+/// - `output`: One sample for every output channel, overwritten.
+pub fn remix(input: &[f32], output: &mut [f32]) {
+    let (from, to) = (input.len(), output.len());
+
+    for (channel, sample) in output.iter_mut().enumerate() {
+        let (sum, count) = input
+            .iter()
+            .skip(channel % from)
+            .step_by(to)
+            .fold((0.0, 0.0), |(sum, count), sample| {
+                (sum + sample, count + 1.0)
+            });
+
+        *sample = sum / count;
+    }
+}
+
+/// Changes the sample rate of interleaved frames with a windowed sinc filter.
+///
+/// The filter removes the frequencies above the Nyquist frequency of the lowest of the two
+/// rates, so downsampling doesn't alias. When both rates are the same the frames are passed
+/// through unchanged. Downsampling by more than 96 times, for example from more than 768 kHz
+/// to 8 kHz, keeps the filter of a 96 times ratio and aliases the frequencies in between.
+///
+/// Push the input frames with [`push`](Self::push) and read the output frames with
+/// [`next_frame`](Self::next_frame) until it returns `false`, then push the next input frame.
+/// Call [`finish`](Self::finish) at the end of the input to read the remaining frames.
+pub struct Resampler {
+    channels: usize,
+    from_rate: u64,
+    to_rate: u64,
+    /// Half of the length of the filter in input frames, `0` when the frames are passed through.
+    half: usize,
+    /// The `2 * half` filter taps of each of the `PHASES + 1` phases.
+    table: Vec<f32>,
+    /// Input frames, interleaved.
+    history: Vec<f32>,
+    /// Index of the first frame of `history` in the input, negative for the leading silence.
+    origin: i64,
+    /// Number of frames pushed.
+    pushed: u64,
+    /// Number of frames computed, the position of the next one in the input is
+    /// `produced * from_rate / to_rate`.
+    produced: u64,
+    finished: bool,
+}
+
+impl Resampler {
+    /// Create a new [`Resampler`]
+    ///
+    /// ## Panics
+    /// If the channel count or one of the rates is zero.
+    pub fn new(channels: u16, from_rate: u32, to_rate: u32) -> Self {
+        assert!(channels > 0, "channels must be greater than zero");
+        assert!(
+            from_rate > 0 && to_rate > 0,
+            "the sample rates must be greater than zero"
+        );
+
+        let (half, table) = if from_rate == to_rate {
+            (0, Vec::new())
+        } else {
+            // when downsampling the filter is stretched to the lower output rate
+            let ratio = (f64::from(to_rate) / f64::from(from_rate)).clamp(1.0 / MAX_RATIO, 1.0);
+            let half = (HALF_TAPS as f64 / ratio).ceil() as usize;
+            (half, filter_table(half, CUTOFF * ratio))
+        };
+
+        Self {
+            channels: usize::from(channels),
+            from_rate: u64::from(from_rate),
+            to_rate: u64::from(to_rate),
+            half,
+            table,
+            // the frames before the input are silent
+            history: vec![0.0; half * usize::from(channels)],
+            origin: -(half as i64),
+            pushed: 0,
+            produced: 0,
+            finished: false,
+        }
+    }
+
+    /// Add the next input frame, one sample for every channel.
+    pub fn push(&mut self, frame: &[f32]) {
+        debug_assert_eq!(frame.len(), self.channels);
+
+        self.history.extend_from_slice(frame);
+        self.pushed += 1;
+    }
+
+    /// Mark the end of the input, the frames still needed by the filter are silent.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Compute the next output frame in `output`.
+    ///
+    /// ## Return
+    /// `false` if more input frames are needed, or at the end after [`finish`](Self::finish).
+    pub fn next_frame(&mut self, output: &mut [f32]) -> bool {
+        debug_assert_eq!(output.len(), self.channels);
+
+        let position = self.produced * self.from_rate;
+
+        // the output ends with the input
+        if self.finished && position >= self.pushed * self.to_rate {
+            return false;
+        }
+
+        // the input frame at or before the output frame, from the start of `history`
+        let index = ((position / self.to_rate) as i64 - self.origin) as usize;
+        let offset = (position % self.to_rate) as f64 / self.to_rate as f64;
+        let frames = self.history.len() / self.channels;
+
+        if index + self.half >= frames {
+            if !self.finished {
+                return false;
+            }
+
+            self.history
+                .resize((index + self.half + 1) * self.channels, 0.0);
+        }
+
+        if self.half == 0 {
+            let start = index * self.channels;
+            output.copy_from_slice(&self.history[start..start + self.channels]);
+        } else {
+            self.filter(index, offset, output);
+        }
+
+        self.produced += 1;
+        self.discard();
+        true
+    }
+
+    /// Apply the filter around the input frame `index`, `offset` frames before the output frame.
+    fn filter(&self, index: usize, offset: f64, output: &mut [f32]) {
+        let taps = 2 * self.half;
+        let position = offset * PHASES as f64;
+        let phase = (position as usize).min(PHASES - 1);
+        let weight = (position - phase as f64) as f32;
+
+        let low = &self.table[phase * taps..][..taps];
+        let high = &self.table[(phase + 1) * taps..][..taps];
+
+        output.fill(0.0);
+
+        let first = (index + 1 - self.half) * self.channels;
+        let frames = self.history[first..].chunks_exact(self.channels);
+
+        for ((frame, low), high) in frames.zip(low).zip(high) {
+            let tap = low + (high - low) * weight;
+
+            for (output, sample) in output.iter_mut().zip(frame) {
+                *output += sample * tap;
+            }
+        }
+    }
+
+    /// Drop the input frames that aren't needed anymore.
+    fn discard(&mut self) {
+        let index = (self.produced * self.from_rate / self.to_rate) as i64 - self.origin;
+        // past a ratio of `MAX_RATIO` the next frame can be further than the frames pushed
+        let unused = ((index + 1 - self.half.max(1) as i64).max(0) as usize)
+            .min(self.history.len() / self.channels);
+
+        // don't move the history for every frame
+        if unused >= 1024 || unused * self.channels >= self.history.len() / 2 {
+            self.history.drain(..unused * self.channels);
+            self.origin += unused as i64;
+        }
+    }
+}
+
+/// Build the taps of a Blackman windowed sinc low-pass filter for every phase.
+fn filter_table(half: usize, cutoff: f64) -> Vec<f32> {
+    let taps = 2 * half;
+    let mut table = Vec::with_capacity((PHASES + 1) * taps);
+
+    for phase in 0..=PHASES {
+        let offset = phase as f64 / PHASES as f64;
+        let row = table.len();
+
+        // tap `k` weighs the input frame `k + 1 - half` frames away from the output frame
+        table.extend((0..taps).map(|k| {
+            let x = (k as f64 + 1.0 - half as f64) - offset;
+            let window = x / half as f64;
+
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * cutoff * x).sin() / (PI * cutoff * x)
+            };
+            let blackman = 0.42 + 0.5 * (PI * window).cos() + 0.08 * (2.0 * PI * window).cos();
+
+            (sinc * blackman) as f32
+        }));
+
+        // a constant signal keeps its level
+        let sum: f32 = table[row..].iter().sum();
+        for tap in &mut table[row..] {
+            *tap /= sum;
+        }
+    }
+
+    table
+}
+
+/// Noise added before quantizing the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Dither {
+    /// The samples are rounded to the nearest value.
+    #[default]
+    None,
+    /// Triangular noise of one least significant bit, it turns the quantization distortion
+    /// of quiet passages into a constant low noise floor.
+    Triangular,
+}
+
+/// Quantizes `f32` samples to [`Sample`]s with a [`Dither`].
+///
+/// The noise is pseudo-random with a fixed seed, so the output is deterministic.
+#[derive(Debug, Clone)]
+pub struct Ditherer {
+    dither: Dither,
+    random: Random,
+}
+
+impl Ditherer {
+    /// Create a new [`Ditherer`]
+    pub fn new(dither: Dither) -> Self {
+        Self {
+            dither,
+            random: Random::new(),
+        }
+    }
+
+    /// Quantize a value between `-1.0` and `1.0`.
+    pub fn quantize<S: Sample>(&mut self, value: f32) -> S {
+        match self.dither {
+            Dither::None => S::from_f32(value),
+            Dither::Triangular => {
+                let noise = self.random.next_f32() - self.random.next_f32();
+                S::from_f32(value + noise * S::LSB)
+            }
+        }
+    }
+}
+
+/// A xorshift32 pseudo-random generator with a fixed seed.
+#[derive(Debug, Clone)]
+pub(crate) struct Random(u32);
+
+impl Random {
+    pub(crate) fn new() -> Self {
+        Self(0x9e37_79b9)
+    }
+
+    /// A pseudo-random value between `0.0` and `1.0`.
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Adapters for iterators of interleaved `f32` samples.
+///
+/// Map samples of another type with [`Sample::to_f32`] first.
+pub trait SamplesExt: Iterator<Item = f32> + Sized {
+    /// Mix the frames from `from` channels to `to` channels, see [`remix`].
+    ///
+    /// ## Panics
+    /// If one of the channel counts is zero.
+    fn remix(self, from: u16, to: u16) -> Remix<Self> {
+        assert!(from > 0 && to > 0, "channels must be greater than zero");
+
+        Remix {
+            samples: self,
+            input: vec![0.0; usize::from(from)],
+            output: vec![0.0; usize::from(to)],
+            offset: usize::from(to),
+        }
+    }
+
+    /// Change the sample rate of the frames, see [`Resampler`].
+    ///
+    /// ## Panics
+    /// If the channel count or one of the rates is zero.
+    fn resample(self, channels: u16, from_rate: u32, to_rate: u32) -> Resample<Self> {
+        Resample {
+            samples: self,
+            resampler: Resampler::new(channels, from_rate, to_rate),
+            input: vec![0.0; usize::from(channels)],
+            output: vec![0.0; usize::from(channels)],
+            offset: usize::from(channels),
+        }
+    }
+
+    /// Quantize the samples, see [`Ditherer`].
+    fn quantize<S: Sample>(self, dither: Dither) -> Quantize<Self, S> {
+        Quantize {
+            samples: self,
+            ditherer: Ditherer::new(dither),
+            sample: PhantomData,
+        }
+    }
+}
+
+impl<I: Iterator<Item = f32>> SamplesExt for I {}
+
+/// Fill `frame` with the next samples, returns `false` if the samples end before the frame is full.
+fn read_frame<I: Iterator<Item = f32>>(samples: &mut I, frame: &mut [f32]) -> bool {
+    frame
+        .iter_mut()
+        .all(|sample| samples.next().map(|s| *sample = s).is_some())
+}
+
+/// Iterator returned by [`SamplesExt::remix`], a trailing partial frame is ignored.
+pub struct Remix<I> {
+    samples: I,
+    input: Vec<f32>,
+    output: Vec<f32>,
+    offset: usize,
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Remix<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.offset == self.output.len() {
+            if !read_frame(&mut self.samples, &mut self.input) {
+                return None;
+            }
+
+            remix(&self.input, &mut self.output);
+            self.offset = 0;
+        }
+
+        self.offset += 1;
+        Some(self.output[self.offset - 1])
+    }
+}
+
+/// Iterator returned by [`SamplesExt::resample`], a trailing partial frame is ignored.
+pub struct Resample<I> {
+    samples: I,
+    resampler: Resampler,
+    input: Vec<f32>,
+    output: Vec<f32>,
+    offset: usize,
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Resample<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.offset == self.output.len() {
+            if self.resampler.next_frame(&mut self.output) {
+                self.offset = 0;
+            } else if self.resampler.finished {
+                return None;
+            } else if read_frame(&mut self.samples, &mut self.input) {
+                self.resampler.push(&self.input);
+            } else {
+                self.resampler.finish();
+            }
+        }
+
+        self.offset += 1;
+        Some(self.output[self.offset - 1])
+    }
+}
+
+/// Iterator returned by [`SamplesExt::quantize`].
+pub struct Quantize<I, S> {
+    samples: I,
+    ditherer: Ditherer,
+    sample: PhantomData<fn() -> S>,
+}
+
+impl<I: Iterator<Item = f32>, S: Sample> Iterator for Quantize<I, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<S> {
+        let value = self.samples.next()?;
+        Some(self.ditherer.quantize(value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+/// Reads raw PCM in any [`SampleFormat`] as `f32` samples.
+///
+/// The samples end at the end of the reader or at the first read error,
+/// use [`convert::Decoder`](crate::convert::Decoder) to handle the errors.
+pub struct ReadSamples<R> {
+    reader: BufReader<R>,
+    format: SampleFormat,
+    bytes: [u8; 8],
+}
+
+impl<R: Read> ReadSamples<R> {
+    /// Create a new [`ReadSamples`]
+    pub fn new(reader: R, format: SampleFormat) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            format,
+            bytes: [0; 8],
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReadSamples<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bytes = &mut self.bytes[..self.format.bytes()];
+        self.reader.read_exact(bytes).ok()?;
+
+        Some(self.format.decode(bytes))
+    }
+}
+
+/// A [`PcmSource`] reading the samples of an iterator, ending with it.
+pub struct IterSource<I>(I);
+
+impl<I: Iterator<Item = i16> + Send> IterSource<I> {
+    /// Create a new [`IterSource`]
+    pub fn new(samples: I) -> Self {
+        Self(samples)
+    }
+}
+
+impl<I: Iterator<Item = i16> + Send> PcmSource for IterSource<I> {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        buf.iter_mut()
+            .zip(&mut self.0)
+            .map(|(sample, value)| *sample = value)
+            .count()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A sine tone of `frequency` Hz at `sample_rate`, lasting `frames` frames.
+    fn tone(frequency: f64, sample_rate: u32, amplitude: f64, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| {
+                let t = i as f64 / f64::from(sample_rate);
+                (amplitude * (2.0 * PI * frequency * t).sin()) as f32
+            })
+            .collect()
+    }
+
+    /// Amplitude of the `frequency` Hz component of a mono signal, with the Goertzel algorithm.
+    pub(crate) fn level(samples: &[f32], frequency: f64, sample_rate: u32) -> f64 {
+        let coefficient = 2.0 * (2.0 * PI * frequency / f64::from(sample_rate)).cos();
+        let (mut s1, mut s2) = (0.0, 0.0);
+
+        for &sample in samples {
+            let s0 = f64::from(sample) + coefficient * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+
+        let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+        2.0 * power.sqrt() / samples.len() as f64
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        let sum: f64 = samples.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+        (sum / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn sample_conversions() {
+        assert_eq!(i16::MIN.to_f32(), -1.0);
+        assert_eq!(i16::from_f32(0.5), 16384);
+        assert_eq!(i16::from_f32(2.0), i16::MAX);
+        assert_eq!(u8::from_f32(0.0), 128);
+        assert_eq!(u8::from_f32(-1.0), 0);
+        assert_eq!(u8::from_f32(1.0), 255);
+        assert_eq!(64u8.to_f32(), -0.5);
+
+        for sample in [i16::MIN, -1, 0, 1, 12345, i16::MAX] {
+            assert_eq!(i16::from_f32(sample.to_f32()), sample);
+        }
+
+        assert_eq!(SampleFormat::S16.decode(&(-16384i16).to_le_bytes()), -0.5);
+        assert_eq!(SampleFormat::S24.decode(&[0, 0, 0xc0]), -0.5);
+        assert_eq!(SampleFormat::F64.decode(&0.25f64.to_le_bytes()), 0.25);
+    }
+
+    #[test]
+    fn remix_channels() {
+        let mut mono = [0.0];
+        remix(&[0.5, -0.25], &mut mono);
+        assert_eq!(mono, [0.125]);
+
+        let mut stereo = [0.0; 2];
+        remix(&[0.5], &mut stereo);
+        assert_eq!(stereo, [0.5, 0.5]);
+
+        // 5.1 to stereo
+        remix(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], &mut stereo);
+        assert!((stereo[0] - 0.3).abs() < 1e-6 && (stereo[1] - 0.4).abs() < 1e-6);
+
+        let samples: Vec<f32> = [0.5, -0.5, 1.0].into_iter().remix(1, 2).collect();
+        assert_eq!(samples, [0.5, 0.5, -0.5, -0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn resample_tone() {
+        let input = tone(1000.0, 44100, 0.5, 44100);
+        let output: Vec<f32> = input.iter().copied().resample(1, 44100, 48000).collect();
+
+        // one second in, one second out
+        assert_eq!(output.len(), 48000);
+
+        // the tone keeps its frequency and level, ignoring the start and the end of the filter
+        let middle = &output[4800..43200];
+        assert!((level(middle, 1000.0, 48000) - 0.5).abs() < 0.005);
+
+        // nothing else is added
+        assert!((rms(middle) - 0.5 / 2f64.sqrt()).abs() < 0.005);
+    }
+
+    #[test]
+    fn resample_does_not_alias() {
+        // 10 kHz is above the Nyquist frequency of 16 kHz and would fold back to 6 kHz
+        let input = tone(10000.0, 48000, 0.5, 48000);
+        let output: Vec<f32> = input.into_iter().resample(1, 48000, 16000).collect();
+        assert_eq!(output.len(), 16000);
+        assert!(rms(&output[1600..14400]) < 0.005);
+
+        // while a tone in the band passes
+        let input = tone(3000.0, 48000, 0.5, 48000);
+        let output: Vec<f32> = input.into_iter().resample(1, 48000, 16000).collect();
+        assert!((level(&output[1600..14400], 3000.0, 16000) - 0.5).abs() < 0.005);
+    }
+
+    #[test]
+    fn resample_stereo() {
+        // a different tone on each channel
+        let left = tone(440.0, 8000, 0.5, 8000);
+        let right = tone(880.0, 8000, 0.25, 8000);
+        let input = left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]);
+
+        let output: Vec<f32> = input.resample(2, 8000, 48000).collect();
+        assert_eq!(output.len(), 2 * 48000);
+
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = output.iter().skip(1).step_by(2).copied().collect();
+        assert!((level(&left[4800..43200], 440.0, 48000) - 0.5).abs() < 0.005);
+        assert!(level(&left[4800..43200], 880.0, 48000) < 0.005);
+        assert!((level(&right[4800..43200], 880.0, 48000) - 0.25).abs() < 0.005);
+    }
+
+    #[test]
+    fn resample_extreme_ratio() {
+        let resampler = Resampler::new(1, u32::MAX, 8000);
+        assert_eq!(resampler.half, HALF_TAPS * MAX_RATIO as usize);
+        assert_eq!(resampler.table.len(), (PHASES + 1) * 2 * resampler.half);
+
+        let output: Vec<f32> = [0.5; 100].into_iter().resample(1, u32::MAX, 8000).collect();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn same_rate_is_unchanged() {
+        let input = tone(1000.0, 48000, 0.5, 480);
+        let output: Vec<f32> = input.iter().copied().resample(1, 48000, 48000).collect();
+        assert_eq!(output, input);
+
+        assert_eq!([0.5].into_iter().resample(1, 8000, 16000).count(), 2);
+        assert_eq!(std::iter::empty().resample(1, 8000, 16000).count(), 0);
+    }
+
+    #[test]
+    fn dithering() {
+        // a tone of a third of a bit is lost without dither
+        let quiet = tone(1000.0, 48000, 1.0 / 3.0 / 32768.0, 48000);
+        assert!(quiet
+            .iter()
+            .copied()
+            .quantize::<i16>(Dither::None)
+            .all(|s| s == 0));
+
+        // and kept, under the noise, with dither
+        let dithered: Vec<f32> = quiet
+            .iter()
+            .copied()
+            .quantize::<i16>(Dither::Triangular)
+            .map(Sample::to_f32)
+            .collect();
+        let level = level(&dithered, 1000.0, 48000) * 32768.0;
+        assert!((level - 1.0 / 3.0).abs() < 0.05, "{level}");
+
+        // the noise is at most one bit
+        let mut ditherer = Ditherer::new(Dither::Triangular);
+        for _ in 0..1000 {
+            let sample: i16 = ditherer.quantize(0.5);
+            assert!((16383..=16385).contains(&sample));
+        }
+        assert_eq!(ditherer.quantize::<f32>(0.5), 0.5);
+    }
+
+    #[test]
+    fn read_samples() {
+        let bytes: Vec<u8> = [0u8, 128, 192, 64].to_vec();
+        let samples: Vec<f32> = ReadSamples::new(&bytes[..], SampleFormat::U8).collect();
+        assert_eq!(samples, [-1.0, 0.0, 0.5, -0.5]);
+
+        // a trailing partial sample is ignored
+        let bytes = [0, 0x40, 0];
+        let mut source = IterSource::new(
+            ReadSamples::new(&bytes[..], SampleFormat::S16).quantize::<i16>(Dither::None),
+        );
+        let mut buf = [0; 4];
+        assert_eq!(source.read(&mut buf), 1);
+        assert_eq!(buf[0], 16384);
+    }
+}
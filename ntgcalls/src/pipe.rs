@@ -0,0 +1,547 @@
+//! Feeding PCM audio and raw video produced in Rust to NTgCalls.
+//!
+//! A [`PcmSource`] produces signed 16-bit samples, and on Unix a [`PcmPipe`] writes them to a
+//! named pipe that NTgCalls reads with [`InputMode::File`], so the audio can be generated,
+//! mixed or filtered on the fly without restarting the stream with `change_stream`.
+//! A [`VideoSource`] and a [`VideoPipe`] do the same for YUV420p video frames.
+
+use std::{io::Read, time::Duration};
+
+use crate::{
+    enums::InputMode, errors::IntoCStringError, profiles::VideoProfile,
+    structures::AudioDescription, utils::IntoCString,
+};
+
+#[cfg(unix)]
+pub use self::unix::{PcmPipe, VideoPipe};
+
+/// Format of PCM audio, the samples are signed 16-bit little endian and interleaved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PcmFormat {
+    /// Audio sample rate.
+    pub sample_rate: u32,
+    /// Audio channel count.
+    pub channel_count: u8,
+}
+
+impl PcmFormat {
+    /// Create a new [`PcmFormat`]
+    pub const fn new(sample_rate: u32, channel_count: u8) -> Self {
+        Self {
+            sample_rate,
+            channel_count,
+        }
+    }
+
+    /// Number of samples, counting every channel, that last for `duration`.
+    pub fn samples(&self, duration: Duration) -> usize {
+        let frames = u128::from(self.sample_rate) * duration.as_micros() / 1_000_000;
+        frames as usize * usize::from(self.channel_count)
+    }
+
+    /// The [`AudioDescription`] of a source in this format.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] if the input can't be passed to NTgCalls.
+    pub fn audio_description<S: IntoCString>(
+        &self,
+        input_mode: InputMode,
+        input: S,
+    ) -> Result<AudioDescription, IntoCStringError> {
+        AudioDescription::new(input_mode, input, self.sample_rate, 16, self.channel_count)
+    }
+}
+
+/// A source of PCM samples.
+pub trait PcmSource: Send {
+    /// Fill `buf` with interleaved samples.
+    ///
+    /// ## Return
+    /// The number of samples written, it's less than the length of `buf` only when the source ended.
+    fn read(&mut self, buf: &mut [i16]) -> usize;
+}
+
+impl<S: PcmSource + ?Sized> PcmSource for Box<S> {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        (**self).read(buf)
+    }
+}
+
+/// A [`PcmSource`] reading signed 16-bit little endian samples, for example from a file
+/// or from the output of a text-to-speech engine.
+///
+/// The source ends at the end of the reader or at the first read error.
+pub struct RawPcm<R> {
+    reader: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read + Send> RawPcm<R> {
+    /// Create a new [`RawPcm`]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read + Send> PcmSource for RawPcm<R> {
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        self.bytes.resize(buf.len() * 2, 0);
+
+        let mut filled = 0;
+        while filled < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+
+        for (sample, bytes) in buf.iter_mut().zip(self.bytes[..filled].chunks_exact(2)) {
+            *sample = i16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+
+        filled / 2
+    }
+}
+
+/// A source of raw video frames.
+///
+/// The frames are YUV420p, as read by NTgCalls: the full resolution Y plane followed by the
+/// U and V planes at half the width and half the height.
+pub trait VideoSource: Send {
+    /// Fill `frame`, of [`frame_size`] bytes, with the next frame.
+    ///
+    /// ## Return
+    /// `false` when the source ended, `frame` is ignored then.
+    fn read_frame(&mut self, frame: &mut [u8]) -> bool;
+}
+
+impl<S: VideoSource + ?Sized> VideoSource for Box<S> {
+    fn read_frame(&mut self, frame: &mut [u8]) -> bool {
+        (**self).read_frame(frame)
+    }
+}
+
+/// Size in bytes of a YUV420p frame of the profile.
+pub fn frame_size(profile: VideoProfile) -> usize {
+    usize::from(profile.width) * usize::from(profile.height) * 3 / 2
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        ffi::CString,
+        fs::{self, File, OpenOptions},
+        io::{self, Write},
+        os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::{PcmFormat, PcmSource, VideoSource};
+    use crate::{
+        enums::InputMode,
+        profiles::VideoProfile,
+        structures::{AudioDescription, VideoDescription},
+        utils::{self, IntoCString},
+    };
+
+    /// Duration of the chunks written to the pipe.
+    const CHUNK: Duration = Duration::from_millis(10);
+
+    /// A named pipe in the temporary directory fed from a background thread, it's removed
+    /// when dropped.
+    struct Fifo {
+        path: PathBuf,
+        /// The path passed to NTgCalls, checked for nul bytes when creating the pipe.
+        c_path: CString,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl Fifo {
+        /// Create the pipe and start feeding it, `fill` writes the next chunk to the buffer
+        /// and returns whether more chunks follow.
+        fn spawn<F>(kind: &str, fill: F) -> io::Result<Self>
+        where
+            F: FnMut(&mut Vec<u8>) -> bool + Send + 'static,
+        {
+            let path = utils::temp_path(kind, "fifo");
+
+            let c_path = path.as_path().try_into_c_string()?;
+            // SAFETY: the path is a valid nul terminated string.
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let stopped = Arc::new(AtomicBool::new(false));
+
+            let spawned = {
+                let path = path.clone();
+                let stopped = stopped.clone();
+
+                thread::Builder::new()
+                    .name(format!("ntgcalls-{kind}-pipe"))
+                    .spawn(move || feed(&path, fill, &stopped))
+            };
+
+            if let Err(e) = spawned {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+
+            Ok(Self {
+                path,
+                c_path,
+                stopped,
+            })
+        }
+    }
+
+    impl Drop for Fifo {
+        fn drop(&mut self) {
+            // the thread notices it within a chunk, even while NTgCalls doesn't read the pipe
+            self.stopped.store(true, Ordering::Relaxed);
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    /// Wait for a reader without blocking forever, so a pipe that is never read can be dropped.
+    ///
+    /// The file stays non-blocking, the chunks are written with [`write_chunk`].
+    fn open(path: &Path, stopped: &AtomicBool) -> io::Result<File> {
+        loop {
+            if stopped.load(Ordering::Relaxed) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            match OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+            {
+                Ok(file) => return Ok(file),
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => thread::sleep(CHUNK),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Write a chunk once NTgCalls reads the previous ones, without blocking forever, so a
+    /// pipe that is kept open but no longer read can be dropped.
+    fn write_chunk(file: &mut File, mut bytes: &[u8], stopped: &AtomicBool) -> io::Result<()> {
+        let timeout = CHUNK.as_millis() as libc::c_int;
+
+        while !bytes.is_empty() {
+            if stopped.load(Ordering::Relaxed) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            let mut fd = libc::pollfd {
+                fd: file.as_raw_fd(),
+                events: libc::POLLOUT,
+                revents: 0,
+            };
+            // SAFETY: `fd` is a single valid pollfd for the duration of the call.
+            if unsafe { libc::poll(&mut fd, 1, timeout) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            match file.write(bytes) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => bytes = &bytes[written..],
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn feed<F: FnMut(&mut Vec<u8>) -> bool>(path: &Path, mut fill: F, stopped: &AtomicBool) {
+        let Ok(mut file) = open(path, stopped) else {
+            return;
+        };
+
+        let mut bytes = Vec::new();
+
+        while !stopped.load(Ordering::Relaxed) {
+            bytes.clear();
+            let more = fill(&mut bytes);
+
+            // the reader is gone or the pipe was dropped
+            if write_chunk(&mut file, &bytes, stopped).is_err() {
+                return;
+            }
+
+            if !more {
+                return;
+            }
+        }
+    }
+
+    /// A named pipe fed by a [`PcmSource`] from a background thread.
+    ///
+    /// NTgCalls reads the pipe at the playback speed, so the source is read just ahead of
+    /// what is being played. When the source ends the pipe is closed and NTgCalls reports
+    /// the end of the stream. The pipe is removed when [`PcmPipe`] is dropped.
+    pub struct PcmPipe {
+        fifo: Fifo,
+        format: PcmFormat,
+    }
+
+    impl PcmPipe {
+        /// Create a new pipe in the temporary directory and start feeding it with the source.
+        ///
+        /// ## Parameters
+        /// - `source`: The samples to write, in the given format.
+        /// - `format`: Format of the samples.
+        ///
+        /// ## Return
+        /// An [`io::Error`] in case the pipe or the thread couldn't be created.
+        pub fn spawn<S: PcmSource + 'static>(mut source: S, format: PcmFormat) -> io::Result<Self> {
+            let mut samples = vec![0; format.samples(CHUNK).max(1)];
+
+            let fifo = Fifo::spawn("pcm", move |bytes| {
+                let read = source.read(&mut samples);
+                bytes.extend(
+                    samples[..read]
+                        .iter()
+                        .flat_map(|sample| sample.to_le_bytes()),
+                );
+
+                read == samples.len()
+            })?;
+
+            Ok(Self { fifo, format })
+        }
+
+        /// Path of the pipe.
+        pub fn path(&self) -> &Path {
+            &self.fifo.path
+        }
+
+        /// Format of the samples written to the pipe.
+        pub fn format(&self) -> PcmFormat {
+            self.format
+        }
+
+        /// The [`AudioDescription`] to stream the pipe with NTgCalls.
+        pub fn audio_description(&self) -> AudioDescription {
+            self.format
+                .audio_description(InputMode::File, self.fifo.c_path.clone())
+                .expect("the path was checked for nul bytes when creating the pipe")
+        }
+    }
+
+    /// A named pipe fed by a [`VideoSource`] from a background thread, one frame at a time.
+    ///
+    /// Like [`PcmPipe`], NTgCalls reads the pipe at the playback speed and the pipe is
+    /// removed when [`VideoPipe`] is dropped.
+    pub struct VideoPipe {
+        fifo: Fifo,
+        profile: VideoProfile,
+    }
+
+    impl VideoPipe {
+        /// Create a new pipe in the temporary directory and start feeding it with the source.
+        ///
+        /// ## Parameters
+        /// - `source`: The frames to write, with the size and rate of the profile.
+        /// - `profile`: Size and rate of the frames.
+        ///
+        /// ## Return
+        /// An [`io::Error`] in case the pipe or the thread couldn't be created.
+        pub fn spawn<S: VideoSource + 'static>(
+            mut source: S,
+            profile: VideoProfile,
+        ) -> io::Result<Self> {
+            let mut frame = vec![0; super::frame_size(profile)];
+
+            let fifo = Fifo::spawn("video", move |bytes| {
+                let more = source.read_frame(&mut frame);
+                if more {
+                    bytes.extend_from_slice(&frame);
+                }
+
+                more
+            })?;
+
+            Ok(Self { fifo, profile })
+        }
+
+        /// Path of the pipe.
+        pub fn path(&self) -> &Path {
+            &self.fifo.path
+        }
+
+        /// Size and rate of the frames written to the pipe.
+        pub fn profile(&self) -> VideoProfile {
+            self.profile
+        }
+
+        /// The [`VideoDescription`] to stream the pipe with NTgCalls.
+        pub fn video_description(&self) -> VideoDescription {
+            VideoDescription::new(
+                InputMode::File,
+                self.fifo.c_path.clone(),
+                self.profile.width,
+                self.profile.height,
+                self.profile.fps,
+            )
+            .expect("the path was checked for nul bytes when creating the pipe")
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A source repeating the same sample, ending after `remaining` samples if set.
+    pub(crate) struct Constant {
+        pub(crate) sample: i16,
+        pub(crate) remaining: Option<usize>,
+    }
+
+    impl PcmSource for Constant {
+        fn read(&mut self, buf: &mut [i16]) -> usize {
+            let count = self.remaining.map_or(buf.len(), |r| r.min(buf.len()));
+            buf[..count].fill(self.sample);
+
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= count;
+            }
+
+            count
+        }
+    }
+
+    #[test]
+    fn raw_pcm() {
+        let bytes: Vec<u8> = [1i16, -2, 300]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let mut source = RawPcm::new(&bytes[..]);
+
+        let mut buf = [0; 2];
+        assert_eq!(source.read(&mut buf), 2);
+        assert_eq!(buf, [1, -2]);
+        assert_eq!(source.read(&mut buf), 1);
+        assert_eq!(buf[0], 300);
+        assert_eq!(source.read(&mut buf), 0);
+    }
+
+    #[test]
+    fn format_samples() {
+        let format = PcmFormat::new(48000, 2);
+        assert_eq!(format.samples(Duration::from_millis(10)), 960);
+
+        let desc = format.audio_description(InputMode::File, "a.raw").unwrap();
+        assert_eq!(desc.bits_per_sample, 16);
+        assert_eq!(desc.channel_count, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_is_fed() {
+        use std::io::Read;
+
+        let format = PcmFormat::new(8000, 1);
+        let source = Constant {
+            sample: 7,
+            remaining: Some(1000),
+        };
+
+        let pipe = PcmPipe::spawn(source, format).unwrap();
+        assert_eq!(
+            pipe.audio_description().input().to_bytes(),
+            pipe.path().as_os_str().as_encoded_bytes()
+        );
+
+        let mut bytes = Vec::new();
+        std::fs::File::open(pipe.path())
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+
+        assert_eq!(bytes.len(), 2000);
+        assert!(bytes
+            .chunks_exact(2)
+            .all(|b| i16::from_le_bytes([b[0], b[1]]) == 7));
+
+        let path = pipe.path().to_owned();
+        drop(pipe);
+        assert!(!path.exists());
+
+        // a pipe that is never read can be dropped
+        drop(
+            PcmPipe::spawn(
+                Constant {
+                    sample: 0,
+                    remaining: None,
+                },
+                format,
+            )
+            .unwrap(),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn video_pipe_is_fed() {
+        use std::io::Read;
+
+        struct Frames(u8);
+
+        impl VideoSource for Frames {
+            fn read_frame(&mut self, frame: &mut [u8]) -> bool {
+                if self.0 == 3 {
+                    return false;
+                }
+
+                frame.fill(self.0);
+                self.0 += 1;
+                true
+            }
+        }
+
+        let profile = VideoProfile {
+            width: 4,
+            height: 2,
+            fps: 1,
+        };
+        assert_eq!(frame_size(profile), 12);
+
+        let pipe = VideoPipe::spawn(Frames(0), profile).unwrap();
+        let desc = pipe.video_description();
+        assert_eq!((desc.width, desc.height, desc.fps), (4, 2, 1));
+
+        let mut bytes = Vec::new();
+        std::fs::File::open(pipe.path())
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+
+        let expected: Vec<u8> = (0..3).flat_map(|i| [i; 12]).collect();
+        assert_eq!(bytes, expected);
+    }
+}
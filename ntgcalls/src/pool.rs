@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    callbacks::DisconnectCallback,
+    enums::StreamType,
+    errors::{NTgCallError, NTgCallResult},
+    filters::{Volume, Volumes},
+    structures::{ChatId, GroupCall, MediaDescription, MediaState},
+    utils::IntoCString,
+    NTgCall,
+};
+
+/// NTgCallPool owns multiple [`NTgCall`] instances and shards the chats across them.
+///
+/// New chats are assigned to the least loaded instance when calling [`NTgCallPool::get_params`],
+/// the load of an instance is the number of its active calls and its CPU usage is used to break ties.
+/// After that all the per-chat methods are forwarded to the instance that owns the chat.
+///
+/// A chat is released when it's stopped or disconnected, a stream end doesn't release it since
+/// the call stays connected and can be given a new stream with [`NTgCallPool::change_stream`].
+/// The pool registers its own disconnect callback on every instance, so register disconnect
+/// callbacks with [`NTgCallPool::on_disconnect`] instead of on the instances directly.
+///
+/// The volume of an assigned chat is the one of its instance, see [`NTgCall::volume`]. The pool
+/// only keeps the volumes of the chats that are not assigned yet, and hands them to the instance
+/// the chat is assigned to, so the sources of a chat can be set up before calling [`NTgCallPool::get_params`].
+pub struct NTgCallPool {
+    instances: Vec<NTgCall>,
+    chats: Arc<Mutex<HashMap<ChatId, usize>>>,
+    disconnect: Arc<Mutex<Option<DisconnectCallback>>>,
+    /// Volumes of the chats not assigned to any instance.
+    pending: Volumes,
+}
+
+/// Initialization and instance management
+impl NTgCallPool {
+    /// Create a new pool with `size` [`NTgCall`] instances.
+    ///
+    /// ## Panics
+    /// If `size` is zero.
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        assert_ne!(size, 0, "NTgCallPool needs at least one instance");
+
+        Self::from_instances((0..size).map(|_| NTgCall::new()).collect())
+    }
+
+    /// Create a new pool from already initialized [`NTgCall`] instances.
+    ///
+    /// ## Panics
+    /// If `instances` is empty.
+    #[must_use]
+    pub fn from_instances(instances: Vec<NTgCall>) -> Self {
+        assert!(
+            !instances.is_empty(),
+            "NTgCallPool needs at least one instance"
+        );
+
+        let chats = Arc::<Mutex<HashMap<ChatId, usize>>>::default();
+        let disconnect = Arc::<Mutex<Option<DisconnectCallback>>>::default();
+
+        for (index, call) in instances.iter().enumerate() {
+            let chats = chats.clone();
+            let disconnect = disconnect.clone();
+
+            // this only fails if the instance is already destroyed, and then it has no calls
+            let _ = call.on_disconnect(move |chat_id| {
+                {
+                    let mut chats = chats.lock().unwrap_or_else(|e| e.into_inner());
+                    if chats.get(&chat_id) == Some(&index) {
+                        chats.remove(&chat_id);
+                    }
+                }
+
+                let callback = disconnect.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                if let Some(callback) = callback {
+                    callback(chat_id);
+                }
+            });
+        }
+
+        Self {
+            instances,
+            chats,
+            disconnect,
+            pending: Volumes::default(),
+        }
+    }
+
+    /// All the instances owned by the pool.
+    pub fn instances(&self) -> &[NTgCall] {
+        &self.instances
+    }
+
+    /// The instance that the given chat is assigned to, if any.
+    pub fn instance_for(&self, chat_id: ChatId) -> Option<&NTgCall> {
+        self.chats()
+            .get(&chat_id)
+            .map(|&index| &self.instances[index])
+    }
+
+    fn chats(&self) -> MutexGuard<'_, HashMap<ChatId, usize>> {
+        self.chats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn assigned(&self, chat_id: ChatId) -> NTgCallResult<&NTgCall> {
+        self.instance_for(chat_id)
+            .ok_or(NTgCallError::ConnectionNotFound)
+    }
+
+    /// The number of calls and the CPU usage of every instance.
+    /// Instances that fail to report their load are considered fully loaded.
+    fn loads(&self) -> Vec<(usize, f64)> {
+        self.instances
+            .iter()
+            .map(|call| {
+                let calls = call.count_calls().map_or(usize::MAX, |c| c as usize);
+                let cpu = call.cpu_usage().unwrap_or(f64::MAX);
+                (calls, cpu)
+            })
+            .collect()
+    }
+
+    /// Index of the instance with the least number of calls, using the CPU usage to break ties.
+    ///
+    /// The chats assigned by the pool count as calls too, so the chats being connected
+    /// concurrently are spread across the instances.
+    fn least_loaded(loads: &[(usize, f64)], chats: &HashMap<ChatId, usize>) -> usize {
+        let mut assigned = vec![0; loads.len()];
+        for &index in chats.values() {
+            assigned[index] += 1;
+        }
+
+        loads
+            .iter()
+            .zip(assigned)
+            .map(|(&(calls, cpu), assigned)| (calls.max(assigned), cpu))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(index, _)| index)
+            .expect("pool always has at least one instance")
+    }
+}
+
+/// Forwarded methods, see [`NTgCall`] for the details of each method.
+impl NTgCallPool {
+    /// Assign the chat to the least loaded instance and get the connection params from it.
+    ///
+    /// If the chat is already assigned, the call is forwarded to the same instance.
+    /// See [`NTgCall::get_params`].
+    pub fn get_params(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<String> {
+        // query the instances before taking the lock, so it's never held across NTgCalls calls
+        let loads = self.loads();
+
+        let (index, assigned) = {
+            let mut chats = self.chats();
+
+            match chats.get(&chat_id) {
+                Some(&index) => (index, false),
+                None => {
+                    // reserve the instance, so concurrent assignments see the new load
+                    let index = Self::least_loaded(&loads, &chats);
+                    chats.insert(chat_id, index);
+                    self.pending
+                        .move_to(chat_id, &self.instances[index].inner.volumes);
+                    (index, true)
+                }
+            }
+        };
+
+        let result = self.instances[index].get_params(chat_id, desc);
+
+        if assigned && result.is_err() {
+            let mut chats = self.chats();
+            if chats.get(&chat_id) == Some(&index) {
+                chats.remove(&chat_id);
+                self.instances[index]
+                    .inner
+                    .volumes
+                    .move_to(chat_id, &self.pending);
+            }
+        }
+
+        result
+    }
+
+    /// See [`NTgCall::connect`].
+    pub fn connect<S: IntoCString>(&self, chat_id: ChatId, params: S) -> NTgCallResult<()> {
+        self.assigned(chat_id)?.connect(chat_id, params)
+    }
+
+    /// Stop the call and release the chat from its instance. See [`NTgCall::stop`].
+    pub fn stop(&self, chat_id: ChatId) -> NTgCallResult<()> {
+        let result = self.assigned(chat_id)?.stop(chat_id);
+
+        if matches!(result, Ok(()) | Err(NTgCallError::ConnectionNotFound)) {
+            self.chats().remove(&chat_id);
+        }
+
+        result
+    }
+
+    /// See [`NTgCall::change_stream`].
+    pub fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<()> {
+        self.assigned(chat_id)?.change_stream(chat_id, desc)
+    }
+
+    /// See [`NTgCall::mute`].
+    pub fn mute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.assigned(chat_id)?.mute(chat_id)
+    }
+
+    /// See [`NTgCall::pause`].
+    pub fn pause(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.assigned(chat_id)?.pause(chat_id)
+    }
+
+    /// See [`NTgCall::resume`].
+    pub fn resume(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.assigned(chat_id)?.resume(chat_id)
+    }
+
+    /// See [`NTgCall::played_time`].
+    pub fn played_time(&self, chat_id: ChatId) -> NTgCallResult<i64> {
+        self.assigned(chat_id)?.played_time(chat_id)
+    }
+
+    /// See [`NTgCall::unmute`].
+    pub fn unmute(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        self.assigned(chat_id)?.unmute(chat_id)
+    }
+
+    /// The volume of a chat, see [`NTgCall::volume`].
+    ///
+    /// The volume of an assigned chat is the one of its instance, the volume of a chat that is
+    /// not assigned yet is handed to the instance it gets assigned to.
+    pub fn volume(&self, chat_id: ChatId) -> Volume {
+        // hold the lock so the chat can't be assigned while its volume is created
+        let chats = self.chats();
+
+        match chats.get(&chat_id) {
+            Some(&index) => self.instances[index].inner.volumes.get(chat_id),
+            None => self.pending.get(chat_id),
+        }
+    }
+
+    /// Change the volume of a chat, see [`NTgCall::set_volume`].
+    pub fn set_volume(&self, chat_id: ChatId, volume: f32) {
+        self.volume(chat_id).set(volume);
+    }
+
+    /// See [`NTgCall::get_state`].
+    pub fn get_state(&self, chat_id: ChatId) -> NTgCallResult<MediaState> {
+        self.assigned(chat_id)?.get_state(chat_id)
+    }
+
+    /// Total number of active group calls of all the instances. See [`NTgCall::count_calls`].
+    pub fn count_calls(&self) -> NTgCallResult<i32> {
+        self.instances.iter().map(NTgCall::count_calls).sum()
+    }
+
+    /// Active group calls of all the instances. See [`NTgCall::calls`].
+    pub fn calls(&self) -> NTgCallResult<Vec<GroupCall>> {
+        let mut calls = Vec::new();
+
+        for call in &self.instances {
+            calls.extend(call.calls()?);
+        }
+
+        Ok(calls)
+    }
+
+    /// Register the callback on all the instances. See [`NTgCall::on_stream_end`].
+    pub fn on_stream_end<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, StreamType) + Send + Sync + 'static,
+    {
+        let callback = Arc::new(callback);
+
+        for call in &self.instances {
+            let callback = callback.clone();
+            call.on_stream_end(move |chat_id, stream_type| callback(chat_id, stream_type))?;
+        }
+
+        Ok(())
+    }
+
+    /// Register the callback on all the instances. See [`NTgCall::on_upgrade`].
+    pub fn on_upgrade<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId, MediaState) + Send + Sync + 'static,
+    {
+        let callback = Arc::new(callback);
+
+        for call in &self.instances {
+            let callback = callback.clone();
+            call.on_upgrade(move |chat_id, state| callback(chat_id, state))?;
+        }
+
+        Ok(())
+    }
+
+    /// Register the callback for all the instances, it's called after the chat is released
+    /// from its instance. See [`NTgCall::on_disconnect`].
+    pub fn on_disconnect<F>(&self, callback: F) -> NTgCallResult<()>
+    where
+        F: Fn(ChatId) + Send + Sync + 'static,
+    {
+        *self.disconnect.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(callback));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn least_loaded() {
+        let chats = HashMap::from([(ChatId::from_bot_api(1), 0), (ChatId::from_bot_api(2), 0)]);
+
+        // the least calls, then the least CPU usage
+        assert_eq!(
+            NTgCallPool::least_loaded(&[(2, 1.0), (1, 9.0)], &HashMap::new()),
+            1
+        );
+        assert_eq!(
+            NTgCallPool::least_loaded(&[(1, 5.0), (1, 2.0)], &HashMap::new()),
+            1
+        );
+
+        // the chats still being connected count too
+        assert_eq!(NTgCallPool::least_loaded(&[(0, 0.0), (1, 9.0)], &chats), 1);
+    }
+}
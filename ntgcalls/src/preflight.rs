@@ -0,0 +1,358 @@
+//! Checking the sources of a stream before calling NTgCalls.
+//!
+//! NTgCalls reports a missing file, a missing FFmpeg or a broken shell command only once the
+//! stream starts, with a bare [`NTgCallError`](crate::errors::NTgCallError).
+//! [`preflight`] finds these problems beforehand and reports all of them at once.
+
+use std::{
+    ffi::CStr,
+    fs::{self, File},
+    io,
+    path::PathBuf,
+};
+
+use crate::{
+    enums::{InputMode, StreamType},
+    errors::{PreflightError, PreflightProblem},
+    structures::MediaDescription,
+    utils,
+};
+
+/// This function allows checking the sources of a [`MediaDescription`] before streaming it.
+///
+/// The checks depend on the input mode of each source:
+/// - [`InputMode::File`]: the file exists and can be read, and the size of a regular file
+///   is a whole number of frames of the described format.
+/// - [`InputMode::FFmpeg`]: FFmpeg is found in the `PATH`.
+/// - [`InputMode::Shell`]: the command isn't empty and its quotes are balanced, on Unix
+///   `sh -n` also checks its syntax without running it.
+///
+/// On Unix, named pipes like the ones of [`PcmPipe`](crate::pipe::PcmPipe) are not opened
+/// since reading them would consume the stream.
+///
+/// ## Parameters
+/// - `desc`: Media parameters of the stream.
+///
+/// ## Return
+/// A [`PreflightError`] with all the problems found.
+pub fn preflight(desc: &MediaDescription) -> Result<(), PreflightError> {
+    let mut problems = Vec::new();
+
+    if let Some(audio) = &desc.audio {
+        let frame_size =
+            u64::from(audio.bits_per_sample).div_ceil(8) * u64::from(audio.channel_count);
+
+        check(
+            StreamType::Audio,
+            audio.input_mode,
+            audio.input(),
+            frame_size,
+            &mut problems,
+        );
+    }
+
+    if let Some(video) = &desc.video {
+        // YUV420p, a full resolution luma plane and two quarter resolution chroma planes
+        let frame_size = u64::from(video.width) * u64::from(video.height) * 3 / 2;
+
+        check(
+            StreamType::Video,
+            video.input_mode,
+            video.input(),
+            frame_size,
+            &mut problems,
+        );
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(PreflightError { problems })
+    }
+}
+
+fn check(
+    stream: StreamType,
+    mode: InputMode,
+    input: &CStr,
+    frame_size: u64,
+    problems: &mut Vec<PreflightProblem>,
+) {
+    if let Err(error) = InputMode::new(mode.bits()) {
+        problems.push(PreflightProblem::InvalidInputMode { stream, error });
+        return;
+    }
+
+    if mode.contains(InputMode::File) {
+        if let Err(problem) = check_file(stream, to_path(input), frame_size) {
+            problems.push(problem);
+        }
+    } else if mode.contains(InputMode::FFmpeg) {
+        if utils::find_ffmpeg().is_none() {
+            problems.push(PreflightProblem::FFmpegNotFound { stream });
+        }
+    } else if mode.contains(InputMode::Shell) {
+        if let Err(reason) = check_shell(input.to_bytes()) {
+            problems.push(PreflightProblem::InvalidShellCommand { stream, reason });
+        }
+    }
+}
+
+#[cfg(unix)]
+fn to_path(input: &CStr) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    PathBuf::from(OsStr::from_bytes(input.to_bytes()))
+}
+
+#[cfg(not(unix))]
+fn to_path(input: &CStr) -> PathBuf {
+    PathBuf::from(&*input.to_string_lossy())
+}
+
+fn check_file(stream: StreamType, path: PathBuf, frame_size: u64) -> Result<(), PreflightProblem> {
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(PreflightProblem::FileNotFound { stream, path })
+        }
+        Err(error) => {
+            return Err(PreflightProblem::FileNotReadable {
+                stream,
+                path,
+                error,
+            })
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if metadata.file_type().is_fifo() {
+            return Ok(());
+        }
+    }
+
+    if !metadata.is_file() {
+        return Err(PreflightProblem::NotAFile { stream, path });
+    }
+
+    if let Err(error) = File::open(&path) {
+        return Err(PreflightProblem::FileNotReadable {
+            stream,
+            path,
+            error,
+        });
+    }
+
+    let size = metadata.len();
+    if frame_size != 0 && size % frame_size != 0 {
+        return Err(PreflightProblem::MisalignedFile {
+            stream,
+            path,
+            size,
+            frame_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Find the syntax errors that make the shell reject a command before running it.
+fn check_shell(command: &[u8]) -> Result<(), String> {
+    if command.iter().all(u8::is_ascii_whitespace) {
+        return Err(String::from("The command is empty"));
+    }
+
+    let mut quote = None;
+    let mut escaped = false;
+
+    for &c in command {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            // a backslash is a literal character between single quotes
+            (None | Some(b'"'), b'\\') => escaped = true,
+            (None, b'\'' | b'"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+
+    if escaped {
+        return Err(String::from("The command ends with an escape character"));
+    }
+
+    if quote.is_some() {
+        return Err(String::from("The command has an unterminated quote"));
+    }
+
+    check_syntax(command)
+}
+
+/// Parse the command with `sh -n`, which reads the commands without running them.
+#[cfg(unix)]
+fn check_syntax(command: &[u8]) -> Result<(), String> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, process::Command};
+
+    let output = match Command::new("sh")
+        .arg("-n")
+        .arg("-c")
+        .arg(OsStr::from_bytes(command))
+        .output()
+    {
+        Ok(output) => output,
+        // without a shell the command can't be checked, NTgCalls reports it when streaming
+        Err(_) => return Ok(()),
+    };
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) => Err(format!("The command has a syntax error: {line}")),
+        None => Err(String::from("The command has a syntax error")),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_syntax(_command: &[u8]) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        structures::{AudioDescription, VideoDescription},
+        utils::test::TempPath,
+    };
+
+    fn temp_file(size: usize) -> TempPath {
+        let path = TempPath::new("preflight", "raw");
+        fs::write(&*path, vec![0; size]).unwrap();
+        path
+    }
+
+    fn audio(mode: InputMode, input: &str) -> AudioDescription {
+        AudioDescription::new(mode, input, 48000, 16, 2).unwrap()
+    }
+
+    #[test]
+    fn files() {
+        let aligned = temp_file(4 * 100);
+        let misaligned = temp_file(4 * 100 + 1);
+
+        let desc = MediaDescription {
+            audio: Some(audio(InputMode::File, aligned.to_str().unwrap())),
+            video: None,
+        };
+        assert!(preflight(&desc).is_ok());
+
+        let desc = MediaDescription {
+            audio: Some(audio(InputMode::File, misaligned.to_str().unwrap())),
+            video: Some(
+                VideoDescription::new(InputMode::File, "/nonexistent/video.yuv", 1280, 720, 30)
+                    .unwrap(),
+            ),
+        };
+        let problems = preflight(&desc).unwrap_err().problems;
+
+        // all the problems are reported at once
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            problems[0],
+            PreflightProblem::MisalignedFile {
+                stream: StreamType::Audio,
+                size: 401,
+                frame_size: 4,
+                ..
+            }
+        ));
+        assert!(matches!(
+            problems[1],
+            PreflightProblem::FileNotFound {
+                stream: StreamType::Video,
+                ..
+            }
+        ));
+
+        let desc = MediaDescription {
+            audio: Some(audio(
+                InputMode::File,
+                std::env::temp_dir().to_str().unwrap(),
+            )),
+            video: None,
+        };
+        assert!(matches!(
+            preflight(&desc).unwrap_err().problems[..],
+            [PreflightProblem::NotAFile { .. }]
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_are_not_read() {
+        use crate::pipe::{test::Constant, PcmFormat, PcmPipe};
+
+        let source = Constant {
+            sample: 0,
+            remaining: None,
+        };
+        let pipe = PcmPipe::spawn(source, PcmFormat::new(48000, 2)).unwrap();
+
+        let desc = MediaDescription {
+            audio: Some(pipe.audio_description()),
+            video: None,
+        };
+        assert!(preflight(&desc).is_ok());
+    }
+
+    #[test]
+    fn input_modes() {
+        let desc = MediaDescription {
+            audio: Some(audio(InputMode::File | InputMode::Shell, "a.raw")),
+            video: None,
+        };
+        assert!(matches!(
+            preflight(&desc).unwrap_err().problems[..],
+            [PreflightProblem::InvalidInputMode { .. }]
+        ));
+
+        let desc = MediaDescription {
+            audio: Some(audio(InputMode::FFmpeg, "https://example.com/a.mp3")),
+            video: None,
+        };
+        assert_eq!(preflight(&desc).is_ok(), utils::find_ffmpeg().is_some());
+    }
+
+    #[test]
+    fn shell_commands() {
+        assert!(check_shell(b"ffmpeg -i 'my file.mp3' -f s16le -").is_ok());
+        assert!(check_shell(br#"echo "a \" b" 'c\'"#).is_ok());
+
+        assert!(check_shell(b"  ").is_err());
+        assert!(check_shell(b"echo 'a").is_err());
+        assert!(check_shell(br#"echo "a \""#).is_err());
+        assert!(check_shell(b"echo a \\").is_err());
+
+        #[cfg(unix)]
+        {
+            assert!(check_shell(b"ffmpeg -i a.mp3 -f s16le - | cat").is_ok());
+            assert!(check_shell(b"if true; then echo a").is_err());
+            assert!(check_shell(b"echo a |").is_err());
+        }
+
+        let desc = MediaDescription {
+            audio: Some(audio(InputMode::Shell, "ffmpeg -i \"a.mp3")),
+            video: None,
+        };
+        let error = preflight(&desc).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Preflight]: 1 problem(s) found\n- audio: [InvalidShellCommand]: The command has an unterminated quote"
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Named stream quality profiles.
+//!
+//! A [`StreamProfile`] stores the quality parameters of a stream without its input source,
+//! pairing it with an input creates the [`MediaDescription`] used by [`NTgCall`](crate::NTgCall).
+//!
+//! Custom profiles can be loaded from JSON (`json` feature) or TOML (`toml` feature) files,
+//! with [`Profiles::load`], where every profile is a table keyed by its name:
+//! ```toml
+//! [podcast]
+//! audio = { sample_rate = 48000, bits_per_sample = 16, channel_count = 1 }
+//!
+//! [screen]
+//! audio = { sample_rate = 48000, bits_per_sample = 16, channel_count = 2 }
+//! video = { width = 1920, height = 1080, fps = 15 }
+//! ```
+
+use std::collections::{btree_map, BTreeMap};
+
+use crate::{
+    enums::InputMode,
+    errors::{IntoCStringError, ProfileError},
+    structures::{AudioDescription, MediaDescription, VideoDescription},
+    utils::IntoCString,
+};
+
+/// Audio parameters of a [`StreamProfile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioProfile {
+    /// Audio sample rate (0-96000, max allowed by PCM16L).
+    pub sample_rate: u32,
+    /// Audio bits per sample (8 or 16).
+    pub bits_per_sample: u8,
+    /// Audio channel count (1-2, max allowed by PCM16L).
+    pub channel_count: u8,
+}
+
+impl AudioProfile {
+    /// Check that the parameters are in the range supported by NTgCalls.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        if self.sample_rate == 0 || self.sample_rate > 96000 {
+            return Err(ProfileError::Invalid(
+                "sample_rate must be between 1 and 96000",
+            ));
+        }
+
+        if !matches!(self.bits_per_sample, 8 | 16) {
+            return Err(ProfileError::Invalid("bits_per_sample must be 8 or 16"));
+        }
+
+        if !matches!(self.channel_count, 1 | 2) {
+            return Err(ProfileError::Invalid("channel_count must be 1 or 2"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Video parameters of a [`StreamProfile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VideoProfile {
+    /// Video width in pixels.
+    pub width: u16,
+    /// Video height in pixels.
+    pub height: u16,
+    /// Frames per second (FPS) for video playback.
+    pub fps: u8,
+}
+
+impl VideoProfile {
+    /// Check that the parameters are in the range supported by NTgCalls.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        if self.width == 0 || self.height == 0 || self.fps == 0 {
+            return Err(ProfileError::Invalid(
+                "width, height and fps must be greater than zero",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Quality parameters of a stream, see the [module level documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamProfile {
+    /// Audio parameters, `None` for streams without audio.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub audio: Option<AudioProfile>,
+    /// Video parameters, `None` for streams without video.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub video: Option<VideoProfile>,
+}
+
+const STEREO_48K: AudioProfile = AudioProfile {
+    sample_rate: 48000,
+    bits_per_sample: 16,
+    channel_count: 2,
+};
+
+/// Built-in profiles
+impl StreamProfile {
+    /// Low bandwidth mono audio, good enough for voice.
+    pub const VOICE: Self = Self {
+        audio: Some(AudioProfile {
+            sample_rate: 24000,
+            bits_per_sample: 16,
+            channel_count: 1,
+        }),
+        video: None,
+    };
+
+    /// High quality stereo audio for music.
+    pub const MUSIC: Self = Self {
+        audio: Some(STEREO_48K),
+        video: None,
+    };
+
+    /// 854x480 video at 30 FPS with stereo audio.
+    pub const VIDEO_480P: Self = Self {
+        audio: Some(STEREO_48K),
+        video: Some(VideoProfile {
+            width: 854,
+            height: 480,
+            fps: 30,
+        }),
+    };
+
+    /// 1280x720 video at 30 FPS with stereo audio.
+    pub const VIDEO_720P: Self = Self {
+        audio: Some(STEREO_48K),
+        video: Some(VideoProfile {
+            width: 1280,
+            height: 720,
+            fps: 30,
+        }),
+    };
+
+    /// 1920x1080 video at 30 FPS with stereo audio.
+    pub const VIDEO_1080P: Self = Self {
+        audio: Some(STEREO_48K),
+        video: Some(VideoProfile {
+            width: 1920,
+            height: 1080,
+            fps: 30,
+        }),
+    };
+
+    /// Names and values of all the built-in profiles.
+    pub const BUILTIN: [(&'static str, Self); 5] = [
+        ("voice", Self::VOICE),
+        ("music", Self::MUSIC),
+        ("480p", Self::VIDEO_480P),
+        ("720p", Self::VIDEO_720P),
+        ("1080p", Self::VIDEO_1080P),
+    ];
+}
+
+/// Media descriptions
+impl StreamProfile {
+    /// Create the [`AudioDescription`] of this profile for the given input, if the profile has audio.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] if the input can't be passed to NTgCalls.
+    pub fn audio_description<S: IntoCString>(
+        &self,
+        input_mode: InputMode,
+        input: S,
+    ) -> Result<Option<AudioDescription>, IntoCStringError> {
+        self.audio
+            .map(|audio| {
+                AudioDescription::new(
+                    input_mode,
+                    input,
+                    audio.sample_rate,
+                    audio.bits_per_sample,
+                    audio.channel_count,
+                )
+            })
+            .transpose()
+    }
+
+    /// Create the [`VideoDescription`] of this profile for the given input, if the profile has video.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] if the input can't be passed to NTgCalls.
+    pub fn video_description<S: IntoCString>(
+        &self,
+        input_mode: InputMode,
+        input: S,
+    ) -> Result<Option<VideoDescription>, IntoCStringError> {
+        self.video
+            .map(|video| {
+                VideoDescription::new(input_mode, input, video.width, video.height, video.fps)
+            })
+            .transpose()
+    }
+
+    /// Create the [`MediaDescription`] of this profile using the same input for audio and video.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] if the input can't be passed to NTgCalls.
+    pub fn media_description<S: IntoCString>(
+        &self,
+        input_mode: InputMode,
+        input: S,
+    ) -> Result<MediaDescription, IntoCStringError> {
+        let input = input.try_into_c_string()?;
+
+        Ok(MediaDescription {
+            audio: self.audio_description(input_mode, input.clone())?,
+            video: self.video_description(input_mode, input)?,
+        })
+    }
+
+    /// Check that the profile parameters are in the range supported by NTgCalls.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        if let Some(audio) = self.audio {
+            audio.validate()?;
+        }
+
+        if let Some(video) = self.video {
+            video.validate()?;
+        }
+
+        if self.audio.is_none() && self.video.is_none() {
+            return Err(ProfileError::Invalid("profile needs audio or video"));
+        }
+
+        Ok(())
+    }
+}
+
+/// A collection of named [`StreamProfile`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profiles(BTreeMap<String, StreamProfile>);
+
+impl Profiles {
+    /// Create a collection containing all the [built-in profiles](StreamProfile::BUILTIN).
+    pub fn builtin() -> Self {
+        Self(
+            StreamProfile::BUILTIN
+                .iter()
+                .map(|(name, profile)| (name.to_string(), *profile))
+                .collect(),
+        )
+    }
+
+    /// Load custom profiles from a JSON object.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, ProfileError> {
+        let profiles = serde_json::from_str(json).map_err(ProfileError::Json)?;
+        Self::validated(profiles)
+    }
+
+    /// Load custom profiles from a TOML document.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, ProfileError> {
+        let profiles = toml::from_str(toml).map_err(ProfileError::Toml)?;
+        Self::validated(profiles)
+    }
+
+    /// Load custom profiles from a file, parsed as JSON or TOML depending on its extension.
+    ///
+    /// ## Return
+    /// A [`ProfileError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`ProfileError::UnknownFormat`] if the extension isn't `.json` or `.toml`, or the
+    ///   feature needed to parse it is disabled.
+    /// - [`ProfileError::Io`] if the file couldn't be read.
+    /// - Any error of [`Profiles::from_json`] or [`Profiles::from_toml`].
+    #[cfg(any(feature = "json", feature = "toml"))]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ProfileError> {
+        let path = path.as_ref();
+
+        let parse = match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "json")]
+            Some("json") => Self::from_json,
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml,
+            _ => return Err(ProfileError::UnknownFormat(path.to_owned())),
+        };
+
+        parse(&std::fs::read_to_string(path).map_err(ProfileError::Io)?)
+    }
+
+    #[cfg(any(feature = "json", feature = "toml"))]
+    fn validated(profiles: BTreeMap<String, StreamProfile>) -> Result<Self, ProfileError> {
+        for (name, profile) in &profiles {
+            profile.validate().map_err(|e| ProfileError::Profile {
+                name: name.clone(),
+                source: Box::new(e),
+            })?;
+        }
+
+        Ok(Self(profiles))
+    }
+
+    /// Get a profile by its name.
+    pub fn get(&self, name: &str) -> Option<&StreamProfile> {
+        self.0.get(name)
+    }
+
+    /// Insert a profile, replacing and returning the profile with the same name.
+    pub fn insert<S: Into<String>>(
+        &mut self,
+        name: S,
+        profile: StreamProfile,
+    ) -> Option<StreamProfile> {
+        self.0.insert(name.into(), profile)
+    }
+
+    /// Add all the profiles of `other`, profiles with the same name are replaced.
+    pub fn merge(&mut self, other: Profiles) {
+        self.0.extend(other.0);
+    }
+
+    /// Iterate over the profiles sorted by name.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, StreamProfile> {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Profiles {
+    type Item = (&'a String, &'a StreamProfile);
+    type IntoIter = btree_map::Iter<'a, String, StreamProfile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_profiles_are_valid() {
+        for (name, profile) in &Profiles::builtin() {
+            assert!(profile.validate().is_ok(), "{name} is invalid");
+        }
+
+        let desc = StreamProfile::VIDEO_720P
+            .media_description(InputMode::Shell, "cat video")
+            .unwrap();
+        let video = desc.video.unwrap();
+        assert_eq!((video.width, video.height, video.fps), (1280, 720, 30));
+        assert!(StreamProfile::MUSIC
+            .media_description(InputMode::FFmpeg, "song.mp3")
+            .unwrap()
+            .video
+            .is_none());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn load_toml() {
+        let mut profiles = Profiles::builtin();
+        profiles.merge(
+            Profiles::from_toml(
+                r#"
+                [podcast]
+                audio = { sample_rate = 48000, bits_per_sample = 16, channel_count = 1 }
+
+                [720p]
+                video = { width = 1280, height = 720, fps = 60 }
+                "#,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(
+            profiles
+                .get("podcast")
+                .unwrap()
+                .audio
+                .unwrap()
+                .channel_count,
+            1
+        );
+        assert_eq!(profiles.get("720p").unwrap().video.unwrap().fps, 60);
+        assert!(profiles.get("720p").unwrap().audio.is_none());
+
+        let invalid = Profiles::from_toml(
+            "[bad]\naudio = { sample_rate = 48000, bits_per_sample = 24, channel_count = 2 }",
+        );
+        assert!(matches!(invalid, Err(ProfileError::Profile { name, .. }) if name == "bad"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn load_json() {
+        let profiles = Profiles::from_json(
+            r#"{ "slides": { "video": { "width": 1024, "height": 768, "fps": 5 } } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(profiles.get("slides").unwrap().video.unwrap().width, 1024);
+        assert!(Profiles::from_json("{ \"empty\": {} }").is_err());
+    }
+
+    #[cfg(all(feature = "json", feature = "toml"))]
+    #[test]
+    fn load_file() {
+        let path = crate::utils::temp_path("profiles", "toml");
+        std::fs::write(
+            &path,
+            "[slides]\nvideo = { width = 1024, height = 768, fps = 5 }",
+        )
+        .unwrap();
+        let profiles = Profiles::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            profiles.unwrap().get("slides").unwrap().video.unwrap().fps,
+            5
+        );
+
+        assert!(matches!(
+            Profiles::load("/nonexistent/profiles.json"),
+            Err(ProfileError::Io(_))
+        ));
+        assert!(matches!(
+            Profiles::load("profiles.yaml"),
+            Err(ProfileError::UnknownFormat(_))
+        ));
+    }
+}
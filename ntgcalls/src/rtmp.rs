@@ -0,0 +1,783 @@
+//! Streaming to the chats that only accept RTMP.
+//!
+//! When [`NTgCall::connect`](crate::NTgCall::connect) fails with
+//! [`NTgCallError::RtmpNeeded`](crate::errors::NTgCallError::RtmpNeeded) the chat can only be
+//! streamed to with the RTMP URL and stream key shown to its admins. [`RtmpStreams`] pushes the
+//! same [`MediaDescription`] sources there with a FFmpeg process for each chat, and implements
+//! [`StreamTransport`](crate::transport::StreamTransport) so both kind of chats can be
+//! controlled the same way.
+//!
+//! The sources are read like NTgCalls does: [`InputMode::File`] reads raw PCM or YUV420p
+//! frames from a file, [`InputMode::Shell`] reads them from the standard output of a shell
+//! command and [`InputMode::FFmpeg`] lets FFmpeg decode any input it supports.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::CStr,
+    io::{self, BufRead, BufReader},
+    mem,
+    path::{Path, PathBuf},
+    process::{Child, ChildStderr, Command, Stdio},
+    sync::{Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    enums::{InputMode, StreamStatus},
+    errors::RtmpError,
+    structures::{ChatId, MediaDescription},
+    utils,
+};
+
+/// Number of lines of the FFmpeg standard error kept to report why it failed.
+const STDERR_LINES: usize = 20;
+
+/// Where an RTMP stream is pushed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtmpTarget {
+    /// The server URL, for example `rtmps://dc4-1.rtmp.t.me/s/`.
+    pub url: String,
+    /// The stream key.
+    pub key: String,
+}
+
+impl RtmpTarget {
+    /// Create a new [`RtmpTarget`]
+    pub fn new<U: Into<String>, K: Into<String>>(url: U, key: K) -> Self {
+        Self {
+            url: url.into(),
+            key: key.into(),
+        }
+    }
+
+    /// The full URL FFmpeg pushes the stream to.
+    pub fn endpoint(&self) -> String {
+        format!("{}/{}", self.url.trim_end_matches('/'), self.key)
+    }
+}
+
+/// Pushes streams to RTMP servers, one FFmpeg process for each chat.
+///
+/// FFmpeg can't pause a live stream, so pausing stops the process and resuming starts
+/// a new one that seeks to the played time. Shell sources can't seek and are restarted
+/// from the beginning instead.
+///
+/// The processes are killed when the streams are stopped or when [`RtmpStreams`] is dropped.
+pub struct RtmpStreams {
+    ffmpeg: PathBuf,
+    streams: Mutex<HashMap<ChatId, RtmpStream>>,
+}
+
+/// Initialization
+impl RtmpStreams {
+    /// Create a new [`RtmpStreams`] using the FFmpeg found in `PATH`.
+    ///
+    /// ## Return
+    /// [`RtmpError::FFmpegNotFound`] if FFmpeg is not in `PATH`.
+    pub fn new() -> Result<Self, RtmpError> {
+        utils::find_ffmpeg()
+            .map(Self::with_ffmpeg)
+            .ok_or(RtmpError::FFmpegNotFound)
+    }
+
+    /// Create a new [`RtmpStreams`] using the given FFmpeg executable.
+    pub fn with_ffmpeg<P: Into<PathBuf>>(ffmpeg: P) -> Self {
+        Self {
+            ffmpeg: ffmpeg.into(),
+            streams: Mutex::default(),
+        }
+    }
+
+    fn streams(&self) -> MutexGuard<'_, HashMap<ChatId, RtmpStream>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Stream methods
+impl RtmpStreams {
+    /// This method allows starting the stream of a chat.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of the chat.
+    /// - `target`: The RTMP server and stream key of the chat.
+    /// - `desc`: Media parameters of the stream.
+    ///
+    /// ## Return
+    /// A [`RtmpError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`RtmpError::StreamAlreadyExists`]
+    /// - [`RtmpError::Unsupported`]
+    /// - [`RtmpError::FFmpegNotFound`]
+    /// - [`RtmpError::Io`]
+    pub fn start(
+        &self,
+        chat_id: ChatId,
+        target: RtmpTarget,
+        desc: MediaDescription,
+    ) -> Result<(), RtmpError> {
+        let mut streams = self.streams();
+
+        if streams.contains_key(&chat_id) {
+            return Err(RtmpError::StreamAlreadyExists);
+        }
+
+        let process = Process::spawn(&self.ffmpeg, arguments(&target, &desc, Duration::ZERO)?)?;
+
+        streams.insert(
+            chat_id,
+            RtmpStream {
+                target,
+                desc,
+                state: State::Running(process),
+                played: Duration::ZERO,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// This method allows replacing the source of the stream, the played time starts again from zero.
+    ///
+    /// ## Parameters
+    /// - `chat_id`: Unique identifier of the chat.
+    /// - `desc`: Media parameters of the stream.
+    ///
+    /// ## Return
+    /// A [`RtmpError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`RtmpError::StreamNotFound`]
+    /// - [`RtmpError::Unsupported`]
+    /// - [`RtmpError::FFmpegNotFound`]
+    /// - [`RtmpError::Io`]
+    pub fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> Result<(), RtmpError> {
+        let (arguments, current) = {
+            let mut streams = self.streams();
+            let stream = streams.get_mut(&chat_id).ok_or(RtmpError::StreamNotFound)?;
+
+            // check the new description before stopping the current stream
+            let arguments = arguments(&stream.target, &desc, Duration::ZERO)?;
+
+            (arguments, mem::replace(&mut stream.state, State::Ended))
+        };
+
+        // kill the current process before pushing the new stream, killing it can take a
+        // while so it's done without blocking the other streams
+        drop(current);
+        let process = Process::spawn(&self.ffmpeg, arguments)?;
+
+        let mut streams = self.streams();
+        // the stream was stopped in the meantime, `process` is killed when dropped
+        let stream = streams.get_mut(&chat_id).ok_or(RtmpError::StreamNotFound)?;
+
+        let replaced = mem::replace(&mut stream.state, State::Running(process));
+        stream.desc = desc;
+        stream.played = Duration::ZERO;
+        drop(streams);
+
+        // the process of a concurrent change, if any
+        drop(replaced);
+
+        Ok(())
+    }
+
+    /// This method allows pausing the stream.
+    ///
+    /// ## Return
+    /// Whether the stream was playing, or a [`RtmpError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`RtmpError::StreamNotFound`]
+    /// - [`RtmpError::Exited`]
+    pub fn pause(&self, chat_id: ChatId) -> Result<bool, RtmpError> {
+        let mut streams = self.streams();
+        let stream = streams.get_mut(&chat_id).ok_or(RtmpError::StreamNotFound)?;
+
+        if stream.poll()? != StreamStatus::Playing {
+            return Ok(false);
+        }
+
+        stream.played += stream.running_time();
+        let process = mem::replace(&mut stream.state, State::Paused);
+        drop(streams);
+
+        // killing the process can take a while, don't block the other streams
+        drop(process);
+
+        Ok(true)
+    }
+
+    /// This method allows resuming the stream.
+    ///
+    /// ## Return
+    /// Whether the stream was paused, or a [`RtmpError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`RtmpError::StreamNotFound`]
+    /// - [`RtmpError::Exited`]
+    /// - [`RtmpError::FFmpegNotFound`]
+    /// - [`RtmpError::Io`]
+    pub fn resume(&self, chat_id: ChatId) -> Result<bool, RtmpError> {
+        let mut streams = self.streams();
+        let stream = streams.get_mut(&chat_id).ok_or(RtmpError::StreamNotFound)?;
+
+        if stream.poll()? != StreamStatus::Paused {
+            return Ok(false);
+        }
+
+        let arguments = arguments(&stream.target, &stream.desc, stream.played)?;
+        stream.state = State::Running(Process::spawn(&self.ffmpeg, arguments)?);
+
+        Ok(true)
+    }
+
+    /// This method allows stopping the stream and killing its FFmpeg process.
+    ///
+    /// ## Return
+    /// [`RtmpError::StreamNotFound`] if there is no stream for the chat.
+    pub fn stop(&self, chat_id: ChatId) -> Result<(), RtmpError> {
+        let stream = self
+            .streams()
+            .remove(&chat_id)
+            .ok_or(RtmpError::StreamNotFound)?;
+
+        // killing the process can take a while, don't block the other streams
+        drop(stream);
+
+        Ok(())
+    }
+
+    /// This method allows getting the played time of the stream in seconds.
+    ///
+    /// ## Return
+    /// [`RtmpError::StreamNotFound`] if there is no stream for the chat.
+    pub fn played_time(&self, chat_id: ChatId) -> Result<i64, RtmpError> {
+        let mut streams = self.streams();
+        let stream = streams.get_mut(&chat_id).ok_or(RtmpError::StreamNotFound)?;
+
+        // refresh the state, the played time doesn't depend on whether FFmpeg failed
+        let _ = stream.poll();
+
+        let played = stream.played + stream.running_time();
+        Ok(played.as_secs() as i64)
+    }
+
+    /// This method allows getting the status of the stream.
+    ///
+    /// The stream is [`StreamStatus::Idling`] once FFmpeg reached the end of the sources.
+    ///
+    /// ## Return
+    /// A [`RtmpError`] in case of failure.
+    ///
+    /// ### Possible error values
+    /// - [`RtmpError::StreamNotFound`]
+    /// - [`RtmpError::Exited`]: FFmpeg failed, for example because the server refused the stream.
+    pub fn stream_status(&self, chat_id: ChatId) -> Result<StreamStatus, RtmpError> {
+        self.streams()
+            .get_mut(&chat_id)
+            .ok_or(RtmpError::StreamNotFound)?
+            .poll()
+    }
+}
+
+struct RtmpStream {
+    target: RtmpTarget,
+    desc: MediaDescription,
+    state: State,
+    /// Played time before the current process was started.
+    played: Duration,
+}
+
+enum State {
+    Running(Process),
+    Paused,
+    /// FFmpeg reached the end of the sources.
+    Ended,
+    Failed {
+        code: Option<i32>,
+        stderr: String,
+    },
+}
+
+impl RtmpStream {
+    /// Update the state if FFmpeg exited and get the status of the stream.
+    fn poll(&mut self) -> Result<StreamStatus, RtmpError> {
+        if let State::Running(process) = &mut self.state {
+            if let Some(status) = process.ffmpeg.try_wait()? {
+                self.played += process.started.elapsed();
+
+                self.state = match status.success() {
+                    true => State::Ended,
+                    false => State::Failed {
+                        code: status.code(),
+                        stderr: process.stderr(),
+                    },
+                };
+            }
+        }
+
+        match &self.state {
+            State::Running(_) => Ok(StreamStatus::Playing),
+            State::Paused => Ok(StreamStatus::Paused),
+            State::Ended => Ok(StreamStatus::Idling),
+            State::Failed { code, stderr } => Err(RtmpError::Exited {
+                code: *code,
+                stderr: stderr.clone(),
+            }),
+        }
+    }
+
+    /// How long the current process has been running.
+    fn running_time(&self) -> Duration {
+        match &self.state {
+            State::Running(process) => process.started.elapsed(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// The arguments of FFmpeg and the shell command feeding its standard input, if any.
+#[derive(Debug)]
+struct Arguments {
+    ffmpeg: Vec<String>,
+    shell: Option<String>,
+}
+
+fn arguments(
+    target: &RtmpTarget,
+    desc: &MediaDescription,
+    offset: Duration,
+) -> Result<Arguments, RtmpError> {
+    let mut arguments = Arguments {
+        ffmpeg: ["-hide_banner", "-loglevel", "error", "-nostats"]
+            .map(String::from)
+            .to_vec(),
+        shell: None,
+    };
+    let mut outputs = Vec::new();
+    let mut index = 0;
+
+    if let Some(audio) = &desc.audio {
+        let format = match audio.bits_per_sample {
+            8 => "u8",
+            16 => "s16le",
+            _ => {
+                return Err(RtmpError::Unsupported(
+                    "audio must have 8 or 16 bits per sample",
+                ))
+            }
+        };
+
+        let raw = vec![
+            String::from("-f"),
+            String::from(format),
+            String::from("-ar"),
+            audio.sample_rate.to_string(),
+            String::from("-ac"),
+            audio.channel_count.to_string(),
+        ];
+
+        arguments.input(audio.input_mode, audio.input(), raw, offset)?;
+        outputs.extend([
+            String::from("-map"),
+            format!("{index}:a:0"),
+            String::from("-c:a"),
+            String::from("aac"),
+            String::from("-b:a"),
+            String::from("128k"),
+            String::from("-ar"),
+            String::from("48000"),
+        ]);
+        index += 1;
+    }
+
+    if let Some(video) = &desc.video {
+        let raw = vec![
+            String::from("-f"),
+            String::from("rawvideo"),
+            String::from("-pix_fmt"),
+            String::from("yuv420p"),
+            String::from("-s"),
+            format!("{}x{}", video.width, video.height),
+            String::from("-r"),
+            video.fps.to_string(),
+        ];
+
+        arguments.input(video.input_mode, video.input(), raw, offset)?;
+        outputs.extend([
+            String::from("-map"),
+            format!("{index}:v:0"),
+            String::from("-c:v"),
+            String::from("libx264"),
+            String::from("-preset"),
+            String::from("veryfast"),
+            String::from("-tune"),
+            String::from("zerolatency"),
+            String::from("-pix_fmt"),
+            String::from("yuv420p"),
+            String::from("-g"),
+            (u16::from(video.fps) * 2).to_string(),
+        ]);
+        index += 1;
+    }
+
+    if index == 0 {
+        return Err(RtmpError::Unsupported(
+            "the media description has no audio nor video",
+        ));
+    }
+
+    arguments.ffmpeg.extend(outputs);
+    arguments
+        .ffmpeg
+        .extend([String::from("-f"), String::from("flv"), target.endpoint()]);
+
+    Ok(arguments)
+}
+
+impl Arguments {
+    /// Add an input, `raw` are the options describing the format of the raw sources.
+    fn input(
+        &mut self,
+        mode: InputMode,
+        input: &CStr,
+        raw: Vec<String>,
+        offset: Duration,
+    ) -> Result<(), RtmpError> {
+        let input = input
+            .to_str()
+            .map_err(|_| RtmpError::Unsupported("the input must be valid UTF-8"))?;
+
+        if mode.contains(InputMode::NoLatency) {
+            return Err(RtmpError::Unsupported(
+                "the NoLatency input mode can't be streamed over RTMP",
+            ));
+        }
+
+        self.ffmpeg.push(String::from("-re"));
+
+        if mode != InputMode::Shell && !offset.is_zero() {
+            self.ffmpeg.push(String::from("-ss"));
+            self.ffmpeg.push(format!("{:.3}", offset.as_secs_f64()));
+        }
+
+        match mode {
+            InputMode::File => {
+                self.ffmpeg.extend(raw);
+                self.ffmpeg
+                    .extend([String::from("-i"), String::from(input)]);
+            }
+            InputMode::FFmpeg => {
+                self.ffmpeg
+                    .extend([String::from("-i"), String::from(input)]);
+            }
+            InputMode::Shell => {
+                if self.shell.is_some() {
+                    return Err(RtmpError::Unsupported(
+                        "only one shell source can be streamed over RTMP",
+                    ));
+                }
+
+                self.shell = Some(String::from(input));
+                self.ffmpeg.extend(raw);
+                self.ffmpeg
+                    .extend([String::from("-i"), String::from("pipe:0")]);
+            }
+            _ => {
+                return Err(RtmpError::Unsupported(
+                    "the input mode must be one of File, Shell or FFmpeg",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A running FFmpeg process, killed when dropped.
+struct Process {
+    ffmpeg: Child,
+    shell: Option<Child>,
+    started: Instant,
+    stderr: Option<JoinHandle<VecDeque<String>>>,
+}
+
+impl Process {
+    fn spawn(ffmpeg: &Path, arguments: Arguments) -> Result<Self, RtmpError> {
+        let mut shell = match arguments.shell {
+            Some(command) => Some(
+                shell_command(&command)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()?,
+            ),
+            None => None,
+        };
+
+        let stdin = match shell.as_mut().and_then(|shell| shell.stdout.take()) {
+            Some(stdout) => Stdio::from(stdout),
+            None => Stdio::null(),
+        };
+
+        let child = Command::new(ffmpeg)
+            .args(&arguments.ffmpeg)
+            .stdin(stdin)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                if let Some(mut shell) = shell {
+                    let _ = shell.kill();
+                    let _ = shell.wait();
+                }
+
+                return Err(match e.kind() {
+                    io::ErrorKind::NotFound => RtmpError::FFmpegNotFound,
+                    _ => RtmpError::Io(e),
+                });
+            }
+        };
+
+        let stderr = child.stderr.take().map(|stderr| {
+            thread::Builder::new()
+                .name(String::from("ntgcalls-rtmp-stderr"))
+                .spawn(move || tail(stderr))
+        });
+
+        Ok(Self {
+            ffmpeg: child,
+            shell,
+            started: Instant::now(),
+            stderr: stderr.transpose()?,
+        })
+    }
+
+    /// The last lines written by FFmpeg, only complete once it exited.
+    fn stderr(&mut self) -> String {
+        self.stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .map(|lines| Vec::from(lines).join("\n"))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        for child in [Some(&mut self.ffmpeg), self.shell.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// Read the standard error until it's closed, keeping only the last lines.
+fn tail(stderr: ChildStderr) -> VecDeque<String> {
+    let mut lines = VecDeque::with_capacity(STDERR_LINES);
+
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        if lines.len() == STDERR_LINES {
+            lines.pop_front();
+        }
+
+        lines.push_back(line);
+    }
+
+    lines
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+    use crate::{
+        structures::{AudioDescription, VideoDescription},
+        utils::test::TempPath,
+    };
+
+    const CHAT: ChatId = ChatId::from_bot_api(-1001);
+
+    /// Write an executable script standing in for FFmpeg.
+    fn fake_ffmpeg(body: &str) -> TempPath {
+        let path = TempPath::new("rtmp", "sh");
+
+        fs::write(&*path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(&*path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        path
+    }
+
+    fn wait_for<T>(mut f: impl FnMut() -> Option<T>) -> T {
+        for _ in 0..200 {
+            if let Some(value) = f() {
+                return value;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("timed out");
+    }
+
+    fn lines(path: &Path, count: usize) -> Vec<String> {
+        wait_for(|| {
+            let content = fs::read_to_string(path).ok()?;
+            let lines: Vec<_> = content.lines().map(String::from).collect();
+            (lines.len() >= count).then_some(lines)
+        })
+    }
+
+    fn audio(mode: InputMode, input: &str) -> MediaDescription {
+        MediaDescription {
+            audio: Some(AudioDescription::new(mode, input, 48000, 16, 2).unwrap()),
+            video: None,
+        }
+    }
+
+    #[test]
+    fn arguments_layout() {
+        let target = RtmpTarget::new("rtmp://localhost/live/", "key");
+        let desc = MediaDescription {
+            audio: Some(AudioDescription::new(InputMode::Shell, "tts", 24000, 16, 1).unwrap()),
+            video: Some(
+                VideoDescription::new(InputMode::File, "video.yuv", 1280, 720, 30).unwrap(),
+            ),
+        };
+
+        let arguments = arguments(&target, &desc, Duration::from_millis(1500)).unwrap();
+        let ffmpeg = arguments.ffmpeg.join(" ");
+
+        assert_eq!(arguments.shell.as_deref(), Some("tts"));
+        assert!(ffmpeg.contains("-re -f s16le -ar 24000 -ac 1 -i pipe:0"));
+        assert!(ffmpeg
+            .contains("-re -ss 1.500 -f rawvideo -pix_fmt yuv420p -s 1280x720 -r 30 -i video.yuv"));
+        assert!(ffmpeg.contains("-map 0:a:0"));
+        assert!(ffmpeg.contains("-map 1:v:0"));
+        assert!(ffmpeg.ends_with("-f flv rtmp://localhost/live/key"));
+
+        let two_shells = MediaDescription {
+            audio: Some(AudioDescription::new(InputMode::Shell, "a", 48000, 16, 2).unwrap()),
+            video: Some(VideoDescription::new(InputMode::Shell, "b", 640, 480, 30).unwrap()),
+        };
+        assert!(matches!(
+            super::arguments(&target, &two_shells, Duration::ZERO),
+            Err(RtmpError::Unsupported(_))
+        ));
+        assert!(matches!(
+            super::arguments(&target, &MediaDescription::default(), Duration::ZERO),
+            Err(RtmpError::Unsupported(_))
+        ));
+        assert!(matches!(
+            super::arguments(
+                &target,
+                &audio(InputMode::FFmpeg | InputMode::NoLatency, "a.mp3"),
+                Duration::ZERO
+            ),
+            Err(RtmpError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn lifecycle() {
+        let log = TempPath::new("rtmp", "log");
+
+        let ffmpeg = fake_ffmpeg(&format!(
+            "echo \"$@\" >> '{}'\nexec sleep 30",
+            log.display()
+        ));
+        let streams = RtmpStreams::with_ffmpeg(&*ffmpeg);
+        let target = RtmpTarget::new("rtmp://localhost/live", "key");
+
+        streams
+            .start(CHAT, target.clone(), audio(InputMode::File, "music.raw"))
+            .unwrap();
+        assert!(matches!(
+            streams.start(CHAT, target, audio(InputMode::File, "music.raw")),
+            Err(RtmpError::StreamAlreadyExists)
+        ));
+
+        let first = lines(&log, 1).remove(0);
+        assert!(first.contains("-f s16le -ar 48000 -ac 2 -i music.raw"));
+        assert!(!first.contains("-ss"));
+        assert_eq!(streams.stream_status(CHAT).unwrap(), StreamStatus::Playing);
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(streams.pause(CHAT).unwrap());
+        assert!(!streams.pause(CHAT).unwrap());
+        assert_eq!(streams.stream_status(CHAT).unwrap(), StreamStatus::Paused);
+
+        // resuming seeks to the played time
+        assert!(streams.resume(CHAT).unwrap());
+        assert!(!streams.resume(CHAT).unwrap());
+        assert!(lines(&log, 2)[1].contains("-ss "));
+
+        streams
+            .change_stream(CHAT, audio(InputMode::FFmpeg, "https://example.com/a.mp3"))
+            .unwrap();
+        assert!(lines(&log, 3)[2].contains("-re -i https://example.com/a.mp3"));
+        assert_eq!(streams.played_time(CHAT).unwrap(), 0);
+
+        streams.stop(CHAT).unwrap();
+        assert!(matches!(
+            streams.stream_status(CHAT),
+            Err(RtmpError::StreamNotFound)
+        ));
+    }
+
+    #[test]
+    fn exit_code() {
+        let ffmpeg = fake_ffmpeg("echo 'Connection refused' >&2\nexit 1");
+        let streams = RtmpStreams::with_ffmpeg(&*ffmpeg);
+
+        streams
+            .start(
+                CHAT,
+                RtmpTarget::new("rtmp://localhost/live", "key"),
+                audio(InputMode::File, "music.raw"),
+            )
+            .unwrap();
+
+        let error = wait_for(|| streams.stream_status(CHAT).err());
+        assert!(matches!(
+            error,
+            RtmpError::Exited { code: Some(1), ref stderr } if stderr == "Connection refused"
+        ));
+
+        // the error is reported until the stream is stopped
+        assert!(streams.resume(CHAT).is_err());
+        streams.stop(CHAT).unwrap();
+
+        let missing = RtmpStreams::with_ffmpeg("/nonexistent/ffmpeg");
+        assert!(matches!(
+            missing.start(
+                CHAT,
+                RtmpTarget::new("rtmp://localhost/live", "key"),
+                audio(InputMode::File, "music.raw"),
+            ),
+            Err(RtmpError::FFmpegNotFound)
+        ));
+    }
+}
@@ -0,0 +1,586 @@
+//! HTTP control server built on [`axum`](https://docs.rs/axum).
+//!
+//! Every per-chat method of [`NTgCallPool`] is exposed as a REST endpoint and the callback
+//! events are streamed as JSON text messages over a WebSocket:
+//!
+//! | Method   | Path                      | Body                 | Response              |
+//! |----------|---------------------------|----------------------|-----------------------|
+//! | `GET`    | `/calls`                  |                      | `[GroupCall]`         |
+//! | `POST`   | `/chats/{chat_id}/params` | `MediaDescription`   | params as a string    |
+//! | `POST`   | `/chats/{chat_id}/connect`| `{"params": string}` | `204 No Content`      |
+//! | `PUT`    | `/chats/{chat_id}/stream` | `MediaDescription`   | `204 No Content`      |
+//! | `POST`   | `/chats/{chat_id}/pause`  |                      | `bool`                |
+//! | `POST`   | `/chats/{chat_id}/resume` |                      | `bool`                |
+//! | `POST`   | `/chats/{chat_id}/mute`   |                      | `bool`                |
+//! | `POST`   | `/chats/{chat_id}/unmute` |                      | `bool`                |
+//! | `GET`    | `/chats/{chat_id}/time`   |                      | played time           |
+//! | `GET`    | `/chats/{chat_id}/state`  |                      | `MediaState`          |
+//! | `DELETE` | `/chats/{chat_id}`        |                      | `204 No Content`      |
+//! | `GET`    | `/events`                 | WebSocket upgrade    | stream of [`Event`]s  |
+//!
+//! All the requests need the token either as an `Authorization: Bearer <token>` header or,
+//! for browsers that can't set headers on WebSockets, as an `access_token` query parameter.
+//!
+//! Errors of NTgCalls are returned as `{"error": "<NTgCallError variant>", "message": "..."}`.
+//!
+//! ## Shell commands
+//! A [`MediaDescription`] with [`InputMode::Shell`] makes NTgCalls run its input as a shell
+//! command on the host, so with it anyone holding the token could execute any command.
+//! These descriptions are rejected with `400 Bad Request` unless the server is created with
+//! [`ShellInput::Allow`].
+
+use std::{io, sync::Arc};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Path, Query, Request, State, WebSocketUpgrade,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpListener, sync::broadcast};
+
+use crate::{
+    enums::{InputMode, StreamType},
+    errors::{NTgCallError, NTgCallResult},
+    pool::NTgCallPool,
+    structures::{ChatId, GroupCall, MediaDescription, MediaState},
+};
+
+/// Number of events buffered for each WebSocket before the slow ones start missing events.
+const EVENTS_CAPACITY: usize = 64;
+
+/// Events sent to the clients connected to the `/events` WebSocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A stream ended.
+    StreamEnd {
+        /// Unique identifier of a chat.
+        chat_id: ChatId,
+        /// Type of the ended stream.
+        stream_type: StreamType,
+    },
+    /// The media state of a call changed.
+    Upgrade {
+        /// Unique identifier of a chat.
+        chat_id: ChatId,
+        /// The new media state.
+        state: MediaState,
+    },
+    /// A call got disconnected.
+    Disconnect {
+        /// Unique identifier of a chat.
+        chat_id: ChatId,
+    },
+}
+
+/// Whether the clients can stream the output of shell commands, see the
+/// [module documentation](self#shell-commands).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShellInput {
+    /// Reject the media descriptions with [`InputMode::Shell`].
+    #[default]
+    Reject,
+    /// Accept them, which lets every client holding the token execute commands on the host.
+    Allow,
+}
+
+#[derive(Clone)]
+struct AppState {
+    pool: Arc<NTgCallPool>,
+    token: Arc<str>,
+    shell: ShellInput,
+    events: broadcast::Sender<Event>,
+}
+
+/// Create the router of the control server.
+///
+/// The callbacks of the pool are replaced to forward the events to the WebSocket clients.
+///
+/// ## Parameters
+/// - `pool`: The instances controlled by the server, use [`NTgCallPool::from_instances`]
+///   to expose a single [`NTgCall`](crate::NTgCall).
+/// - `token`: The token the clients must authenticate with.
+/// - `shell`: Whether the clients can run shell commands on the host, see [`ShellInput`].
+///
+/// ## Return
+/// A [`NTgCallError`] in case registering the callbacks failed.
+pub fn router<S: Into<String>>(
+    pool: Arc<NTgCallPool>,
+    token: S,
+    shell: ShellInput,
+) -> NTgCallResult<Router> {
+    let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+
+    // sending only fails when there are no subscribers, in which case the event is just dropped
+    let sender = events.clone();
+    pool.on_stream_end(move |chat_id, stream_type| {
+        let _ = sender.send(Event::StreamEnd {
+            chat_id,
+            stream_type,
+        });
+    })?;
+
+    let sender = events.clone();
+    pool.on_upgrade(move |chat_id, state| {
+        let _ = sender.send(Event::Upgrade { chat_id, state });
+    })?;
+
+    let sender = events.clone();
+    pool.on_disconnect(move |chat_id| {
+        let _ = sender.send(Event::Disconnect { chat_id });
+    })?;
+
+    let state = AppState {
+        pool,
+        token: token.into().into(),
+        shell,
+        events,
+    };
+
+    Ok(Router::new()
+        .route("/calls", get(calls))
+        .route("/chats/{chat_id}", delete(stop))
+        .route("/chats/{chat_id}/params", post(get_params))
+        .route("/chats/{chat_id}/connect", post(connect))
+        .route("/chats/{chat_id}/stream", put(change_stream))
+        .route("/chats/{chat_id}/pause", post(pause))
+        .route("/chats/{chat_id}/resume", post(resume))
+        .route("/chats/{chat_id}/mute", post(mute))
+        .route("/chats/{chat_id}/unmute", post(unmute))
+        .route("/chats/{chat_id}/time", get(played_time))
+        .route("/chats/{chat_id}/state", get(get_state))
+        .route("/events", get(subscribe))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .with_state(state))
+}
+
+/// Serve the control server on the given listener until the process is stopped.
+///
+/// ## Parameters
+/// See [`router`].
+pub async fn serve<S: Into<String>>(
+    listener: TcpListener,
+    pool: Arc<NTgCallPool>,
+    token: S,
+    shell: ShellInput,
+) -> io::Result<()> {
+    let router = router(pool, token, shell).map_err(io::Error::other)?;
+    axum::serve(listener, router).await
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+async fn authenticate(
+    State(state): State<AppState>,
+    Query(query): Query<TokenQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match header.or(query.access_token.as_deref()) {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Compare the tokens without leaking the length of the matching prefix.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Error returned by the handlers.
+enum ApiError {
+    NTgCall(NTgCallError),
+    /// The blocking task running the call panicked.
+    Panicked,
+    /// A media description has a shell command and the server doesn't allow them.
+    ShellInputRejected,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, body) = match self {
+            Self::NTgCall(e) => {
+                let status = match e {
+                    NTgCallError::ConnectionNotFound => StatusCode::NOT_FOUND,
+                    NTgCallError::ConnectionAlreadyExists | NTgCallError::RtmpNeeded => {
+                        StatusCode::CONFLICT
+                    }
+                    NTgCallError::FileNotFound
+                    | NTgCallError::EncoderNotFound
+                    | NTgCallError::FFmpegNotFound
+                    | NTgCallError::ShellError
+                    | NTgCallError::InvalidTransport
+                    | NTgCallError::InvalidInputMode
+                    | NTgCallError::InvalidString => StatusCode::UNPROCESSABLE_ENTITY,
+                    NTgCallError::Destroyed => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+
+                let body = ErrorBody {
+                    error: e.name().to_string(),
+                    message: e.to_string(),
+                };
+
+                (status, body)
+            }
+            Self::Panicked => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorBody {
+                    error: String::from("Panicked"),
+                    message: String::from("The call to NTgCalls panicked"),
+                },
+            ),
+            Self::ShellInputRejected => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody {
+                    error: String::from("ShellInputRejected"),
+                    message: String::from("The server doesn't allow the Shell input mode"),
+                },
+            ),
+        };
+
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Run a call on the blocking thread pool, NTgCalls calls can take a while.
+async fn blocking<T, F>(state: AppState, f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&NTgCallPool) -> NTgCallResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || f(&state.pool))
+        .await
+        .map_err(|_| ApiError::Panicked)?
+        .map_err(ApiError::NTgCall)
+}
+
+/// Check the input modes of a media description against the [`ShellInput`] of the server.
+fn check_shell(state: &AppState, desc: &MediaDescription) -> Result<(), ApiError> {
+    let audio = desc.audio.as_ref().map(|audio| audio.input_mode);
+    let video = desc.video.as_ref().map(|video| video.input_mode);
+
+    match state.shell {
+        ShellInput::Reject
+            if audio
+                .into_iter()
+                .chain(video)
+                .any(|mode| mode.contains(InputMode::Shell)) =>
+        {
+            Err(ApiError::ShellInputRejected)
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn calls(State(state): State<AppState>) -> Result<Json<Vec<GroupCall>>, ApiError> {
+    blocking(state, |pool| pool.calls()).await.map(Json)
+}
+
+async fn get_params(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+    Json(desc): Json<MediaDescription>,
+) -> Result<Json<String>, ApiError> {
+    check_shell(&state, &desc)?;
+    blocking(state, move |pool| pool.get_params(chat_id, desc))
+        .await
+        .map(Json)
+}
+
+#[derive(Deserialize)]
+struct ConnectBody {
+    params: String,
+}
+
+async fn connect(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+    Json(body): Json<ConnectBody>,
+) -> Result<StatusCode, ApiError> {
+    blocking(state, move |pool| pool.connect(chat_id, body.params)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn change_stream(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+    Json(desc): Json<MediaDescription>,
+) -> Result<StatusCode, ApiError> {
+    check_shell(&state, &desc)?;
+    blocking(state, move |pool| pool.change_stream(chat_id, desc)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn stop(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<StatusCode, ApiError> {
+    blocking(state, move |pool| pool.stop(chat_id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn pause(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<bool>, ApiError> {
+    blocking(state, move |pool| pool.pause(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn resume(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<bool>, ApiError> {
+    blocking(state, move |pool| pool.resume(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn mute(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<bool>, ApiError> {
+    blocking(state, move |pool| pool.mute(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn unmute(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<bool>, ApiError> {
+    blocking(state, move |pool| pool.unmute(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn played_time(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<i64>, ApiError> {
+    blocking(state, move |pool| pool.played_time(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn get_state(
+    State(state): State<AppState>,
+    Path(chat_id): Path<ChatId>,
+) -> Result<Json<MediaState>, ApiError> {
+    blocking(state, move |pool| pool.get_state(chat_id))
+        .await
+        .map(Json)
+}
+
+async fn subscribe(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    let receiver = state.events.subscribe();
+    upgrade.on_upgrade(move |socket| forward_events(socket, receiver))
+}
+
+/// Send the events to the socket until either side is closed.
+async fn forward_events(mut socket: WebSocket, mut receiver: broadcast::Receiver<Event>) {
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    // the client is too slow, skip the missed events
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let text = serde_json::to_string(&event).expect("events are always serializable");
+
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                // the messages sent by the client are ignored
+                if !matches!(message, Some(Ok(_))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::NTgCall;
+
+    #[test]
+    fn event_format() {
+        let event = Event::Upgrade {
+            chat_id: ChatId::from_bot_api(-1001),
+            state: MediaState {
+                muted: true,
+                video_paused: false,
+                video_stopped: false,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "event": "upgrade",
+                "chat_id": -1001,
+                "state": { "muted": true, "video_paused": false, "video_stopped": false },
+            })
+        );
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    /// Called by the sequential test of the crate, which owns the native instances.
+    pub(crate) fn endpoints() {
+        let pool = Arc::new(NTgCallPool::from_instances(vec![NTgCall::new()]));
+        let router = router(pool.clone(), "secret", ShellInput::Reject).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let request = |router: &Router, method: Method, uri: &str, body: Option<Value>| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, "Bearer secret");
+
+            if body.is_some() {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+            }
+
+            let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+            let request = request.body(body).unwrap();
+
+            runtime.block_on(async {
+                let response = router.clone().oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = response.into_body().collect().await.unwrap().to_bytes();
+                (status, serde_json::from_slice::<Value>(&bytes).ok())
+            })
+        };
+
+        // every request needs the token
+        let unauthenticated = Request::get("/calls").body(Body::empty()).unwrap();
+        let response = runtime
+            .block_on(router.clone().oneshot(unauthenticated))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let desc = serde_json::to_value(MediaDescription::default()).unwrap();
+        let (status, params) = request(&router, Method::POST, "/chats/-1001/params", Some(desc));
+        assert_eq!(status, StatusCode::OK);
+        assert!(params.unwrap().is_string());
+
+        // shell commands are only run when the server allows them
+        let shell = json!({
+            "audio": {
+                "input_mode": "Shell",
+                "input": "rm -rf ~",
+                "sample_rate": 48000,
+                "bits_per_sample": 16,
+                "channel_count": 2,
+            },
+        });
+        let (status, error) = request(
+            &router,
+            Method::PUT,
+            "/chats/-1001/stream",
+            Some(shell.clone()),
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.unwrap()["error"], json!("ShellInputRejected"));
+        let (status, _) = request(&router, Method::POST, "/chats/-1002/params", Some(shell));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = request(
+            &router,
+            Method::POST,
+            "/chats/-1001/connect",
+            Some(json!({ "params": "{}" })),
+        );
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, muted) = request(&router, Method::POST, "/chats/-1001/mute", None);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(muted, Some(json!(true)));
+
+        // the token can also be passed as a query parameter
+        let (status, state) = request(
+            &router,
+            Method::GET,
+            "/chats/-1001/state?access_token=secret",
+            None,
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state.unwrap()["muted"], json!(true));
+
+        let (status, error) = request(&router, Method::POST, "/chats/42/pause", None);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.unwrap()["error"], json!("ConnectionNotFound"));
+
+        let (status, _) = request(&router, Method::DELETE, "/chats/-1001", None);
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (_, calls) = request(&router, Method::GET, "/calls", None);
+        assert_eq!(calls, Some(json!([])));
+
+        // a second router takes over the callbacks of the pool
+        let router = super::router(pool, "secret", ShellInput::Allow).unwrap();
+        let desc = json!({
+            "audio": {
+                "input_mode": "Shell",
+                "input": "cat music.raw",
+                "sample_rate": 48000,
+                "bits_per_sample": 16,
+                "channel_count": 2,
+            },
+        });
+        let (status, _) = request(&router, Method::POST, "/chats/-1001/params", Some(desc));
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = request(&router, Method::DELETE, "/chats/-1001", None);
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+}
@@ -1,16 +1,24 @@
-use std::ffi::CString;
+use std::{
+    ffi::{CStr, CString},
+    fmt::{self, Display},
+    num::ParseIntError,
+    str::FromStr,
+};
 
 use libntgcalls_sys::{
-    ntg_audio_description_struct, ntg_input_mode_enum, ntg_video_description_struct,
+    ntg_audio_description_struct, ntg_group_call_struct, ntg_media_state_struct,
+    ntg_video_description_struct,
 };
 
 use crate::{
     enums::{InputMode, StreamStatus},
+    errors::{IntoCStringError, NTgCallError, NTgCallResult},
     utils::IntoCString,
 };
 
 /// Media Configuration for the Stream.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MediaDescription {
     /// Audio configuration for the stream.
     pub audio: Option<AudioDescription>,
@@ -18,12 +26,28 @@ pub struct MediaDescription {
     pub video: Option<VideoDescription>,
 }
 
+impl MediaDescription {
+    /// Check the input modes before they are passed to NTgCalls, which doesn't validate them.
+    pub(crate) fn check_input_modes(&self) -> NTgCallResult<()> {
+        let audio = self.audio.as_ref().map(|audio| audio.input_mode);
+        let video = self.video.as_ref().map(|video| video.input_mode);
+
+        if audio.into_iter().chain(video).all(InputMode::is_valid) {
+            Ok(())
+        } else {
+            Err(NTgCallError::InvalidInputMode)
+        }
+    }
+}
+
 /// Stream’s Audio Configuration
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AudioDescription {
     /// The input mode for audio.
     pub input_mode: InputMode,
     /// The input media source.
+    #[cfg_attr(feature = "serde", serde(with = "c_string"))]
     input: CString,
     /// Audio sample rate (0-96000, max allowed by PCM16L).
     pub sample_rate: u32,
@@ -34,26 +58,31 @@ pub struct AudioDescription {
 }
 
 impl AudioDescription {
-    /// Create a new [`AudioDescription`]
+    /// Create a new [`AudioDescription`], failing if the input contains a nul byte.
     pub fn new<S: IntoCString>(
         input_mode: InputMode,
         input: S,
         sample_rate: u32,
         bits_per_sample: u8,
         channel_count: u8,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, IntoCStringError> {
+        Ok(Self {
             input_mode,
-            input: input.into_c_string(),
+            input: input.try_into_c_string()?,
             sample_rate,
             bits_per_sample,
             channel_count,
-        }
+        })
+    }
+
+    /// The input media source.
+    pub fn input(&self) -> &CStr {
+        &self.input
     }
 
     pub(crate) fn to_ffi(&self) -> ntg_audio_description_struct {
         ntg_audio_description_struct {
-            inputMode: self.input_mode as ntg_input_mode_enum,
+            inputMode: self.input_mode.into(),
             input: self.input.as_ptr(),
             sampleRate: self.sample_rate,
             bitsPerSample: self.bits_per_sample,
@@ -64,10 +93,12 @@ impl AudioDescription {
 
 /// Stream’s Video Configuration
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VideoDescription {
     /// The input mode for video.
     pub input_mode: InputMode,
     /// The input media source.
+    #[cfg_attr(feature = "serde", serde(with = "c_string"))]
     input: CString,
     /// Video width in pixels.
     pub width: u16,
@@ -78,26 +109,31 @@ pub struct VideoDescription {
 }
 
 impl VideoDescription {
-    /// Create a new [`VideoDescription`]
+    /// Create a new [`VideoDescription`], failing if the input contains a nul byte.
     pub fn new<S: IntoCString>(
         input_mode: InputMode,
         input: S,
         width: u16,
         height: u16,
         fps: u8,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, IntoCStringError> {
+        Ok(Self {
             input_mode,
-            input: input.into_c_string(),
+            input: input.try_into_c_string()?,
             width,
             height,
             fps,
-        }
+        })
+    }
+
+    /// The input media source.
+    pub fn input(&self) -> &CStr {
+        &self.input
     }
 
     pub(crate) fn to_ffi(&self) -> ntg_video_description_struct {
         ntg_video_description_struct {
-            inputMode: self.input_mode as ntg_input_mode_enum,
+            inputMode: self.input_mode.into(),
             input: self.input.as_ptr(),
             width: self.width,
             height: self.height,
@@ -106,18 +142,165 @@ impl VideoDescription {
     }
 }
 
+/// Unique identifier of a chat, in the format of the Bot API used by NTgCalls.
+///
+/// The Bot API ids of basic groups are negated and the ids of channels and supergroups
+/// are prefixed with `-100`, use [`ChatId::from_chat`] and [`ChatId::from_channel`] with the
+/// ids of MTProto peers instead of converting them by hand:
+///
+/// ```
+/// use ntgcalls::structures::ChatId;
+///
+/// let chat_id = ChatId::from_channel(1234567890);
+/// assert_eq!(chat_id.bot_api(), -1001234567890);
+/// assert_eq!(chat_id.channel_id(), Some(1234567890));
+/// assert_eq!(chat_id, "-1001234567890".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ChatId(i64);
+
+impl ChatId {
+    /// Bot API ids of channels are below this value.
+    const CHANNEL_OFFSET: i64 = -1_000_000_000_000;
+
+    /// Create a [`ChatId`] from a Bot API id, like `-1001234567890`.
+    pub const fn from_bot_api(id: i64) -> Self {
+        Self(id)
+    }
+
+    /// Create a [`ChatId`] from the id of an MTProto user.
+    pub const fn from_user(user_id: i64) -> Self {
+        Self(user_id)
+    }
+
+    /// Create a [`ChatId`] from the id of an MTProto chat, a basic group.
+    ///
+    /// # Panics
+    /// If `chat_id` is not the id of a basic group, see [`ChatId::checked_from_chat`].
+    pub const fn from_chat(chat_id: i64) -> Self {
+        match Self::checked_from_chat(chat_id) {
+            Some(chat_id) => chat_id,
+            None => panic!("the id is out of the range of the basic groups"),
+        }
+    }
+
+    /// Create a [`ChatId`] from the id of an MTProto chat, a basic group.
+    ///
+    /// [`None`] if the id is not positive or too big for a basic group.
+    pub const fn checked_from_chat(chat_id: i64) -> Option<Self> {
+        if 0 < chat_id && chat_id < -Self::CHANNEL_OFFSET {
+            Some(Self(-chat_id))
+        } else {
+            None
+        }
+    }
+
+    /// Create a [`ChatId`] from the id of an MTProto channel or supergroup.
+    ///
+    /// # Panics
+    /// If `channel_id` is not the id of a channel, see [`ChatId::checked_from_channel`].
+    pub const fn from_channel(channel_id: i64) -> Self {
+        match Self::checked_from_channel(channel_id) {
+            Some(chat_id) => chat_id,
+            None => panic!("the id is out of the range of the channels"),
+        }
+    }
+
+    /// Create a [`ChatId`] from the id of an MTProto channel or supergroup.
+    ///
+    /// [`None`] if the id is not positive or the Bot API id would overflow.
+    pub const fn checked_from_channel(channel_id: i64) -> Option<Self> {
+        if channel_id <= 0 {
+            return None;
+        }
+
+        match Self::CHANNEL_OFFSET.checked_sub(channel_id) {
+            Some(id) => Some(Self(id)),
+            None => None,
+        }
+    }
+
+    /// The Bot API id.
+    pub const fn bot_api(self) -> i64 {
+        self.0
+    }
+
+    /// The id of the MTProto user, [`None`] if the chat is not a user.
+    pub fn user_id(self) -> Option<i64> {
+        (self.0 > 0).then_some(self.0)
+    }
+
+    /// The id of the MTProto chat, [`None`] if the chat is not a basic group.
+    pub fn chat_id(self) -> Option<i64> {
+        (Self::CHANNEL_OFFSET < self.0 && self.0 < 0).then_some(-self.0)
+    }
+
+    /// The id of the MTProto channel, [`None`] if the chat is not a channel or a supergroup.
+    pub fn channel_id(self) -> Option<i64> {
+        (self.0 < Self::CHANNEL_OFFSET).then_some(Self::CHANNEL_OFFSET - self.0)
+    }
+
+    pub(crate) fn from_ffi(chat_id: i64) -> Self {
+        Self(chat_id)
+    }
+
+    pub(crate) fn to_ffi(self) -> i64 {
+        self.0
+    }
+}
+
+impl From<ChatId> for i64 {
+    /// Convert to a Bot API id, see [`ChatId::bot_api`].
+    fn from(value: ChatId) -> Self {
+        value.bot_api()
+    }
+}
+
+impl Display for ChatId {
+    /// The Bot API id.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for ChatId {
+    type Err = ParseIntError;
+
+    /// Parse a Bot API id.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self::from_bot_api)
+    }
+}
+
 /// Info about a group call
 #[derive(Debug, Clone)]
-#[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GroupCall {
     /// Unique identifier of a chat.
-    pub chat_id: i64,
+    pub chat_id: ChatId,
     /// Stream’s Status.
     pub stream_status: StreamStatus,
 }
 
+impl GroupCall {
+    pub(crate) fn from_ffi(call: &ntg_group_call_struct) -> Self {
+        Self {
+            chat_id: ChatId::from_ffi(call.chatId),
+            stream_status: StreamStatus::from_ffi(call.status),
+        }
+    }
+}
+
 /// GroupCall MTProto Configurations.
-#[derive(Debug, Clone)]
+///
+/// Send the changes to Telegram with [`EditParticipantParams`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MediaState {
     /// Indicates whether the media stream is muted.
     pub muted: bool,
@@ -127,19 +310,262 @@ pub struct MediaState {
     pub video_stopped: bool,
 }
 
+impl MediaState {
+    pub(crate) fn from_ffi(state: ntg_media_state_struct) -> Self {
+        Self {
+            muted: state.muted,
+            video_paused: state.videoPaused,
+            video_stopped: state.videoStopped,
+        }
+    }
+}
+
+/// Parameters of [`phone.editGroupCallParticipant`] for a [`MediaState`].
+///
+/// Only the flags that are [`Some`] must be sent, use [`EditParticipantParams::diff`]
+/// to send only what changed since the previous state.
+///
+/// [`phone.editGroupCallParticipant`]: https://core.telegram.org/method/phone.editGroupCallParticipant
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditParticipantParams {
+    /// The `muted` parameter, bit [`EditParticipantParams::MUTED`] of the flags.
+    pub muted: Option<bool>,
+    /// The `video_stopped` parameter, bit [`EditParticipantParams::VIDEO_STOPPED`] of the flags.
+    pub video_stopped: Option<bool>,
+    /// The `video_paused` parameter, bit [`EditParticipantParams::VIDEO_PAUSED`] of the flags.
+    pub video_paused: Option<bool>,
+}
+
+impl EditParticipantParams {
+    /// Flag of the `muted` parameter.
+    pub const MUTED: u32 = 1 << 0;
+    /// Flag of the `video_stopped` parameter.
+    pub const VIDEO_STOPPED: u32 = 1 << 3;
+    /// Flag of the `video_paused` parameter.
+    pub const VIDEO_PAUSED: u32 = 1 << 4;
+
+    /// The parameters that set every field of the state.
+    pub fn from_state(state: &MediaState) -> Self {
+        Self {
+            muted: Some(state.muted),
+            video_stopped: Some(state.video_stopped),
+            video_paused: Some(state.video_paused),
+        }
+    }
+
+    /// The parameters that set only the fields that changed from `previous` to `current`.
+    pub fn diff(previous: &MediaState, current: &MediaState) -> Self {
+        let changed = |previous: bool, current: bool| (previous != current).then_some(current);
+
+        Self {
+            muted: changed(previous.muted, current.muted),
+            video_stopped: changed(previous.video_stopped, current.video_stopped),
+            video_paused: changed(previous.video_paused, current.video_paused),
+        }
+    }
+
+    /// Whether there is nothing to send.
+    pub fn is_empty(&self) -> bool {
+        self.flags() == 0
+    }
+
+    /// The `flags` field of the request, the parameters of the other flags must not be sent.
+    pub fn flags(&self) -> u32 {
+        [
+            (self.muted, Self::MUTED),
+            (self.video_stopped, Self::VIDEO_STOPPED),
+            (self.video_paused, Self::VIDEO_PAUSED),
+        ]
+        .into_iter()
+        .filter(|(value, _)| value.is_some())
+        .fold(0, |flags, (_, flag)| flags | flag)
+    }
+}
+
+impl From<&MediaState> for EditParticipantParams {
+    fn from(value: &MediaState) -> Self {
+        Self::from_state(value)
+    }
+}
+
+impl From<MediaState> for EditParticipantParams {
+    fn from(value: MediaState) -> Self {
+        Self::from_state(&value)
+    }
+}
+
+/// (De)serialize a [`CString`] as a string when it's valid UTF-8 and as bytes otherwise.
+#[cfg(feature = "serde")]
+mod c_string {
+    use std::ffi::CString;
+
+    use serde::{de, Deserializer, Serializer};
+
+    /// Non human readable formats, which may not be self describing, always get the bytes.
+    /// Human readable ones get a string when it's valid UTF-8.
+    pub fn serialize<S: Serializer>(value: &CString, serializer: S) -> Result<S::Ok, S::Error> {
+        match value.to_str() {
+            Ok(s) if serializer.is_human_readable() => serializer.serialize_str(s),
+            _ => serializer.serialize_bytes(value.as_bytes()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CString, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = CString;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string or bytes without interior nul bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                self.visit_bytes(v.as_bytes())
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                self.visit_byte_buf(v.into_bytes())
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                CString::new(v).map_err(E::custom)
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                CString::new(v).map_err(E::custom)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+
+                CString::new(bytes).map_err(de::Error::custom)
+            }
+        }
+
+        // self describing formats call the visitor with what they have, strings included
+        deserializer.deserialize_byte_buf(Visitor)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use libntgcalls_sys::{ntg_group_call_struct, ntg_stream_status_enum};
-
     use super::*;
-    use std::mem::size_of;
 
     #[test]
-    fn group_call_is_same_as_ntg_group_call_struct() {
-        assert_eq!(size_of::<GroupCall>(), size_of::<ntg_group_call_struct>());
+    fn chat_id_conversions() {
+        let channel = ChatId::from_channel(1234567890);
+        assert_eq!(channel, ChatId::from_bot_api(-1001234567890));
+        assert_eq!(channel.channel_id(), Some(1234567890));
+        assert_eq!(channel.chat_id(), None);
+        assert_eq!(channel.user_id(), None);
+
+        let chat = ChatId::from_chat(123456);
+        assert_eq!(chat.bot_api(), -123456);
+        assert_eq!(chat.chat_id(), Some(123456));
+        assert_eq!(chat.channel_id(), None);
+
+        let user = ChatId::from_user(777);
+        assert_eq!(i64::from(user), 777);
+        assert_eq!(user.user_id(), Some(777));
+        assert_eq!(user.chat_id(), None);
+
+        assert_eq!(ChatId::checked_from_channel(i64::MAX), None);
+        assert_eq!(ChatId::checked_from_channel(0), None);
+        assert_eq!(ChatId::checked_from_chat(i64::MIN), None);
+        assert_eq!(ChatId::checked_from_chat(1_000_000_000_000), None);
+        assert_eq!(ChatId::checked_from_chat(123456), Some(chat));
+
+        assert_eq!(channel.to_string(), "-1001234567890");
+        assert_eq!("-123456".parse(), Ok(chat));
+        assert!("chat".parse::<ChatId>().is_err());
+    }
+
+    #[test]
+    fn edit_participant_params() {
+        let previous = MediaState {
+            muted: false,
+            video_paused: false,
+            video_stopped: true,
+        };
+        let current = MediaState {
+            muted: true,
+            video_paused: false,
+            video_stopped: false,
+        };
+
+        let all = EditParticipantParams::from(current);
+        assert_eq!(all.flags(), 0b11001);
+        assert_eq!(all.video_paused, Some(false));
+
+        let diff = EditParticipantParams::diff(&previous, &current);
+        assert_eq!(
+            diff,
+            EditParticipantParams {
+                muted: Some(true),
+                video_stopped: Some(false),
+                video_paused: None,
+            }
+        );
         assert_eq!(
-            size_of::<StreamStatus>(),
-            size_of::<ntg_stream_status_enum>()
+            diff.flags(),
+            EditParticipantParams::MUTED | EditParticipantParams::VIDEO_STOPPED
         );
+
+        assert!(EditParticipantParams::diff(&current, &current).is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let desc = MediaDescription {
+            audio: Some(
+                AudioDescription::new(InputMode::FFmpeg, "music.mp3", 48000, 16, 2).unwrap(),
+            ),
+            video: Some(
+                VideoDescription::new(
+                    InputMode::Shell,
+                    "ffmpeg -i video.mp4 -f rawvideo -",
+                    1280,
+                    720,
+                    30,
+                )
+                .unwrap(),
+            ),
+        };
+
+        let json = serde_json::to_string(&desc).unwrap();
+        let de: MediaDescription = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&de).unwrap());
+        assert_eq!(de.audio.unwrap().input.to_str(), Ok("music.mp3"));
+
+        // non UTF-8 inputs are kept as bytes
+        let audio = AudioDescription::new(InputMode::File, c"\xff.raw", 48000, 16, 2).unwrap();
+        let json = serde_json::to_string(&audio).unwrap();
+        let de: AudioDescription = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.input, audio.input);
+
+        // interior nul bytes are rejected
+        let json = json.replace("255,", "0,");
+        assert!(serde_json::from_str::<AudioDescription>(&json).is_err());
+
+        // formats that aren't self describing
+        let bytes = bincode::serialize(&desc).unwrap();
+        let de: MediaDescription = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(bincode::serialize(&de).unwrap(), bytes);
+        assert_eq!(
+            de.video.unwrap().input.to_str(),
+            Ok("ffmpeg -i video.mp4 -f rawvideo -")
+        );
+
+        let bytes = bincode::serialize(&audio).unwrap();
+        let de: AudioDescription = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(de.input, audio.input);
     }
 }
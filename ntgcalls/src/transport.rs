@@ -0,0 +1,140 @@
+//! A common interface for the ways a stream can reach a chat.
+//!
+//! Group calls are streamed with NTgCalls, while the chats that only accept RTMP
+//! (see [`NTgCallError::RtmpNeeded`]) are streamed with [`RtmpStreams`](crate::rtmp::RtmpStreams).
+//! Code written against [`StreamTransport`] controls both the same way.
+
+use crate::{
+    enums::StreamStatus,
+    errors::{NTgCallError, NTgCallResult, RtmpError},
+    pool::NTgCallPool,
+    rtmp::RtmpStreams,
+    structures::{ChatId, GroupCall, MediaDescription},
+    NTgCall,
+};
+
+/// The playback controls of a stream, independent of how the stream reaches the chat.
+///
+/// The methods mirror the [`NTgCall`] methods with the same name.
+pub trait StreamTransport {
+    /// Error of the transport.
+    type Error;
+
+    /// Replace the source of the stream. See [`NTgCall::change_stream`].
+    fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> Result<(), Self::Error>;
+
+    /// Pause the stream, returns whether it was playing. See [`NTgCall::pause`].
+    fn pause(&self, chat_id: ChatId) -> Result<bool, Self::Error>;
+
+    /// Resume the stream, returns whether it was paused. See [`NTgCall::resume`].
+    fn resume(&self, chat_id: ChatId) -> Result<bool, Self::Error>;
+
+    /// Stop the stream. See [`NTgCall::stop`].
+    fn stop(&self, chat_id: ChatId) -> Result<(), Self::Error>;
+
+    /// Played time of the stream. See [`NTgCall::played_time`].
+    fn played_time(&self, chat_id: ChatId) -> Result<i64, Self::Error>;
+
+    /// Status of the stream. See [`NTgCall::calls`].
+    fn stream_status(&self, chat_id: ChatId) -> Result<StreamStatus, Self::Error>;
+}
+
+/// Status of the chat in the calls of an instance.
+fn find_status(
+    calls: NTgCallResult<Vec<GroupCall>>,
+    chat_id: ChatId,
+) -> NTgCallResult<StreamStatus> {
+    calls?
+        .into_iter()
+        .find(|call| call.chat_id == chat_id)
+        .map(|call| call.stream_status)
+        .ok_or(NTgCallError::ConnectionNotFound)
+}
+
+impl StreamTransport for NTgCall {
+    type Error = NTgCallError;
+
+    fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<()> {
+        NTgCall::change_stream(self, chat_id, desc)
+    }
+
+    fn pause(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        NTgCall::pause(self, chat_id)
+    }
+
+    fn resume(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        NTgCall::resume(self, chat_id)
+    }
+
+    fn stop(&self, chat_id: ChatId) -> NTgCallResult<()> {
+        NTgCall::stop(self, chat_id)
+    }
+
+    fn played_time(&self, chat_id: ChatId) -> NTgCallResult<i64> {
+        NTgCall::played_time(self, chat_id)
+    }
+
+    fn stream_status(&self, chat_id: ChatId) -> NTgCallResult<StreamStatus> {
+        find_status(self.calls(), chat_id)
+    }
+}
+
+impl StreamTransport for NTgCallPool {
+    type Error = NTgCallError;
+
+    fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> NTgCallResult<()> {
+        NTgCallPool::change_stream(self, chat_id, desc)
+    }
+
+    fn pause(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        NTgCallPool::pause(self, chat_id)
+    }
+
+    fn resume(&self, chat_id: ChatId) -> NTgCallResult<bool> {
+        NTgCallPool::resume(self, chat_id)
+    }
+
+    fn stop(&self, chat_id: ChatId) -> NTgCallResult<()> {
+        NTgCallPool::stop(self, chat_id)
+    }
+
+    fn played_time(&self, chat_id: ChatId) -> NTgCallResult<i64> {
+        NTgCallPool::played_time(self, chat_id)
+    }
+
+    fn stream_status(&self, chat_id: ChatId) -> NTgCallResult<StreamStatus> {
+        let call = self
+            .instance_for(chat_id)
+            .ok_or(NTgCallError::ConnectionNotFound)?;
+
+        find_status(call.calls(), chat_id)
+    }
+}
+
+impl StreamTransport for RtmpStreams {
+    type Error = RtmpError;
+
+    fn change_stream(&self, chat_id: ChatId, desc: MediaDescription) -> Result<(), RtmpError> {
+        RtmpStreams::change_stream(self, chat_id, desc)
+    }
+
+    fn pause(&self, chat_id: ChatId) -> Result<bool, RtmpError> {
+        RtmpStreams::pause(self, chat_id)
+    }
+
+    fn resume(&self, chat_id: ChatId) -> Result<bool, RtmpError> {
+        RtmpStreams::resume(self, chat_id)
+    }
+
+    fn stop(&self, chat_id: ChatId) -> Result<(), RtmpError> {
+        RtmpStreams::stop(self, chat_id)
+    }
+
+    fn played_time(&self, chat_id: ChatId) -> Result<i64, RtmpError> {
+        RtmpStreams::played_time(self, chat_id)
+    }
+
+    fn stream_status(&self, chat_id: ChatId) -> Result<StreamStatus, RtmpError> {
+        RtmpStreams::stream_status(self, chat_id)
+    }
+}
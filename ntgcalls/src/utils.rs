@@ -1,43 +1,202 @@
-use std::ffi::{CStr, CString};
+use std::{
+    env,
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::errors::IntoCStringError;
 
 /// A helper trait to convert multiple strings type to CString to be used with ntg library
-pub trait IntoCString {
-    /// convert the string to CString, this operation shouldn't fail.
-    fn into_c_string(self) -> CString;
+///
+/// On Unix paths and OS strings are converted as they are, so inputs that are not valid
+/// UTF-8 can be used. On other platforms they must be valid Unicode.
+pub trait IntoCString: Sized {
+    /// convert the string to CString, failing if it contains a nul byte.
+    ///
+    /// ## Return
+    /// A [`IntoCStringError`] in case of failure.
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError>;
 }
 
 impl IntoCString for String {
-    fn into_c_string(self) -> CString {
-        match CString::new(self) {
-            Ok(s) => s,
-            Err(r) => {
-                let i = r.nul_position();
-                CString::new(&r.into_vec()[0..i]).unwrap()
-            }
-        }
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        Ok(CString::new(self)?)
     }
 }
 
 impl IntoCString for &str {
-    fn into_c_string(self) -> CString {
-        match CString::new(self) {
-            Ok(s) => s,
-            Err(r) => {
-                let i = r.nul_position();
-                CString::new(&r.into_vec()[0..i]).unwrap()
-            }
-        }
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        Ok(CString::new(self)?)
     }
 }
 
 impl IntoCString for CString {
-    fn into_c_string(self) -> CString {
-        self
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        Ok(self)
     }
 }
 
 impl IntoCString for &CStr {
-    fn into_c_string(self) -> CString {
-        CString::from(self)
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        Ok(CString::from(self))
+    }
+}
+
+impl IntoCString for &PathBuf {
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        self.as_path().try_into_c_string()
+    }
+}
+
+impl IntoCString for PathBuf {
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        self.into_os_string().try_into_c_string()
+    }
+}
+
+impl IntoCString for &Path {
+    fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+        self.as_os_str().try_into_c_string()
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        ffi::{CString, OsStr, OsString},
+        os::unix::ffi::{OsStrExt, OsStringExt},
+    };
+
+    use super::IntoCString;
+    use crate::errors::IntoCStringError;
+
+    impl IntoCString for &OsStr {
+        fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+            Ok(CString::new(self.as_bytes())?)
+        }
+    }
+
+    impl IntoCString for OsString {
+        fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+            Ok(CString::new(self.into_vec())?)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod not_unix {
+    use std::ffi::{CString, OsStr, OsString};
+
+    use super::IntoCString;
+    use crate::errors::IntoCStringError;
+
+    impl IntoCString for &OsStr {
+        fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+            self.to_str()
+                .ok_or_else(|| IntoCStringError::NotUnicode(self.to_owned()))?
+                .try_into_c_string()
+        }
+    }
+
+    impl IntoCString for OsString {
+        fn try_into_c_string(self) -> Result<CString, IntoCStringError> {
+            self.into_string()
+                .map_err(IntoCStringError::NotUnicode)?
+                .try_into_c_string()
+        }
+    }
+}
+
+/// A unique path in the temporary directory for the files created by this process.
+pub(crate) fn temp_path(kind: &str, extension: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    env::temp_dir().join(format!(
+        "ntgcalls-{kind}-{}-{}.{extension}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Find the `ffmpeg` executable in the directories of the `PATH` environment variable.
+pub fn find_ffmpeg() -> Option<PathBuf> {
+    let name = if cfg!(windows) {
+        "ffmpeg.exe"
+    } else {
+        "ffmpeg"
+    };
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{fs, ops::Deref};
+
+    use super::*;
+
+    /// A [`temp_path`] removed when dropped, so the tests clean up even when they fail.
+    pub(crate) struct TempPath(PathBuf);
+
+    impl TempPath {
+        pub(crate) fn new(kind: &str, extension: &str) -> Self {
+            Self(temp_path(kind, extension))
+        }
+    }
+
+    impl Deref for TempPath {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn nul_bytes() {
+        assert_eq!("a.raw".try_into_c_string().unwrap().as_bytes(), b"a.raw");
+
+        let error = String::from("a.raw\0.mp4").try_into_c_string().unwrap_err();
+        let IntoCStringError::Nul(error) = error else {
+            panic!("expected a nul error, got {error:?}");
+        };
+        assert_eq!(error.nul_position(), 5);
+        assert!(c"a.raw".try_into_c_string().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        // not valid UTF-8
+        let name = OsStr::from_bytes(b"music-\xff.raw");
+        assert_eq!(
+            name.try_into_c_string().unwrap().as_bytes(),
+            b"music-\xff.raw"
+        );
+
+        let path = Path::new("/tmp").join(name);
+        assert_eq!(
+            path.as_path().try_into_c_string().unwrap().as_bytes(),
+            b"/tmp/music-\xff.raw"
+        );
+        assert_eq!(
+            (&path).try_into_c_string(),
+            path.clone().try_into_c_string()
+        );
+
+        assert!(PathBuf::from(OsStr::from_bytes(b"a\0b"))
+            .try_into_c_string()
+            .is_err());
     }
 }